@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use tower_lsp::lsp_types::{TextEdit, Url, WorkspaceEdit};
+
+use crate::position::{self, Encoding};
+use crate::wikilink::Delimiters;
+
+/// The command that inserts or refreshes a "Backlinks" section listing the notes linking
+/// to the current one. Its argument is the note's URI.
+pub const INSERT_BACKLINKS_COMMAND: &str = "notemancy.insertBacklinks";
+
+const BACKLINKS_START: &str = "<!-- backlinks -->";
+const BACKLINKS_END: &str = "<!-- /backlinks -->";
+
+/// Builds the backlinks section between its markers: a heading and one
+/// `[[path | title]]` bullet per linking note, given as `(vpath, title)` pairs.
+pub fn backlinks_text(notes: &[(String, String)], delimiters: &Delimiters) -> String {
+    let mut section = format!("{}\n## Backlinks\n\n", BACKLINKS_START);
+    for (vpath, title) in notes {
+        section.push_str(&format!("- {}\n", delimiters.link(vpath, Some(title))));
+    }
+    section.push_str(BACKLINKS_END);
+    section
+}
+
+/// Returns the byte range of an existing backlinks section, markers included.
+fn existing_section(text: &str) -> Option<Range<usize>> {
+    let start = text.find(BACKLINKS_START)?;
+    let end = start + text[start..].find(BACKLINKS_END)? + BACKLINKS_END.len();
+    Some(start..end)
+}
+
+/// Builds the edit that replaces the note's existing backlinks section with a fresh one
+/// or, when it has none, appends one after a blank line at the end of the note.
+pub fn backlinks_edit(
+    uri: &Url,
+    text: &str,
+    notes: &[(String, String)],
+    delimiters: &Delimiters,
+    encoding: Encoding,
+) -> WorkspaceEdit {
+    let (range, new_text) = match existing_section(text) {
+        Some(range) => (range, backlinks_text(notes, delimiters)),
+        None => {
+            let separator = if text.is_empty() || text.ends_with("\n\n") {
+                ""
+            } else if text.ends_with('\n') {
+                "\n"
+            } else {
+                "\n\n"
+            };
+            (
+                text.len()..text.len(),
+                format!("{}{}\n", separator, backlinks_text(notes, delimiters)),
+            )
+        }
+    };
+    let mut changes = HashMap::new();
+    changes.insert(
+        uri.clone(),
+        vec![TextEdit {
+            range: position::offset_range(text, &range, encoding),
+            new_text,
+        }],
+    );
+    WorkspaceEdit {
+        changes: Some(changes),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_lsp::lsp_types::Position;
+
+    #[test]
+    fn test_backlinks_edit() {
+        let uri = Url::parse("file:///vault/note.md").unwrap();
+        let notes = vec![
+            ("a.md".to_string(), "Alpha".to_string()),
+            ("dir/b.md".to_string(), "Beta".to_string()),
+        ];
+        let edit = backlinks_edit(
+            &uri,
+            "# Note\nBody",
+            &notes,
+            &Delimiters::default(),
+            Encoding::default(),
+        );
+        let edits = &edit.changes.unwrap()[&uri];
+        assert_eq!(edits[0].range.start, Position::new(1, 4));
+        assert_eq!(
+            edits[0].new_text,
+            "\n\n<!-- backlinks -->\n## Backlinks\n\n- [[a.md | Alpha]]\n\
+             - [[dir/b.md | Beta]]\n<!-- /backlinks -->\n"
+        );
+
+        let text = "# Note\n\n<!-- backlinks -->\n## Backlinks\n\n- [[old.md | Old]]\n\
+                    <!-- /backlinks -->\n";
+        let edit = backlinks_edit(
+            &uri,
+            text,
+            &notes[..1],
+            &Delimiters::default(),
+            Encoding::default(),
+        );
+        let edits = &edit.changes.unwrap()[&uri];
+        assert_eq!(edits[0].range.start, Position::new(2, 0));
+        assert_eq!(edits[0].range.end, Position::new(6, 19));
+        assert_eq!(
+            edits[0].new_text,
+            "<!-- backlinks -->\n## Backlinks\n\n- [[a.md | Alpha]]\n<!-- /backlinks -->"
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use std::ops::Range;
+
+use tower_lsp::lsp_types::{Color, ColorInformation, ColorPresentation, TextEdit};
+
+use crate::markdown_context::FenceState;
+use crate::position::{self, Encoding};
+
+/// Callout types and the color of their swatch, as `0xRRGGBB`. Aliases such as `hint`
+/// for `tip` share their type's color.
+const PALETTE: [(&str, u32); 13] = [
+    ("note", 0x448aff),
+    ("abstract", 0x00b0ff),
+    ("info", 0x00b8d4),
+    ("todo", 0x2979ff),
+    ("tip", 0x00bfa5),
+    ("success", 0x00c853),
+    ("question", 0xffd600),
+    ("warning", 0xff9100),
+    ("failure", 0xff5252),
+    ("danger", 0xff1744),
+    ("bug", 0xf50057),
+    ("example", 0x7c4dff),
+    ("quote", 0x9e9e9e),
+];
+
+/// The `[!type]` marker opening a callout block, such as `> [!warning] Careful`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Callout {
+    /// The type, lowercased.
+    pub kind: String,
+    /// Where the `[!type]` token sits, as byte offsets into the document.
+    pub range: Range<usize>,
+}
+
+/// Finds the callout markers in `text`: a blockquote line whose content starts with
+/// `[!type]`. Blockquotes inside fenced code don't count.
+pub fn find_callouts(text: &str) -> Vec<Callout> {
+    let mut callouts = Vec::new();
+    let mut fence = FenceState::default();
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        if fence.update(line) {
+            continue;
+        }
+        let Some(quoted) = line.trim_start().strip_prefix('>') else {
+            continue;
+        };
+        let token = quoted.trim_start();
+        let Some(inner) = token.strip_prefix("[!") else {
+            continue;
+        };
+        let Some(len) = inner.find(']') else {
+            continue;
+        };
+        let kind = inner[..len].trim();
+        if kind.is_empty() || kind.contains(char::is_whitespace) {
+            continue;
+        }
+        let start = line_start + (line.len() - token.len());
+        callouts.push(Callout {
+            kind: kind.to_lowercase(),
+            range: start..start + len + 3,
+        });
+    }
+    callouts
+}
+
+/// Returns the palette color for a callout type, resolving the usual aliases; unknown
+/// types get the neutral `quote` color.
+fn palette_color(kind: &str) -> u32 {
+    let kind = match kind {
+        "summary" | "tldr" => "abstract",
+        "hint" | "important" => "tip",
+        "check" | "done" => "success",
+        "help" | "faq" => "question",
+        "caution" | "attention" => "warning",
+        "fail" | "missing" => "failure",
+        "error" => "danger",
+        "cite" => "quote",
+        kind => kind,
+    };
+    PALETTE
+        .iter()
+        .find(|(name, _)| *name == kind)
+        .map_or(0x9e9e9e, |(_, rgb)| *rgb)
+}
+
+fn to_color(rgb: u32) -> Color {
+    let channel = |shift: u32| ((rgb >> shift) & 0xff) as f32 / 255.0;
+    Color {
+        red: channel(16),
+        green: channel(8),
+        blue: channel(0),
+        alpha: 1.0,
+    }
+}
+
+/// Returns one swatch per callout in `text`, covering its `[!type]` token.
+pub fn callout_colors(text: &str, encoding: Encoding) -> Vec<ColorInformation> {
+    find_callouts(text)
+        .into_iter()
+        .map(|callout| ColorInformation {
+            range: position::offset_range(text, &callout.range, encoding),
+            color: to_color(palette_color(&callout.kind)),
+        })
+        .collect()
+}
+
+/// Offers the callout types for a color picked on the swatch at `range`, closest color
+/// first; choosing one rewrites the token to that type.
+pub fn color_presentations(
+    color: Color,
+    range: tower_lsp::lsp_types::Range,
+) -> Vec<ColorPresentation> {
+    let distance = |rgb: u32| {
+        let other = to_color(rgb);
+        (other.red - color.red).powi(2)
+            + (other.green - color.green).powi(2)
+            + (other.blue - color.blue).powi(2)
+    };
+    let mut kinds: Vec<(&str, f32)> = PALETTE
+        .iter()
+        .map(|(name, rgb)| (*name, distance(*rgb)))
+        .collect();
+    kinds.sort_by(|a, b| a.1.total_cmp(&b.1));
+    kinds
+        .into_iter()
+        .map(|(name, _)| {
+            let token = format!("[!{}]", name);
+            ColorPresentation {
+                label: token.clone(),
+                text_edit: Some(TextEdit {
+                    range,
+                    new_text: token,
+                }),
+                additional_text_edits: None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_callouts() {
+        let text = "> [!NOTE] Title\n> body\n  >  [!warning]- Folded\n```\n> [!tip]\n```\n> [!]\n";
+        let callouts = find_callouts(text);
+        let found: Vec<_> = callouts
+            .iter()
+            .map(|callout| (callout.kind.as_str(), &text[callout.range.clone()]))
+            .collect();
+        assert_eq!(found, vec![("note", "[!NOTE]"), ("warning", "[!warning]")]);
+
+        let colors = callout_colors(text, Encoding::default());
+        assert_eq!(
+            colors[1].range.start,
+            tower_lsp::lsp_types::Position::new(2, 5)
+        );
+        assert_eq!(colors[1].color.red, 1.0);
+        assert_eq!(palette_color("caution"), palette_color("warning"));
+    }
+
+    #[test]
+    fn test_color_presentations() {
+        let range = tower_lsp::lsp_types::Range::default();
+        let presentations = color_presentations(to_color(0xff9000), range);
+        assert_eq!(presentations.len(), PALETTE.len());
+        assert_eq!(presentations[0].label, "[!warning]");
+    }
+}
@@ -0,0 +1,609 @@
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, TextEdit, Url, WorkspaceEdit,
+};
+
+use crate::heading;
+use crate::markdown_link;
+use crate::position::{self, Encoding};
+use crate::vault;
+use crate::wikilink::{self, Delimiters};
+
+/// Offers to rewrite the link at `offset` into the other syntax: a wiki-link becomes a
+/// markdown link relative to the current note, and a markdown link to a note in the
+/// vault becomes a wiki-link. Wiki-links resolve as they do elsewhere: relative to the
+/// note, then by the title or alias of one of `pages`.
+pub fn link_conversion_actions(
+    uri: &Url,
+    text: &str,
+    offset: usize,
+    vault_dir: &Path,
+    pages: &[vault::Page],
+    delimiters: &Delimiters,
+    encoding: Encoding,
+) -> Vec<CodeActionOrCommand> {
+    let Ok(file) = uri.to_file_path() else {
+        return Vec::new();
+    };
+    let Some(current_dir) = file.parent().map(Path::to_path_buf) else {
+        return Vec::new();
+    };
+
+    if let Some(link) = wikilink::wikilink_at(text, offset, delimiters) {
+        let Some(target) = vault::resolve_note_link(vault_dir, &file, &link.path, pages) else {
+            return Vec::new();
+        };
+        let mut destination = vault::relative_path(&current_dir, &target).replace(' ', "%20");
+        if let Some(anchor) = &link.anchor {
+            let content = fs::read_to_string(&target).unwrap_or_default();
+            destination.push('#');
+            destination.push_str(&heading::anchor_slug(
+                &heading::parse_headings(&content),
+                anchor,
+            ));
+        }
+        let label = link.alias.as_ref().unwrap_or(&link.path);
+        return vec![rewrite_action(
+            "Convert to Markdown link",
+            uri,
+            text,
+            &link.range,
+            format!("[{}]({})", label, destination),
+            encoding,
+        )];
+    }
+
+    if let Some(link) = markdown_link::markdown_link_at(text, offset) {
+        if link.target.contains("://") || link.target.starts_with("mailto:") {
+            return Vec::new();
+        }
+        let (path, anchor) = match link.target.split_once('#') {
+            Some((path, anchor)) => (path, Some(anchor.replace("%20", " "))),
+            None => (link.target.as_str(), None),
+        };
+        let target = vault::normalize_path(&current_dir.join(path.replace("%20", " ")));
+        let Some(vpath) = vault::virtual_path(vault_dir, &target) else {
+            return Vec::new();
+        };
+        let mut path = vpath.strip_suffix(".md").unwrap_or(&vpath).to_string();
+        if let Some(anchor) = anchor {
+            // A slug is written back as the title of the heading it stands for.
+            let content = fs::read_to_string(&target).unwrap_or_default();
+            let headings = heading::parse_headings(&content);
+            path.push('#');
+            match heading::heading_by_slug(&headings, &anchor) {
+                Some(heading) => path.push_str(&heading::heading_anchor(&headings, heading)),
+                None => path.push_str(&anchor),
+            }
+        }
+        let alias = Some(link.text.trim()).filter(|alias| !alias.is_empty());
+        return vec![rewrite_action(
+            "Convert to wiki-link",
+            uri,
+            text,
+            &link.range,
+            delimiters.link(&path, alias),
+            encoding,
+        )];
+    }
+
+    Vec::new()
+}
+
+/// Words kept lowercase by title case unless they start or end the heading.
+const SMALL_WORDS: [&str; 16] = [
+    "a", "an", "and", "as", "at", "but", "by", "for", "in", "nor", "of", "on", "or", "the", "to",
+    "vs",
+];
+
+/// Offers to rewrite the heading on `line` in title case and in sentence case, keeping
+/// its `#` markers and any trailing `{#id}`, `^block` or closing `#`s as they are.
+/// Only the rewrites that change the heading are offered.
+pub fn heading_case_actions(
+    uri: &Url,
+    text: &str,
+    line: usize,
+    delimiters: &Delimiters,
+    encoding: Encoding,
+) -> Vec<CodeActionOrCommand> {
+    if !heading::parse_headings(text)
+        .iter()
+        .any(|heading| heading.line == line)
+    {
+        return Vec::new();
+    }
+    let line_start: usize = text.split_inclusive('\n').take(line).map(str::len).sum();
+    let Some(line_text) = text[line_start..].lines().next() else {
+        return Vec::new();
+    };
+    let (before, words, after) = split_heading(line_text);
+    let rewrites = [
+        ("Title Case heading", title_case(words, delimiters)),
+        ("Sentence case heading", sentence_case(words, delimiters)),
+    ];
+    rewrites
+        .into_iter()
+        .filter(|(_, rewritten)| rewritten != words)
+        .map(|(title, rewritten)| {
+            rewrite_action(
+                title,
+                uri,
+                text,
+                &(line_start..line_start + line_text.len()),
+                format!("{}{}{}", before, rewritten, after),
+                encoding,
+            )
+        })
+        .collect()
+}
+
+/// Splits a heading line into its leading markers, its words and its trailing markers.
+fn split_heading(line: &str) -> (&str, &str, &str) {
+    let marker_len = line.len() - line.trim_start_matches('#').len();
+    let content = &line[marker_len..];
+    let start = marker_len + (content.len() - content.trim_start().len());
+    let mut end = line.trim_end().len();
+    if marker_len > 0 {
+        // Closing `#`s only count when separated from the text by a space.
+        let body = &line[start..end];
+        let without = body.trim_end_matches('#');
+        if without.len() < body.len() && without.ends_with(' ') {
+            end = start + without.trim_end().len();
+        }
+    }
+    let body = &line[start..end];
+    if body.ends_with('}') {
+        if let Some(open) = body.rfind(" {#") {
+            end = start + open;
+        }
+    } else if let Some(caret) = body.rfind(" ^") {
+        if body[caret + 2..]
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-')
+        {
+            end = start + caret;
+        }
+    }
+    (&line[..start], &line[start..end], &line[end..])
+}
+
+/// Words such as acronyms (`LSP`), mixed-case names (`iPhone`), code and links are
+/// written the way the author wrote them in either case style.
+fn keeps_case(word: &str, delimiters: &Delimiters) -> bool {
+    word.starts_with('`')
+        || word.contains(&delimiters.open)
+        || word.contains("](")
+        || word.contains("://")
+        || word.chars().skip(1).any(char::is_uppercase)
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Capitalizes every word except small words in the middle of the heading.
+fn title_case(heading: &str, delimiters: &Delimiters) -> String {
+    let words: Vec<&str> = heading.split(' ').collect();
+    let last = words.len().saturating_sub(1);
+    words
+        .iter()
+        .enumerate()
+        .map(|(i, word)| {
+            if keeps_case(word, delimiters) {
+                word.to_string()
+            } else if i != 0 && i != last && SMALL_WORDS.contains(&word.to_lowercase().as_str()) {
+                word.to_lowercase()
+            } else {
+                capitalize(&word.to_lowercase())
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Capitalizes the first word and lowercases the rest.
+fn sentence_case(heading: &str, delimiters: &Delimiters) -> String {
+    heading
+        .split(' ')
+        .enumerate()
+        .map(|(i, word)| {
+            if keeps_case(word, delimiters) {
+                word.to_string()
+            } else if i == 0 {
+                capitalize(&word.to_lowercase())
+            } else {
+                word.to_lowercase()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Number of "Did you mean" fixes offered for an unresolved link.
+const MAX_LINK_SUGGESTIONS: usize = 3;
+
+/// Offers to point the unresolved `link` at one of the closest existing notes instead.
+/// `notes` holds the virtual path and title of every note; the link text is fuzzy
+/// matched against both, and the best few matches become quickfixes, the closest one
+/// preferred.
+pub fn link_suggestion_actions(
+    uri: &Url,
+    text: &str,
+    link: &wikilink::WikiLink,
+    notes: &[(String, String)],
+    encoding: Encoding,
+) -> Vec<CodeActionOrCommand> {
+    let query = link.path.trim();
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let matcher = crate::FuzzyMatcher::new(query);
+    let mut matches: Vec<(usize, &str)> = notes
+        .iter()
+        .filter_map(|(vpath, title)| {
+            let stem = vpath.strip_suffix(".md").unwrap_or(vpath);
+            let score = [stem, title.as_str()]
+                .into_iter()
+                .filter_map(|candidate| matcher.score(candidate))
+                .min()?;
+            Some((score, vpath.as_str()))
+        })
+        .collect();
+    matches.sort();
+    matches.dedup_by(|a, b| a.1 == b.1);
+    matches
+        .into_iter()
+        .take(MAX_LINK_SUGGESTIONS)
+        .enumerate()
+        .map(|(rank, (_, vpath))| {
+            let new_path = if query.ends_with(".md") {
+                vpath
+            } else {
+                vpath.strip_suffix(".md").unwrap_or(vpath)
+            };
+            let mut action = edit_action(
+                CodeActionKind::QUICKFIX,
+                &format!("Did you mean '{}'?", new_path),
+                uri,
+                text,
+                &link.path_range,
+                new_path.to_string(),
+                encoding,
+            );
+            if let CodeActionOrCommand::CodeAction(action) = &mut action {
+                action.is_preferred = Some(rank == 0);
+            }
+            action
+        })
+        .collect()
+}
+
+/// The callout types offered for a selection, with their action titles.
+const CALLOUT_TYPES: [(&str, &str); 3] = [("note", "Note"), ("warning", "Warning"), ("tip", "Tip")];
+
+/// Quotes `lines` as a callout of `kind`: a `> [!kind]` header, then each line behind
+/// `> `. Lines already in a blockquote keep their own markers, one level deeper.
+fn callout_text(lines: &str, kind: &str) -> String {
+    let mut callout = format!("> [!{}]", kind);
+    for line in lines.lines() {
+        callout.push('\n');
+        if line.trim().is_empty() {
+            callout.push('>');
+        } else if line.starts_with('>') {
+            callout.push('>');
+            callout.push_str(line);
+        } else {
+            callout.push_str("> ");
+            callout.push_str(line);
+        }
+    }
+    callout
+}
+
+/// Offers to wrap the lines touched by `selection` in a callout, one action per callout
+/// type. A selection ending at the start of a line leaves that line out; an empty
+/// selection gets no actions.
+pub fn callout_actions(
+    uri: &Url,
+    text: &str,
+    selection: Range<usize>,
+    encoding: Encoding,
+) -> Vec<CodeActionOrCommand> {
+    if text[selection.clone()].trim().is_empty() {
+        return Vec::new();
+    }
+    let start = text[..selection.start].rfind('\n').map_or(0, |i| i + 1);
+    let end = if text[..selection.end].ends_with('\n') {
+        selection.end - 1
+    } else {
+        text[selection.end..]
+            .find('\n')
+            .map_or(text.len(), |i| selection.end + i)
+    };
+    let end = text[..end].strip_suffix('\r').map_or(end, str::len);
+    let lines = &text[start..end];
+    CALLOUT_TYPES
+        .iter()
+        .map(|(kind, title)| {
+            rewrite_action(
+                &format!("Wrap in callout: {}", title),
+                uri,
+                text,
+                &(start..end),
+                callout_text(lines, kind),
+                encoding,
+            )
+        })
+        .collect()
+}
+
+fn rewrite_action(
+    title: &str,
+    uri: &Url,
+    text: &str,
+    range: &Range<usize>,
+    new_text: String,
+    encoding: Encoding,
+) -> CodeActionOrCommand {
+    edit_action(
+        CodeActionKind::REFACTOR_REWRITE,
+        title,
+        uri,
+        text,
+        range,
+        new_text,
+        encoding,
+    )
+}
+
+fn edit_action(
+    kind: CodeActionKind,
+    title: &str,
+    uri: &Url,
+    text: &str,
+    range: &Range<usize>,
+    new_text: String,
+    encoding: Encoding,
+) -> CodeActionOrCommand {
+    let edit = TextEdit {
+        range: position::offset_range(text, range, encoding),
+        new_text,
+    };
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title: title.to_string(),
+        kind: Some(kind),
+        edit: Some(WorkspaceEdit {
+            changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_lsp::lsp_types::Position;
+
+    fn new_text(actions: &[CodeActionOrCommand]) -> String {
+        match &actions[0] {
+            CodeActionOrCommand::CodeAction(action) => {
+                let changes = action.edit.as_ref().unwrap().changes.as_ref().unwrap();
+                changes.values().next().unwrap()[0].new_text.clone()
+            }
+            CodeActionOrCommand::Command(_) => panic!("expected a code action"),
+        }
+    }
+
+    #[test]
+    fn test_link_conversion_round_trip() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-code-action-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(vault_dir.join("notes")).unwrap();
+        fs::create_dir_all(vault_dir.join("ideas")).unwrap();
+        fs::write(
+            vault_dir.join("ideas/big idea.md"),
+            "# Big\n## My Heading!\n## Setup\n## Setup\n",
+        )
+        .unwrap();
+        let uri = Url::from_file_path(vault_dir.join("notes/today.md")).unwrap();
+
+        let wiki = "See [[ideas/big idea#Big | the idea]].";
+        let actions = link_conversion_actions(
+            &uri,
+            wiki,
+            6,
+            &vault_dir,
+            &[],
+            &Delimiters::default(),
+            Encoding::default(),
+        );
+        assert_eq!(new_text(&actions), "[the idea](../ideas/big%20idea.md#big)");
+
+        let markdown = "See [the idea](../ideas/big%20idea.md#big).";
+        let actions = link_conversion_actions(
+            &uri,
+            markdown,
+            6,
+            &vault_dir,
+            &[],
+            &Delimiters::default(),
+            Encoding::default(),
+        );
+        assert_eq!(new_text(&actions), "[[ideas/big idea#Big | the idea]]");
+
+        let wiki = "See [[ideas/big idea#My Heading!]] and [[ideas/big idea#Setup#2]].";
+        let actions = link_conversion_actions(
+            &uri,
+            wiki,
+            6,
+            &vault_dir,
+            &[],
+            &Delimiters::default(),
+            Encoding::default(),
+        );
+        assert_eq!(
+            new_text(&actions),
+            "[ideas/big idea](../ideas/big%20idea.md#my-heading)"
+        );
+        let actions = link_conversion_actions(
+            &uri,
+            wiki,
+            40,
+            &vault_dir,
+            &[],
+            &Delimiters::default(),
+            Encoding::default(),
+        );
+        assert_eq!(
+            new_text(&actions),
+            "[ideas/big idea](../ideas/big%20idea.md#setup-1)"
+        );
+        let markdown = "See [setup](../ideas/big%20idea.md#setup-1).";
+        let actions = link_conversion_actions(
+            &uri,
+            markdown,
+            6,
+            &vault_dir,
+            &[],
+            &Delimiters::default(),
+            Encoding::default(),
+        );
+        assert_eq!(new_text(&actions), "[[ideas/big idea#Setup#2 | setup]]");
+
+        let pages = [vault::Page {
+            file: vault_dir.join("ideas/big idea.md"),
+            title: "Big".to_string(),
+            aliases: Vec::new(),
+        }];
+        for wiki in ["See [[../ideas/big idea]].", "See [[Big]]."] {
+            let actions = link_conversion_actions(
+                &uri,
+                wiki,
+                6,
+                &vault_dir,
+                &pages,
+                &Delimiters::default(),
+                Encoding::default(),
+            );
+            assert!(new_text(&actions).ends_with("(../ideas/big%20idea.md)"));
+        }
+        let escaping = "See [[../../../etc/passwd]].";
+        let actions = link_conversion_actions(
+            &uri,
+            escaping,
+            6,
+            &vault_dir,
+            &pages,
+            &Delimiters::default(),
+            Encoding::default(),
+        );
+        assert!(actions.is_empty());
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+
+    #[test]
+    fn test_title_and_sentence_case() {
+        assert_eq!(
+            title_case("the lord of the rings and LSP", &Delimiters::default()),
+            "The Lord of the Rings and LSP"
+        );
+        assert_eq!(
+            title_case("what to look for", &Delimiters::default()),
+            "What to Look For"
+        );
+        assert_eq!(
+            sentence_case(
+                "Getting Started With The LSP on iPhone",
+                &Delimiters::default()
+            ),
+            "Getting started with the LSP on iPhone"
+        );
+    }
+
+    #[test]
+    fn test_heading_case_actions() {
+        let uri = Url::parse("file:///vault/note.md").unwrap();
+        let text = "intro\n## notes on the API {#api} ##\nbody ^x\n";
+        let actions =
+            heading_case_actions(&uri, text, 1, &Delimiters::default(), Encoding::default());
+        assert_eq!(actions.len(), 2);
+        assert_eq!(new_text(&actions), "## Notes on the API {#api} ##");
+        assert_eq!(new_text(&actions[1..]), "## Notes on the API {#api} ##");
+        assert!(
+            heading_case_actions(&uri, text, 2, &Delimiters::default(), Encoding::default())
+                .is_empty()
+        );
+
+        let text = "# Ideas for Later ^later-1\n";
+        let actions =
+            heading_case_actions(&uri, text, 0, &Delimiters::default(), Encoding::default());
+        assert_eq!(actions.len(), 1);
+        assert_eq!(new_text(&actions), "# Ideas for later ^later-1");
+    }
+
+    #[test]
+    fn test_callout_actions() {
+        let uri = Url::parse("file:///vault/note.md").unwrap();
+        let text = "# Title\nFirst line\n\n> quoted\nLast\nAfter\n";
+        let start = text.find("line").unwrap();
+        let end = text.find("After").unwrap();
+        let actions = callout_actions(&uri, text, start..end, Encoding::default());
+        assert_eq!(actions.len(), 3);
+        let CodeActionOrCommand::CodeAction(tip) = &actions[2] else {
+            panic!("expected a code action");
+        };
+        assert_eq!(tip.title, "Wrap in callout: Tip");
+        assert_eq!(
+            new_text(&actions),
+            "> [!note]\n> First line\n>\n>> quoted\n> Last"
+        );
+        let edit = &tip.edit.as_ref().unwrap().changes.as_ref().unwrap()[&uri][0];
+        assert_eq!(edit.range.start, Position::new(1, 0));
+        assert_eq!(edit.range.end, Position::new(4, 4));
+        assert!(callout_actions(&uri, text, start..start, Encoding::default()).is_empty());
+    }
+
+    #[test]
+    fn test_link_suggestion_actions() {
+        let uri = Url::parse("file:///vault/index.md").unwrap();
+        let text = "See [[rst notes | Rust]].";
+        let link = &wikilink::find_wikilinks(text, &Delimiters::default())[0];
+        let note = |vpath: &str, title: &str| (vpath.to_string(), title.to_string());
+        let notes = vec![
+            note("lang/rust-notes.md", "Rust Notes"),
+            note("rest.md", "Resting notes"),
+            note("cooking.md", "Cooking"),
+            note("lang/rust.md", "Rust"),
+            note("reset-notes.md", "Reset"),
+            note("rust notes 2024.md", "Rust notes 2024"),
+            note("drafts/rust snippets and notes.md", "Snippets"),
+        ];
+        let actions = link_suggestion_actions(&uri, text, link, &notes, Encoding::default());
+        assert_eq!(actions.len(), 3);
+        let CodeActionOrCommand::CodeAction(best) = &actions[0] else {
+            panic!("expected a code action");
+        };
+        assert_eq!(best.title, "Did you mean 'lang/rust-notes'?");
+        assert_eq!(best.kind, Some(CodeActionKind::QUICKFIX));
+        assert_eq!(best.is_preferred, Some(true));
+        assert_eq!(new_text(&actions), "lang/rust-notes");
+        assert!(link_suggestion_actions(
+            &uri,
+            text,
+            link,
+            &[note("a.md", "Other")],
+            Encoding::default()
+        )
+        .is_empty());
+    }
+}
@@ -0,0 +1,55 @@
+use tower_lsp::lsp_types::{CodeLens, Command, Position, Range, Url};
+
+use crate::heading;
+use crate::position::{self, Encoding};
+
+/// The command behind the backlink lens. Its argument is the note's URI and it returns
+/// the URIs of the notes linking to it.
+pub const SHOW_BACKLINKS_COMMAND: &str = "notemancy.showBacklinks";
+
+/// Builds the lens reading "N backlinks", placed on the note's first heading or, when it
+/// has none, on the first line.
+pub fn backlink_lens(uri: &Url, text: &str, count: usize, encoding: Encoding) -> CodeLens {
+    let range = heading::parse_headings(text)
+        .first()
+        .map_or(Range::default(), |heading| Range {
+            start: Position::new(heading.line as u32, 0),
+            end: position::line_end(text, heading.line, encoding),
+        });
+    let title = match count {
+        1 => "1 backlink".to_string(),
+        n => format!("{} backlinks", n),
+    };
+    CodeLens {
+        range,
+        command: Some(Command {
+            title,
+            command: SHOW_BACKLINKS_COMMAND.to_string(),
+            arguments: Some(vec![serde_json::Value::String(uri.to_string())]),
+        }),
+        data: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backlink_lens() {
+        let uri = Url::parse("file:///vault/note.md").unwrap();
+        let lens = backlink_lens(
+            &uri,
+            "---\ntitle: x\n---\n\n# Noté\n",
+            1,
+            Encoding::default(),
+        );
+        assert_eq!(lens.range.start, Position::new(4, 0));
+        assert_eq!(lens.range.end, Position::new(4, 6));
+        assert_eq!(lens.command.unwrap().title, "1 backlink");
+
+        let lens = backlink_lens(&uri, "No headings", 3, Encoding::default());
+        assert_eq!(lens.range.start, Position::new(0, 0));
+        assert_eq!(lens.command.unwrap().title, "3 backlinks");
+    }
+}
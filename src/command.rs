@@ -0,0 +1,79 @@
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tower_lsp::jsonrpc::{Error, Result};
+use tower_lsp::lsp_types::{Position, Range};
+use url::Url;
+
+/// Reads the string argument at `index` of a command, naming it `what` in the error.
+pub fn string_arg<'a>(arguments: &'a [Value], index: usize, what: &str) -> Result<&'a str> {
+    arguments.get(index).and_then(Value::as_str).ok_or_else(|| {
+        Error::invalid_params(format!("expected {} as argument {}", what, index + 1))
+    })
+}
+
+/// Reads the document URI argument at `index` of a command.
+pub fn uri_arg(arguments: &[Value], index: usize) -> Result<Url> {
+    let uri = string_arg(arguments, index, "a document URI")?;
+    Url::parse(uri).map_err(|_| Error::invalid_params(format!("invalid document URI: {}", uri)))
+}
+
+/// Reads the integer argument at `index` of a command, naming it `what` in the error.
+pub fn int_arg(arguments: &[Value], index: usize, what: &str) -> Result<i64> {
+    json_arg(arguments, index, what)
+}
+
+/// Reads the boolean argument at `index` of a command, naming it `what` in the error.
+pub fn bool_arg(arguments: &[Value], index: usize, what: &str) -> Result<bool> {
+    json_arg(arguments, index, what)
+}
+
+/// Reads the LSP range argument at `index` of a command.
+pub fn range_arg(arguments: &[Value], index: usize) -> Result<Range> {
+    json_arg(arguments, index, "a range")
+}
+
+/// Reads the LSP position argument at `index` of a command.
+pub fn position_arg(arguments: &[Value], index: usize) -> Result<Position> {
+    json_arg(arguments, index, "a position")
+}
+
+/// Reads the list-of-strings argument at `index` of a command.
+pub fn string_list_arg(arguments: &[Value], index: usize, what: &str) -> Result<Vec<String>> {
+    json_arg(arguments, index, what)
+}
+
+fn json_arg<T: DeserializeOwned>(arguments: &[Value], index: usize, what: &str) -> Result<T> {
+    arguments
+        .get(index)
+        .and_then(|value| serde_json::from_value(value.clone()).ok())
+        .ok_or_else(|| {
+            Error::invalid_params(format!("expected {} as argument {}", what, index + 1))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_command_arguments() {
+        let arguments = vec![
+            json!("file:///vault/note.md"),
+            json!(3),
+            json!({"start": {"line": 1, "character": 0}, "end": {"line": 2, "character": 4}}),
+        ];
+        assert_eq!(
+            uri_arg(&arguments, 0).unwrap().as_str(),
+            "file:///vault/note.md"
+        );
+        assert!(string_arg(&arguments, 1, "a name").is_err());
+        assert!(uri_arg(&arguments, 2).is_err());
+        assert_eq!(range_arg(&arguments, 2).unwrap().end.character, 4);
+        assert!(range_arg(&arguments, 0).is_err());
+        assert!(position_arg(&arguments, 1).is_err());
+        assert!(string_list_arg(&arguments, 0, "tags").is_err());
+        assert_eq!(int_arg(&arguments, 1, "an offset").unwrap(), 3);
+        assert!(int_arg(&arguments, 0, "an offset").is_err());
+    }
+}
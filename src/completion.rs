@@ -0,0 +1,508 @@
+use std::path::Path;
+use std::time::SystemTime;
+
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionTextEdit, Documentation, MarkupContent,
+    MarkupKind, Position, Range, TextEdit, Url,
+};
+
+use crate::date;
+use crate::frontmatter;
+use crate::heading;
+use crate::position::{self, Encoding};
+use crate::settings::WikilinkFormat;
+use crate::tags;
+use crate::wikilink::Delimiters;
+
+/// Keys offered when completing inside the frontmatter block.
+const FRONTMATTER_KEYS: [&str; 6] = ["title", "tags", "aliases", "date", "created", "modified"];
+
+/// What the cursor is typing inside the frontmatter block.
+#[derive(Debug, PartialEq, Eq)]
+pub enum FrontmatterContext {
+    /// A top-level key starting at this byte column.
+    Key { start: usize },
+    /// A value of the `tags` list starting at this byte column.
+    Tag { start: usize },
+    /// Anything else, such as the value of another key.
+    Other,
+}
+
+/// Works out what is being typed at `line`, whose text up to the cursor is `prefix`, when
+/// that line lies between the frontmatter delimiters of `text`. Returns `None` outside the
+/// frontmatter.
+pub fn frontmatter_context(text: &str, line: usize, prefix: &str) -> Option<FrontmatterContext> {
+    let range = frontmatter::frontmatter_range(text)?;
+    let lines: Vec<&str> = text[range].lines().collect();
+    if line == 0 || line + 1 >= lines.len() {
+        return None;
+    }
+    if !prefix.starts_with(char::is_whitespace) && !prefix.starts_with('-') {
+        return Some(match prefix.split_once(':') {
+            None => FrontmatterContext::Key { start: 0 },
+            Some(("tags", _)) => {
+                let start = prefix.rfind(['[', ',', ':']).map_or(0, |i| i + 1);
+                let start = start + (prefix[start..].len() - prefix[start..].trim_start().len());
+                FrontmatterContext::Tag { start }
+            }
+            Some(_) => FrontmatterContext::Other,
+        });
+    }
+    // A `- item` line belongs to the closest top-level key above it.
+    let owner = lines[1..line]
+        .iter()
+        .rev()
+        .find(|line| !line.starts_with(char::is_whitespace) && !line.starts_with('-'));
+    let item = prefix.trim_start().strip_prefix('-');
+    match (owner, item) {
+        (Some(owner), Some(item)) if owner.trim_end() == "tags:" => {
+            let start = prefix.len() - item.trim_start().len();
+            Some(FrontmatterContext::Tag { start })
+        }
+        _ => Some(FrontmatterContext::Other),
+    }
+}
+
+/// Offers the common frontmatter keys that `text` doesn't set yet, replacing the text
+/// typed between `start` and the cursor with `key: `.
+pub fn frontmatter_key_completions(
+    text: &str,
+    start: Position,
+    cursor: Position,
+) -> Vec<CompletionItem> {
+    let present: Vec<String> = frontmatter::fields(text)
+        .into_iter()
+        .map(|field| field.key)
+        .collect();
+    FRONTMATTER_KEYS
+        .iter()
+        .filter(|key| !present.iter().any(|present| present == *key))
+        .map(|key| CompletionItem {
+            label: key.to_string(),
+            kind: Some(CompletionItemKind::PROPERTY),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                range: Range { start, end: cursor },
+                new_text: format!("{}: ", key),
+            })),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// If `prefix` (the text of a line up to the cursor) ends in a date being typed after
+/// `trigger`, such as `@next mo`, returns the byte index of the trigger. The trigger must
+/// start the line or follow whitespace, so `me@example` isn't taken for a date.
+pub fn date_query_start(prefix: &str, trigger: &str) -> Option<usize> {
+    if trigger.is_empty() {
+        return None;
+    }
+    let start = prefix.rfind(trigger)?;
+    let typed = &prefix[start + trigger.len()..];
+    let at_boundary = prefix[..start].is_empty() || prefix[..start].ends_with(char::is_whitespace);
+    (at_boundary && typed.chars().all(|c| c.is_alphabetic() || c == ' ')).then_some(start)
+}
+
+/// Offers dates relative to `today` (days since 1970-01-01) for the text the date
+/// trigger started, the `replace` range: today, tomorrow, yesterday and the next of each
+/// weekday, each inserting the date written with `format`.
+pub fn date_items(today: i64, format: &str, trigger: &str, replace: Range) -> Vec<CompletionItem> {
+    let mut days = vec![
+        ("today".to_string(), today),
+        ("tomorrow".to_string(), today + 1),
+        ("yesterday".to_string(), today - 1),
+    ];
+    let weekday = date::weekday(today) as i64;
+    for (index, name) in date::WEEKDAYS.iter().enumerate() {
+        let ahead = (index as i64 - weekday - 1).rem_euclid(7) + 1;
+        days.push((format!("next {}", name), today + ahead));
+    }
+    days.into_iter()
+        .enumerate()
+        .map(|(rank, (label, days))| {
+            let new_text = date::format_date(format, date::civil_from_days(days));
+            CompletionItem {
+                filter_text: Some(format!("{}{}", trigger, label)),
+                sort_text: Some(format!("{:02}", rank)),
+                detail: Some(new_text.clone()),
+                label,
+                kind: Some(CompletionItemKind::VALUE),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                    range: replace,
+                    new_text,
+                })),
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// Offers the headings of a link target's `content` after the `#` of `[[path#`, each
+/// replacing `replace` with the heading text. The item's `data` holds the `target`
+/// note's URI and the heading's line, so the text under the heading can be resolved
+/// later.
+pub fn heading_items(content: &str, target: &Url, replace: Range) -> Vec<CompletionItem> {
+    heading::parse_headings(content)
+        .into_iter()
+        .map(|heading| CompletionItem {
+            label: heading.title.clone(),
+            kind: Some(CompletionItemKind::REFERENCE),
+            detail: Some(format!("Heading level {}", heading.level)),
+            data: Some(serde_json::json!({ "uri": target, "line": heading.line })),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                range: replace,
+                new_text: heading.title,
+            })),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Builds the documentation shown for a highlighted heading completion: the first line
+/// of text under the heading on `line` of `content`, or `None` when the section has no
+/// text before the next heading.
+pub fn heading_documentation(content: &str, line: usize) -> Option<Documentation> {
+    let headings = heading::parse_headings(content);
+    let index = headings.iter().position(|heading| heading.line == line)?;
+    let end = headings.get(index + 1).map_or(usize::MAX, |next| next.line);
+    let text = content
+        .lines()
+        .enumerate()
+        .skip(line + 1)
+        .take_while(|(number, _)| *number < end)
+        .map(|(_, text)| text.trim())
+        // A setext heading's underline isn't text under it.
+        .find(|text| !text.is_empty() && !text.chars().all(|c| c == '=' || c == '-'))?;
+    Some(Documentation::MarkupContent(MarkupContent {
+        kind: MarkupKind::Markdown,
+        value: text.to_string(),
+    }))
+}
+
+/// Offers a note as the target of the `[[` link being typed, replacing the text already
+/// typed after the `[[` (the `range`) with the note in the configured `format`. The
+/// item's `data` holds the virtual path, so its documentation can be resolved later.
+/// Clients filter on both the title and the path, as the server does.
+pub fn note_item(
+    vpath: String,
+    title: String,
+    format: WikilinkFormat,
+    delimiters: &Delimiters,
+    range: Range,
+) -> CompletionItem {
+    let new_text = match format {
+        WikilinkFormat::Path => vpath.clone(),
+        WikilinkFormat::PathAlias => delimiters.inner(&vpath, Some(&title)),
+        WikilinkFormat::Title => title.clone(),
+    };
+    CompletionItem {
+        filter_text: Some(format!("{} {}", title, vpath)),
+        label: title,
+        kind: Some(CompletionItemKind::FILE),
+        data: Some(serde_json::Value::String(vpath.clone())),
+        detail: Some(vpath),
+        text_edit: Some(CompletionTextEdit::Edit(TextEdit { range, new_text })),
+        ..Default::default()
+    }
+}
+
+/// Builds the documentation shown for a highlighted note completion from the note's
+/// `content`: its frontmatter fields, one per line, then the first paragraph of the body.
+pub fn note_documentation(content: &str) -> Documentation {
+    let mut sections = Vec::new();
+    let fields: Vec<String> = frontmatter::fields(content)
+        .into_iter()
+        .map(|field| {
+            let value = if field.value.is_empty() {
+                frontmatter::list_field(content, &field.key).join(", ")
+            } else {
+                field.value
+            };
+            format!("**{}**: {}", field.key, value)
+        })
+        .collect();
+    if !fields.is_empty() {
+        sections.push(fields.join("  \n"));
+    }
+    let paragraph: Vec<&str> = frontmatter::strip_frontmatter(content)
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| line.is_empty() || line.starts_with('#'))
+        .take_while(|line| !line.is_empty())
+        .collect();
+    if !paragraph.is_empty() {
+        sections.push(paragraph.join("\n"));
+    }
+    Documentation::MarkupContent(MarkupContent {
+        kind: MarkupKind::Markdown,
+        value: sections.join("\n\n"),
+    })
+}
+
+/// Ranks note completions most recently modified first by setting their `sort_text`;
+/// the label is left alone so the client still filters on it. `modified[i]` is the
+/// modification time of `items[i]`'s note, and notes without one rank last.
+pub fn order_by_recency(items: &mut [CompletionItem], modified: &[Option<SystemTime>]) {
+    let mut order: Vec<usize> = (0..items.len()).collect();
+    // Newest first; `None` sorts before any time, so reverse the whole comparison.
+    order.sort_by(|a, b| modified[*b].cmp(&modified[*a]));
+    for (rank, index) in order.into_iter().enumerate() {
+        items[index].sort_text = Some(format!("{:06}", rank));
+    }
+}
+
+/// Offers the tags used across the vault for the partial tag `typed`, which starts at
+/// `start` (just after the `#`) and ends at the cursor.
+pub fn tag_completions(
+    vault_dir: &Path,
+    typed: &str,
+    start: Position,
+    cursor: Position,
+    encoding: Encoding,
+) -> Vec<CompletionItem> {
+    tag_items(
+        &tags::vault_tag_counts(vault_dir),
+        typed,
+        start,
+        cursor,
+        encoding,
+    )
+}
+
+/// Builds the tag completions from the vault's tag `counts`, most used first. Once a
+/// `/` has been typed only the next level of the tag tree is offered, so `#project/`
+/// completes the segments nested below `project` and replaces just the last segment.
+pub fn tag_items(
+    counts: &[(String, usize)],
+    typed: &str,
+    start: Position,
+    cursor: Position,
+    encoding: Encoding,
+) -> Vec<CompletionItem> {
+    let (candidates, start) = match typed.rfind('/') {
+        Some(slash) => (
+            tags::tag_children(counts, &typed[..slash]),
+            Position {
+                line: start.line,
+                character: start.character + position::column(typed, slash + 1, encoding),
+            },
+        ),
+        None => (counts.to_vec(), start),
+    };
+    candidates
+        .into_iter()
+        .enumerate()
+        .map(|(rank, (tag, count))| CompletionItem {
+            label: tag.clone(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            detail: Some(format!("{} notes", count)),
+            // Keep the frequency order instead of letting the client sort by label.
+            sort_text: Some(format!("{:06}", rank)),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                range: Range { start, end: cursor },
+                new_text: tag,
+            })),
+            ..Default::default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_date_items() {
+        assert_eq!(date_query_start("due @to", "@"), Some(4));
+        assert_eq!(date_query_start("@next mo", "@"), Some(0));
+        assert_eq!(date_query_start("me@example", "@"), None);
+        assert_eq!(date_query_start("due @2024", "@"), None);
+
+        // 2024-01-01 was a Monday.
+        let replace = Range::new(Position::new(0, 4), Position::new(0, 7));
+        let items = date_items(19_723, "[[{{date}}]]", "@", replace);
+        let inserted = |label: &str| {
+            let item = items.iter().find(|item| item.label == label).unwrap();
+            match &item.text_edit {
+                Some(CompletionTextEdit::Edit(edit)) => edit.new_text.clone(),
+                _ => panic!("expected a text edit"),
+            }
+        };
+        assert_eq!(inserted("today"), "[[2024-01-01]]");
+        assert_eq!(inserted("yesterday"), "[[2023-12-31]]");
+        assert_eq!(inserted("next monday"), "[[2024-01-08]]");
+        assert_eq!(inserted("next wednesday"), "[[2024-01-03]]");
+        assert_eq!(items[0].filter_text.as_deref(), Some("@today"));
+    }
+
+    #[test]
+    fn test_heading_items_for_guide_link() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-heading-completion-test");
+        let _ = std::fs::remove_dir_all(&vault_dir);
+        std::fs::create_dir_all(&vault_dir).unwrap();
+        let guide = vault_dir.join("guide.md");
+        std::fs::write(
+            &guide,
+            "# Guide\n## Install\n```\n# not a heading\n```\n### Linux\n",
+        )
+        .unwrap();
+        let pages = vec![crate::vault::Page {
+            file: guide.clone(),
+            title: "User Guide".to_string(),
+            aliases: Vec::new(),
+        }];
+
+        // As typed in "[[guide#]]" and "[[User Guide#]]": the path before the "#".
+        for path in ["guide", "User Guide"] {
+            let target = crate::vault::resolve_link(&vault_dir, path, &pages).unwrap();
+            let content = std::fs::read_to_string(&target).unwrap();
+            let replace = Range::new(Position::new(0, 8), Position::new(0, 8));
+            let target_uri = Url::from_file_path(&target).unwrap();
+            let items = heading_items(&content, &target_uri, replace);
+            let labels: Vec<_> = items.iter().map(|item| item.label.as_str()).collect();
+            assert_eq!(labels, vec!["Guide", "Install", "Linux"]);
+            assert_eq!(items[2].detail.as_deref(), Some("Heading level 3"));
+            assert_eq!(
+                items[2].data,
+                Some(serde_json::json!({ "uri": target_uri, "line": 5 }))
+            );
+        }
+
+        let _ = std::fs::remove_dir_all(&vault_dir);
+    }
+
+    #[test]
+    fn test_heading_documentation() {
+        let content = "# Guide\n\nIntro line.\nMore.\n## Empty\nSetup\n-----\n  Run it.\n";
+        let value = |line| match heading_documentation(content, line) {
+            Some(Documentation::MarkupContent(markup)) => Some(markup.value),
+            _ => None,
+        };
+        assert_eq!(value(0).as_deref(), Some("Intro line."));
+        assert_eq!(value(4), None);
+        assert_eq!(value(5).as_deref(), Some("Run it."));
+        assert_eq!(value(2), None);
+    }
+
+    #[test]
+    fn test_frontmatter_context() {
+        let text = "---\ntitle: Note\nti\ntags: [rust, ls\ntags:\n  - ru\n---\nbody\n";
+        assert_eq!(
+            frontmatter_context(text, 2, "ti"),
+            Some(FrontmatterContext::Key { start: 0 })
+        );
+        assert_eq!(
+            frontmatter_context(text, 3, "tags: [rust, ls"),
+            Some(FrontmatterContext::Tag { start: 13 })
+        );
+        assert_eq!(
+            frontmatter_context(text, 3, "tags: ["),
+            Some(FrontmatterContext::Tag { start: 7 })
+        );
+        assert_eq!(
+            frontmatter_context(text, 5, "  - ru"),
+            Some(FrontmatterContext::Tag { start: 4 })
+        );
+        assert_eq!(
+            frontmatter_context(text, 1, "title: No"),
+            Some(FrontmatterContext::Other)
+        );
+        assert_eq!(frontmatter_context(text, 0, "--"), None);
+        assert_eq!(frontmatter_context(text, 6, "--"), None);
+        assert_eq!(frontmatter_context(text, 7, "bo"), None);
+    }
+
+    #[test]
+    fn test_frontmatter_key_completions() {
+        let text = "---\ntitle: Note\nta\n---\n";
+        let labels: Vec<_> =
+            frontmatter_key_completions(text, Position::new(2, 0), Position::new(2, 2))
+                .into_iter()
+                .map(|item| item.label)
+                .collect();
+        assert_eq!(
+            labels,
+            vec!["tags", "aliases", "date", "created", "modified"]
+        );
+    }
+
+    #[test]
+    fn test_note_item_formats() {
+        let new_text = |format| {
+            let item = note_item(
+                "notes/rust.md".to_string(),
+                "Rust".to_string(),
+                format,
+                &Delimiters::default(),
+                Range::new(Position::new(0, 2), Position::new(0, 2)),
+            );
+            match item.text_edit {
+                Some(CompletionTextEdit::Edit(edit)) => edit.new_text,
+                _ => panic!("expected a text edit"),
+            }
+        };
+        assert_eq!(new_text(WikilinkFormat::Path), "notes/rust.md");
+        assert_eq!(new_text(WikilinkFormat::PathAlias), "notes/rust.md | Rust");
+        assert_eq!(new_text(WikilinkFormat::Title), "Rust");
+    }
+
+    #[test]
+    fn test_note_documentation() {
+        let content = "---\ntitle: Rust\ntags:\n  - lang\n  - systems\n---\n# Rust\n\n\
+                       A language\nfor systems.\n\nMore later.\n";
+        let Documentation::MarkupContent(markup) = note_documentation(content) else {
+            panic!("expected markdown documentation");
+        };
+        assert_eq!(
+            markup.value,
+            "**title**: Rust  \n**tags**: lang, systems\n\nA language\nfor systems."
+        );
+    }
+
+    #[test]
+    fn test_order_by_recency() {
+        let item = |label: &str| CompletionItem {
+            label: label.to_string(),
+            ..Default::default()
+        };
+        let mut items = vec![item("old"), item("unknown"), item("new")];
+        let time = |secs| Some(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs));
+        order_by_recency(&mut items, &[time(10), None, time(20)]);
+        let sort_texts: Vec<_> = items
+            .iter()
+            .map(|item| item.sort_text.as_deref().unwrap())
+            .collect();
+        assert_eq!(sort_texts, vec!["000001", "000002", "000000"]);
+    }
+
+    #[test]
+    fn test_nested_tag_items() {
+        let counts = vec![
+            ("project/alpha/design".to_string(), 2),
+            ("project/alpha/review".to_string(), 1),
+            ("project/beta".to_string(), 1),
+            ("rust".to_string(), 4),
+        ];
+        let items = |typed: &str| {
+            let start = Position::new(0, 1);
+            let cursor = Position::new(0, 1 + typed.len() as u32);
+            tag_items(&counts, typed, start, cursor, Encoding::default())
+                .into_iter()
+                .map(|item| match item.text_edit {
+                    Some(CompletionTextEdit::Edit(edit)) => {
+                        (edit.range.start.character, edit.new_text)
+                    }
+                    _ => panic!("expected a text edit"),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            items("project/"),
+            vec![(9, "alpha".to_string()), (9, "beta".to_string())]
+        );
+        assert_eq!(
+            items("project/alpha/re"),
+            vec![(15, "design".to_string()), (15, "review".to_string())]
+        );
+        assert_eq!(items("ru").len(), 4);
+    }
+}
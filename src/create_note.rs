@@ -0,0 +1,130 @@
+use std::path::{Path, PathBuf};
+
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Command, CreateFile, CreateFileOptions,
+    DocumentChangeOperation, DocumentChanges, OneOf, OptionalVersionedTextDocumentIdentifier,
+    Position, Range, ResourceOp, TextDocumentEdit, TextEdit, Url, WorkspaceEdit,
+};
+
+use crate::vault;
+use crate::wikilink::WikiLink;
+
+/// The command that creates the note an unresolved link points at. Its arguments are the
+/// link path and the new note's title.
+pub const CREATE_NOTE_COMMAND: &str = "notemancy.createNote";
+
+/// The command that follows the link at a position, creating its note first when the
+/// link is unresolved. Its arguments are the document URI and the position; it returns
+/// the location the link leads to.
+pub const FOLLOW_LINK_COMMAND: &str = "notemancy.followLink";
+
+/// Returns the file a link to `link_path` resolves to once it exists: the path itself
+/// when it names a markdown file, otherwise the path with `.md` appended. Paths leading
+/// out of the vault are refused.
+pub fn note_path(vault_dir: &Path, link_path: &str) -> Option<PathBuf> {
+    let link_path = link_path.trim();
+    if link_path.is_empty() {
+        return None;
+    }
+    let file = if link_path.ends_with(".md") {
+        vault_dir.join(link_path)
+    } else {
+        vault_dir.join(format!("{}.md", link_path))
+    };
+    let file = vault::normalize_path(&file);
+    file.starts_with(vault::normalize_path(vault_dir))
+        .then_some(file)
+}
+
+/// Names a new note after the link's alias, or after the last segment of its path.
+pub fn note_title(link: &WikiLink) -> String {
+    match link.alias.as_deref().map(str::trim) {
+        Some(alias) if !alias.is_empty() => alias.to_string(),
+        _ => {
+            let name = link.path.rsplit('/').next().unwrap_or(&link.path);
+            name.strip_suffix(".md").unwrap_or(name).to_string()
+        }
+    }
+}
+
+/// Offers to create the note behind the unresolved `link`.
+pub fn create_note_action(link: &WikiLink) -> Option<CodeActionOrCommand> {
+    if link.path.is_empty() {
+        return None;
+    }
+    let title = format!("Create note '{}'", link.path);
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: title.clone(),
+        kind: Some(CodeActionKind::QUICKFIX),
+        command: Some(Command {
+            title,
+            command: CREATE_NOTE_COMMAND.to_string(),
+            arguments: Some(vec![
+                serde_json::Value::String(link.path.clone()),
+                serde_json::Value::String(note_title(link)),
+            ]),
+        }),
+        ..Default::default()
+    }))
+}
+
+/// Builds the edit that creates `file` holding a frontmatter block with its `title`.
+pub fn create_note_edit(file: &Path, title: &str) -> Option<WorkspaceEdit> {
+    let uri = Url::from_file_path(file).ok()?;
+    let operations = vec![
+        DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+            uri: uri.clone(),
+            options: Some(CreateFileOptions {
+                overwrite: Some(false),
+                ignore_if_exists: Some(true),
+            }),
+            annotation_id: None,
+        })),
+        DocumentChangeOperation::Edit(TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier { uri, version: None },
+            edits: vec![OneOf::Left(TextEdit {
+                range: Range::new(Position::default(), Position::default()),
+                new_text: format!("---\ntitle: {}\n---\n", title.trim()),
+            })],
+        }),
+    ];
+    Some(WorkspaceEdit {
+        document_changes: Some(DocumentChanges::Operations(operations)),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wikilink::{find_wikilinks, Delimiters};
+
+    #[test]
+    fn test_note_path_matches_resolver() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-create-note-test");
+        let _ = std::fs::remove_dir_all(&vault_dir);
+        std::fs::create_dir_all(&vault_dir).unwrap();
+        let file = note_path(&vault_dir, "ideas/new note").unwrap();
+        assert_eq!(file, vault_dir.join("ideas/new note.md"));
+        assert_eq!(note_path(&vault_dir, "a.md"), Some(vault_dir.join("a.md")));
+        assert_eq!(note_path(&vault_dir, "../outside"), None);
+
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(&file, "").unwrap();
+        assert_eq!(
+            vault::resolve_link_target(&vault_dir, "ideas/new note"),
+            Some(file)
+        );
+        let _ = std::fs::remove_dir_all(&vault_dir);
+    }
+
+    #[test]
+    fn test_note_title() {
+        let links = find_wikilinks(
+            "[[ideas/new note]] [[x.md | Fresh Start]]",
+            &Delimiters::default(),
+        );
+        assert_eq!(note_title(&links[0]), "new note");
+        assert_eq!(note_title(&links[1]), "Fresh Start");
+    }
+}
@@ -0,0 +1,81 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::date;
+use crate::template;
+use crate::vault;
+
+/// The command that opens a daily note, creating it when missing. Its optional argument
+/// is a day offset from today, such as `-1` for yesterday; it returns the note's URI.
+pub const OPEN_DAILY_NOTE_COMMAND: &str = "notemancy.openDailyNote";
+
+/// Fills in the date placeholders of the daily note path `pattern`.
+pub fn daily_note_path(pattern: &str, date: (i64, u32, u32)) -> String {
+    date::format_date(pattern, date)
+}
+
+/// Returns the daily note for `date`, creating it and its folders first when it doesn't
+/// exist yet. New notes are filled from `template` when there is one, and otherwise get
+/// a frontmatter block titled with the date.
+pub fn ensure_daily_note(
+    vault_dir: &Path,
+    pattern: &str,
+    date: (i64, u32, u32),
+    template: Option<&str>,
+) -> Result<PathBuf, String> {
+    let file = vault::normalize_path(&vault_dir.join(daily_note_path(pattern, date)));
+    if !file.starts_with(vault::normalize_path(vault_dir)) {
+        return Err(format!("daily note path is outside the vault: {}", pattern));
+    }
+    if file.exists() {
+        return Ok(file);
+    }
+    let title = daily_note_path("{{date}}", date);
+    let contents = match template {
+        Some(template) => template::expand(template, &title, &title),
+        None => format!("---\ntitle: {}\n---\n", title),
+    };
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::write(&file, contents).map_err(|e| e.to_string())?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daily_note_path() {
+        assert_eq!(
+            daily_note_path("journal/{{year}}/{{month}}/{{date}}.md", (2024, 3, 7)),
+            "journal/2024/03/2024-03-07.md"
+        );
+    }
+
+    #[test]
+    fn test_ensure_daily_note() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-daily-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(&vault_dir).unwrap();
+        let pattern = "journal/{{year}}/{{date}}.md";
+
+        let file = ensure_daily_note(&vault_dir, pattern, (2024, 3, 7), None).unwrap();
+        assert_eq!(file, vault_dir.join("journal/2024/2024-03-07.md"));
+        assert_eq!(
+            fs::read_to_string(&file).unwrap(),
+            "---\ntitle: 2024-03-07\n---\n"
+        );
+        fs::write(&file, "kept").unwrap();
+        ensure_daily_note(&vault_dir, pattern, (2024, 3, 7), Some("new")).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "kept");
+
+        let templated =
+            ensure_daily_note(&vault_dir, pattern, (2024, 3, 8), Some("# {{title}}")).unwrap();
+        assert_eq!(fs::read_to_string(templated).unwrap(), "# 2024-03-08");
+        assert!(ensure_daily_note(&vault_dir, "../{{date}}.md", (2024, 3, 8), None).is_err());
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+}
@@ -0,0 +1,85 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Names of the weekdays, Monday first.
+pub const WEEKDAYS: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+/// Today's date in UTC as `YYYY-MM-DD`.
+pub fn today() -> String {
+    let (year, month, day) = day_from_today(0);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// The UTC date `offset` days from today as `(year, month, day)`; a negative offset
+/// counts back, so `-1` is yesterday.
+pub fn day_from_today(offset: i64) -> (i64, u32, u32) {
+    civil_from_days(days_today() + offset)
+}
+
+/// Today in UTC, as a count of days since 1970-01-01.
+pub fn days_today() -> i64 {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    (secs / 86_400) as i64
+}
+
+/// The weekday of a count of days since 1970-01-01, as an index into `WEEKDAYS`.
+pub fn weekday(days: i64) -> usize {
+    // 1970-01-01 was a Thursday.
+    (days + 3).rem_euclid(7) as usize
+}
+
+/// Fills in the date placeholders of `pattern`: `{{year}}`, `{{month}}`, `{{day}}` and
+/// `{{date}}`, the last as `YYYY-MM-DD`.
+pub fn format_date(pattern: &str, (year, month, day): (i64, u32, u32)) -> String {
+    let date = format!("{:04}-{:02}-{:02}", year, month, day);
+    pattern
+        .replace("{{year}}", &format!("{:04}", year))
+        .replace("{{month}}", &format!("{:02}", month))
+        .replace("{{day}}", &format!("{:02}", day))
+        .replace("{{date}}", &date)
+}
+
+/// Converts a count of days since 1970-01-01 into a `(year, month, day)` date in the
+/// proleptic Gregorian calendar.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Shift the epoch to 0000-03-01 so leap days fall at the end of each 400-year era.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19_723), (2024, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
+
+    #[test]
+    fn test_weekday() {
+        assert_eq!(WEEKDAYS[weekday(0)], "thursday");
+        assert_eq!(WEEKDAYS[weekday(19_723)], "monday");
+        assert_eq!(WEEKDAYS[weekday(-1)], "wednesday");
+    }
+}
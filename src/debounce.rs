@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+use url::Url;
+
+/// Delays work on a document until its changes settle: scheduling a task for a document
+/// cancels the one still waiting for it, so a burst of edits runs the task only once,
+/// after the last of them.
+#[derive(Clone, Debug, Default)]
+pub struct Debouncer {
+    /// The task waiting for each document.
+    pending: Arc<Mutex<HashMap<Url, Waiting>>>,
+    /// Numbers the scheduled tasks, so a finished task only forgets its own entry.
+    scheduled: Arc<AtomicU64>,
+}
+
+/// A scheduled task, and the number it was scheduled under.
+#[derive(Debug)]
+struct Waiting {
+    id: u64,
+    handle: JoinHandle<()>,
+}
+
+impl Debouncer {
+    /// Runs `task` for `uri` after `delay`, unless another task is scheduled for the same
+    /// document first.
+    pub fn schedule<F>(&self, uri: Url, delay: Duration, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let id = self.scheduled.fetch_add(1, Ordering::Relaxed);
+        let pending = self.pending.clone();
+        let key = uri.clone();
+        // Held while the task is spawned, so it can't finish before its entry exists.
+        let mut waiting = self.pending.lock().unwrap();
+        let handle = tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            task.await;
+            let mut pending = pending.lock().unwrap();
+            if pending.get(&key).is_some_and(|waiting| waiting.id == id) {
+                pending.remove(&key);
+            }
+        });
+        if let Some(previous) = waiting.insert(uri, Waiting { id, handle }) {
+            previous.handle.abort();
+        }
+    }
+
+    /// Cancels the task still waiting for `uri`, if any.
+    pub fn cancel(&self, uri: &Url) {
+        if let Some(waiting) = self.pending.lock().unwrap().remove(uri) {
+            waiting.handle.abort();
+        }
+    }
+
+    /// Cancels every task still waiting and waits up to `timeout` for them to stop, so
+    /// none runs once the server is shutting down.
+    pub async fn shutdown(&self, timeout: Duration) {
+        let handles: Vec<JoinHandle<()>> = self
+            .pending
+            .lock()
+            .unwrap()
+            .drain()
+            .map(|(_, waiting)| waiting.handle)
+            .collect();
+        for handle in &handles {
+            handle.abort();
+        }
+        let joined = async {
+            for handle in handles {
+                let _ = handle.await;
+            }
+        };
+        let _ = tokio::time::timeout(timeout, joined).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    /// Lets the spawned tasks start their timers, moves the paused clock forward by `ms`
+    /// and lets the tasks it wakes run.
+    async fn advance(ms: u64) {
+        tokio::task::yield_now().await;
+        tokio::time::advance(Duration::from_millis(ms)).await;
+        tokio::task::yield_now().await;
+    }
+
+    fn counting_task(runs: &Arc<AtomicUsize>) -> impl Future<Output = ()> {
+        let runs = runs.clone();
+        async move {
+            runs.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rapid_changes_run_once() {
+        let debouncer = Debouncer::default();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let uri = Url::parse("file:///vault/note.md").unwrap();
+        for _ in 0..5 {
+            debouncer.schedule(uri.clone(), Duration::from_millis(50), counting_task(&runs));
+            advance(5).await;
+        }
+        advance(45).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert!(debouncer.pending.lock().unwrap().is_empty());
+
+        let other = Url::parse("file:///vault/other.md").unwrap();
+        for uri in [uri, other] {
+            debouncer.schedule(uri, Duration::from_millis(10), counting_task(&runs));
+        }
+        advance(10).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        assert!(debouncer.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancel() {
+        let debouncer = Debouncer::default();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let uri = Url::parse("file:///vault/note.md").unwrap();
+        debouncer.schedule(uri.clone(), Duration::from_millis(20), counting_task(&runs));
+        debouncer.cancel(&uri);
+        advance(40).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+        assert!(debouncer.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shutdown_cancels_pending() {
+        let debouncer = Debouncer::default();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let uri = Url::parse("file:///vault/note.md").unwrap();
+        debouncer.schedule(uri, Duration::from_millis(20), counting_task(&runs));
+        debouncer.shutdown(Duration::from_secs(1)).await;
+        advance(60).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+        assert!(debouncer.pending.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+
+use crate::frontmatter;
+use crate::heading;
+use crate::position::{self, Encoding};
+use crate::vault;
+use crate::wikilink::{self, Delimiters};
+
+/// Reports a link with nothing to point at, such as `[[]]` or `[[ | text]]`, as an error,
+/// and a link with nothing after its `|`, such as `[[path | ]]`, as a warning. A same-note
+/// `[[#heading]]` or `[[^block]]` link has no path but isn't empty.
+fn empty_link_diagnostic(
+    text: &str,
+    link: &wikilink::WikiLink,
+    encoding: Encoding,
+) -> Option<Diagnostic> {
+    let (severity, message) =
+        if link.path.is_empty() && link.anchor.is_none() && link.block.is_none() {
+            (DiagnosticSeverity::ERROR, "Empty wiki-link")
+        } else if link.alias.as_deref() == Some("") {
+            (DiagnosticSeverity::WARNING, "Empty wiki-link alias")
+        } else {
+            return None;
+        };
+    Some(Diagnostic {
+        range: position::offset_range(text, &link.range, encoding),
+        severity: Some(severity),
+        source: Some("notemancy".to_string()),
+        message: message.to_string(),
+        ..Default::default()
+    })
+}
+
+/// Reports every wiki-link in `text` whose target doesn't exist in the vault, either as a
+/// path or as the title or alias of one of the indexed `pages`. A name shared by several
+/// pages is reported as ambiguous, and empty links and aliases are reported too. Links
+/// inside code are literal text and never reported. `./` and `../` links are taken
+/// relative to `file`, the document's own file if it has one.
+pub fn broken_link_diagnostics(
+    text: &str,
+    file: Option<&Path>,
+    vault_dir: &Path,
+    pages: &[vault::Page],
+    delimiters: &Delimiters,
+    encoding: Encoding,
+) -> Vec<Diagnostic> {
+    let links = wikilink::find_wikilinks(text, delimiters);
+    let empty = links
+        .iter()
+        .filter_map(|link| empty_link_diagnostic(text, link, encoding));
+    let broken = links
+        .iter()
+        .filter(|link| !link.path.is_empty())
+        .filter(|link| {
+            let path = match file {
+                Some(file) => vault::vault_link_path(vault_dir, file, &link.path),
+                None => link.path.clone(),
+            };
+            vault::resolve_link_target(vault_dir, &path).is_none()
+        })
+        .filter_map(|link| {
+            let message = match vault::pages_named(pages, &link.path).len() {
+                0 => format!("Unresolved wiki-link: {}", link.path),
+                1 => return None,
+                n => format!("Ambiguous wiki-link: {} matches {} notes", link.path, n),
+            };
+            Some(Diagnostic {
+                range: position::offset_range(text, &link.range, encoding),
+                severity: Some(DiagnosticSeverity::WARNING),
+                source: Some("notemancy".to_string()),
+                message,
+                ..Default::default()
+            })
+        });
+    let mut diagnostics: Vec<Diagnostic> = empty.chain(broken).collect();
+    diagnostics.sort_by_key(|diagnostic| diagnostic.range.start);
+    diagnostics
+}
+
+/// Reports the wiki-links in `text`, the content of `file`, that resolve to `file` itself,
+/// which is usually a mistake. `[[#Heading]]` anchors have no path and aren't reported.
+pub fn self_link_diagnostics(
+    text: &str,
+    file: &Path,
+    vault_dir: &Path,
+    pages: &[vault::Page],
+    delimiters: &Delimiters,
+    encoding: Encoding,
+) -> Vec<Diagnostic> {
+    let file = vault::normalize_path(file);
+    wikilink::find_wikilinks(text, delimiters)
+        .into_iter()
+        .filter(|link| !link.path.is_empty())
+        .filter(|link| {
+            vault::resolve_note_link(vault_dir, &file, &link.path, pages)
+                .is_some_and(|target| vault::normalize_path(&target) == file)
+        })
+        .map(|link| Diagnostic {
+            range: position::offset_range(text, &link.range, encoding),
+            severity: Some(DiagnosticSeverity::INFORMATION),
+            source: Some("notemancy".to_string()),
+            message: format!("Self-link: {} points at this note", link.path),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Reports syntax errors in the frontmatter, and an opening `---` with no closing line,
+/// which leaves the whole note without frontmatter.
+pub fn frontmatter_diagnostics(text: &str, encoding: Encoding) -> Vec<Diagnostic> {
+    let line_range = |line: usize| Range {
+        start: Position::new(line as u32, 0),
+        end: position::line_end(text, line, encoding),
+    };
+    if text
+        .lines()
+        .next()
+        .is_some_and(|line| line.trim_end() == "---")
+        && frontmatter::frontmatter_range(text).is_none()
+    {
+        return vec![Diagnostic {
+            range: line_range(0),
+            severity: Some(DiagnosticSeverity::WARNING),
+            source: Some("notemancy".to_string()),
+            message: "Frontmatter is never closed: add a `---` line after it".to_string(),
+            ..Default::default()
+        }];
+    }
+    frontmatter::syntax_errors(text)
+        .into_iter()
+        .map(|error| Diagnostic {
+            range: line_range(error.line),
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some("notemancy".to_string()),
+            message: format!("Malformed frontmatter: {}", error.message),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Hints at every heading whose name is repeated in `text`, since a plain `#Name` anchor
+/// only reaches the first of them. Each hint gives the occurrence number and the anchor
+/// that links to that occurrence.
+pub fn duplicate_heading_diagnostics(text: &str, encoding: Encoding) -> Vec<Diagnostic> {
+    let headings = heading::parse_headings(text);
+    let mut groups: HashMap<String, Vec<&heading::Heading>> = HashMap::new();
+    for heading in &headings {
+        groups
+            .entry(wikilink::normalize_heading(&heading.title))
+            .or_default()
+            .push(heading);
+    }
+    let mut diagnostics = Vec::new();
+    for heading in &headings {
+        let group = &groups[&wikilink::normalize_heading(&heading.title)];
+        if group.len() < 2 {
+            continue;
+        }
+        let occurrence = group
+            .iter()
+            .position(|other| other.line == heading.line)
+            .map_or(1, |index| index + 1);
+        let anchor = match occurrence {
+            1 => format!("#{}", heading.title),
+            n => format!("#{}#{}", heading.title, n),
+        };
+        diagnostics.push(Diagnostic {
+            range: Range {
+                start: Position::new(heading.line as u32, 0),
+                end: position::line_end(text, heading.line, encoding),
+            },
+            severity: Some(DiagnosticSeverity::HINT),
+            source: Some("notemancy".to_string()),
+            message: format!(
+                "Duplicate heading \"{}\" (occurrence {} of {}); link to it with {}",
+                heading.title,
+                occurrence,
+                group.len(),
+                anchor
+            ),
+            ..Default::default()
+        });
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_broken_link_diagnostics() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-diagnostics-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(&vault_dir).unwrap();
+        fs::write(vault_dir.join("exists.md"), "# Exists\n").unwrap();
+
+        let text = "[[exists]] [[missing | alias]]\n```\n[[in-code]]\n```\n";
+        let diagnostics = broken_link_diagnostics(
+            text,
+            None,
+            &vault_dir,
+            &[],
+            &Delimiters::default(),
+            Encoding::default(),
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Unresolved wiki-link: missing");
+        assert_eq!(diagnostics[0].range.start.character, 11);
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+
+    #[test]
+    fn test_broken_link_diagnostics_with_custom_delimiters() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-diagnostics-delimiters-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(&vault_dir).unwrap();
+        fs::write(vault_dir.join("exists.md"), "# Exists\n").unwrap();
+
+        let text = "%%exists%% %%missing%% [[not a link]]\n";
+        let percent = Delimiters::new("%%", "%%");
+        let diagnostics =
+            broken_link_diagnostics(text, None, &vault_dir, &[], &percent, Encoding::default());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Unresolved wiki-link: missing");
+        assert_eq!(diagnostics[0].range.start.character, 11);
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+
+    #[test]
+    fn test_empty_link_diagnostics() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-empty-link-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(&vault_dir).unwrap();
+        fs::write(vault_dir.join("note.md"), "# Note\n").unwrap();
+
+        let reported = |text: &str| {
+            broken_link_diagnostics(
+                text,
+                None,
+                &vault_dir,
+                &[],
+                &Delimiters::default(),
+                Encoding::default(),
+            )
+            .into_iter()
+            .map(|diagnostic| {
+                let range =
+                    position::position_to_offset(text, diagnostic.range.start, Encoding::default())
+                        .unwrap()
+                        ..position::position_to_offset(
+                            text,
+                            diagnostic.range.end,
+                            Encoding::default(),
+                        )
+                        .unwrap();
+                (text[range].to_string(), diagnostic.severity.unwrap())
+            })
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            reported("[[]] and [[ ]]"),
+            vec![
+                ("[[]]".to_string(), DiagnosticSeverity::ERROR),
+                ("[[ ]]".to_string(), DiagnosticSeverity::ERROR)
+            ]
+        );
+        assert_eq!(
+            reported("[[ | text]]"),
+            vec![("[[ | text]]".to_string(), DiagnosticSeverity::ERROR)]
+        );
+        assert_eq!(
+            reported("[[note | ]]"),
+            vec![("[[note | ]]".to_string(), DiagnosticSeverity::WARNING)]
+        );
+        assert!(reported("[[#Note]] [[^block]] [[note | Note]]").is_empty());
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+
+    #[test]
+    fn test_title_links_resolve_or_are_ambiguous() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-title-diagnostics-test");
+        let page = |file: &str, title: &str| vault::Page {
+            file: vault_dir.join(file),
+            title: title.to_string(),
+            aliases: Vec::new(),
+        };
+        let pages = vec![
+            page("one.md", "Unique"),
+            page("two.md", "Twice"),
+            page("three.md", "twice"),
+        ];
+        let diagnostics = broken_link_diagnostics(
+            "[[Unique]] [[Twice]]",
+            None,
+            &vault_dir,
+            &pages,
+            &Delimiters::default(),
+            Encoding::default(),
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "Ambiguous wiki-link: Twice matches 2 notes"
+        );
+    }
+
+    #[test]
+    fn test_frontmatter_diagnostics() {
+        let diagnostics =
+            frontmatter_diagnostics("---\ntitle: x\n  - stray\n---\n", Encoding::default());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(diagnostics[0].range.start.line, 2);
+        assert_eq!(diagnostics[0].range.end.character, 9);
+
+        let diagnostics = frontmatter_diagnostics("---\ntitle: x\n\n# Body\n", Encoding::default());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert!(frontmatter_diagnostics("# No frontmatter\n---\n", Encoding::default()).is_empty());
+    }
+
+    #[test]
+    fn test_self_link_diagnostics() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-self-link-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(&vault_dir).unwrap();
+        let file = vault_dir.join("this-note.md");
+        fs::write(&file, "").unwrap();
+        fs::write(vault_dir.join("other.md"), "").unwrap();
+        let pages = vec![vault::Page {
+            file: file.clone(),
+            title: "This Note".to_string(),
+            aliases: Vec::new(),
+        }];
+
+        let text = "[[this-note]] [[#Heading]] [[other]] [[This Note | me]]";
+        let diagnostics = self_link_diagnostics(
+            text,
+            &file,
+            &vault_dir,
+            &pages,
+            &Delimiters::default(),
+            Encoding::default(),
+        );
+        let starts: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.range.start.character)
+            .collect();
+        assert_eq!(starts, vec![0, 37]);
+        assert_eq!(
+            diagnostics[0].severity,
+            Some(DiagnosticSeverity::INFORMATION)
+        );
+        assert_eq!(
+            diagnostics[0].message,
+            "Self-link: this-note points at this note"
+        );
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+
+    #[test]
+    fn test_duplicate_heading_diagnostics() {
+        let text = "# Log\n## Notes é\n## Other\n## notes é\n";
+        let diagnostics = duplicate_heading_diagnostics(text, Encoding::default());
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].range.start.line, 1);
+        // "é" is two bytes but one UTF-16 unit.
+        assert_eq!(diagnostics[0].range.end, Position::new(1, 10));
+        assert_eq!(
+            diagnostics[0].message,
+            "Duplicate heading \"Notes é\" (occurrence 1 of 2); link to it with #Notes é"
+        );
+        assert_eq!(diagnostics[1].range.start.line, 3);
+        assert_eq!(diagnostics[1].severity, Some(DiagnosticSeverity::HINT));
+        assert_eq!(
+            diagnostics[1].message,
+            "Duplicate heading \"notes é\" (occurrence 2 of 2); link to it with #notes é#2"
+        );
+    }
+}
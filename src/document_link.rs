@@ -0,0 +1,122 @@
+use std::fs;
+use std::path::Path;
+
+use tower_lsp::lsp_types::{DocumentLink, Url};
+
+use crate::frontmatter;
+use crate::position::{self, Encoding};
+use crate::vault;
+use crate::wikilink::{self, Delimiters};
+
+/// Turns every wiki-link in `text` into a `DocumentLink` covering just the path, so the
+/// alias isn't underlined. Targets are left for `resolve_document_link`; each link carries
+/// its path in `data` for that step.
+pub fn document_links(
+    text: &str,
+    delimiters: &Delimiters,
+    encoding: Encoding,
+) -> Vec<DocumentLink> {
+    wikilink::find_wikilinks(text, delimiters)
+        .into_iter()
+        .filter(|link| !link.path.is_empty())
+        .map(|link| DocumentLink {
+            range: position::offset_range(text, &link.path_range, encoding),
+            target: None,
+            tooltip: None,
+            data: Some(serde_json::Value::String(link.path)),
+        })
+        .collect()
+}
+
+/// Fills in the target of a link from `document_links`, with the linked note's title, or
+/// the image's file name for image links and embeds, as its tooltip. Links that don't resolve come back unchanged and stay non-clickable.
+pub fn resolve_document_link(
+    link: DocumentLink,
+    vault_dir: &Path,
+    pages: &[vault::Page],
+) -> DocumentLink {
+    let Some(path) = link.data.as_ref().and_then(|data| data.as_str()) else {
+        return link;
+    };
+    let Some(file) = vault::resolve_link(vault_dir, path, pages) else {
+        return link;
+    };
+    let Ok(target) = Url::from_file_path(&file) else {
+        return link;
+    };
+    if wikilink::is_image(path) {
+        let name = file.file_name().unwrap_or_default().to_string_lossy();
+        return DocumentLink {
+            target: Some(target),
+            tooltip: Some(format!("Image: {}", name)),
+            ..link
+        };
+    }
+    let title = fs::read_to_string(&file)
+        .ok()
+        .and_then(|content| frontmatter::title(&content))
+        .or_else(|| {
+            pages
+                .iter()
+                .find(|page| page.file == file)
+                .map(|page| page.title.clone())
+        })
+        .unwrap_or_else(|| {
+            file.file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        });
+    DocumentLink {
+        target: Some(target),
+        tooltip: Some(title),
+        ..link
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_links() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-document-link-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(&vault_dir).unwrap();
+        fs::write(vault_dir.join("target.md"), "---\ntitle: The Target\n---\n").unwrap();
+        fs::write(vault_dir.join("plain.md"), "# Plain\n").unwrap();
+
+        let text = "[[target | Alias]] [[nowhere]] [[plain]]";
+        let links = document_links(text, &Delimiters::default(), Encoding::default());
+        assert_eq!(links.len(), 3);
+        assert_eq!(links[0].range.start.character, 2);
+        assert_eq!(links[0].range.end.character, 8);
+        assert!(links.iter().all(|link| link.target.is_none()));
+
+        let resolved: Vec<_> = links
+            .into_iter()
+            .map(|link| resolve_document_link(link, &vault_dir, &[]))
+            .collect();
+        assert!(resolved[0].target.is_some());
+        assert_eq!(resolved[0].tooltip.as_deref(), Some("The Target"));
+        assert!(resolved[1].target.is_none());
+        assert_eq!(resolved[2].tooltip.as_deref(), Some("plain"));
+
+        fs::write(vault_dir.join("diagram.png"), b"\x89PNG").unwrap();
+        let embed = document_links(
+            "![[diagram.png]]",
+            &Delimiters::default(),
+            Encoding::default(),
+        )
+        .remove(0);
+        assert_eq!(embed.range.start.character, 3);
+        let embed = resolve_document_link(embed, &vault_dir, &[]);
+        assert_eq!(
+            embed.target,
+            Url::from_file_path(vault_dir.join("diagram.png")).ok()
+        );
+        assert_eq!(embed.tooltip.as_deref(), Some("Image: diagram.png"));
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+}
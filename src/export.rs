@@ -0,0 +1,528 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::frontmatter;
+use crate::heading;
+use crate::vault::{self, Page};
+use crate::wikilink::{self, Delimiters, WikiLink};
+
+/// The command that exports a note to a standalone HTML file. Its argument is the note's
+/// URI; it returns the path of the file written.
+pub const EXPORT_HTML_COMMAND: &str = "notemancy.exportHtml";
+
+/// Where the export of the note at `vpath` goes: the same path with an `.html` extension,
+/// under `export_dir` when one is set and next to the note otherwise.
+pub fn output_path(vault_dir: &Path, export_dir: Option<&Path>, vpath: &str) -> PathBuf {
+    let root = export_dir.unwrap_or(vault_dir);
+    root.join(vpath).with_extension("html")
+}
+
+/// Renders a note to a standalone HTML page. The frontmatter becomes a header with the
+/// title and a list of the other fields. `resolve` turns a wiki-link to another note into
+/// the `href` of its target, fragment included (see `anchored_href`), or `None` for
+/// unresolved links, which are rendered as plain text. Links to the note's own headings
+/// are resolved here.
+pub fn render_html(
+    text: &str,
+    fallback_title: &str,
+    delimiters: &Delimiters,
+    resolve: &dyn Fn(&WikiLink) -> Option<String>,
+) -> String {
+    let headings = heading::parse_headings(text);
+    let resolve = |link: &WikiLink| match &link.anchor {
+        Some(anchor) if link.path.is_empty() => {
+            Some(format!("#{}", heading::anchor_slug(&headings, anchor)))
+        }
+        _ => resolve(link),
+    };
+    let resolve: &dyn Fn(&WikiLink) -> Option<String> = &resolve;
+    let title = frontmatter::title(text)
+        .or_else(|| {
+            headings
+                .iter()
+                .find(|heading| heading.level == 1)
+                .map(|heading| heading.title.clone())
+        })
+        .unwrap_or_else(|| fallback_title.to_string());
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n",
+        escape(&title)
+    );
+    let fields: Vec<_> = frontmatter::fields(text)
+        .into_iter()
+        .filter(|field| field.key != "title")
+        .collect();
+    if frontmatter::frontmatter_range(text).is_some() {
+        html.push_str("<header>\n");
+        if let Some(title) = frontmatter::title(text) {
+            html.push_str(&format!("<h1>{}</h1>\n", escape(&title)));
+        }
+        if !fields.is_empty() {
+            html.push_str("<dl>\n");
+            for field in fields {
+                let value = if field.value.is_empty() {
+                    frontmatter::list_field(text, &field.key).join(", ")
+                } else {
+                    field.value
+                };
+                html.push_str(&format!(
+                    "<dt>{}</dt><dd>{}</dd>\n",
+                    escape(&field.key),
+                    escape(&value)
+                ));
+            }
+            html.push_str("</dl>\n");
+        }
+        html.push_str("</header>\n");
+    }
+    let body: Vec<&str> = frontmatter::strip_frontmatter(text).lines().collect();
+    html.push_str(&render_blocks(
+        &body,
+        delimiters,
+        resolve,
+        &mut heading::Slugger::default(),
+    ));
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// An open list and whether it is ordered.
+struct OpenList {
+    ordered: bool,
+}
+
+/// Renders block-level markdown: headings, paragraphs, lists, block quotes, fenced code
+/// and rules. Nested lists are flattened into their parent list. Headings get their ids
+/// from `slugger`, so repeated titles get distinct ones.
+fn render_blocks(
+    lines: &[&str],
+    delimiters: &Delimiters,
+    resolve: &dyn Fn(&WikiLink) -> Option<String>,
+    slugger: &mut heading::Slugger,
+) -> String {
+    let mut html = String::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut list: Option<OpenList> = None;
+    let mut i = 0;
+
+    let flush_paragraph = |html: &mut String, paragraph: &mut Vec<&str>| {
+        if !paragraph.is_empty() {
+            html.push_str(&format!(
+                "<p>{}</p>\n",
+                inline(&paragraph.join("\n"), delimiters, resolve)
+            ));
+            paragraph.clear();
+        }
+    };
+    let close_list = |html: &mut String, list: &mut Option<OpenList>| {
+        if let Some(open) = list.take() {
+            html.push_str(if open.ordered { "</ol>\n" } else { "</ul>\n" });
+        }
+    };
+
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim();
+        i += 1;
+
+        if let Some(fence) = ["```", "~~~"]
+            .into_iter()
+            .find(|fence| trimmed.starts_with(fence))
+        {
+            flush_paragraph(&mut html, &mut paragraph);
+            close_list(&mut html, &mut list);
+            let language = trimmed
+                .trim_start_matches(fence.chars().next().unwrap())
+                .trim();
+            let mut code = Vec::new();
+            while i < lines.len() && !lines[i].trim().starts_with(fence) {
+                code.push(lines[i]);
+                i += 1;
+            }
+            i += 1;
+            let class = if language.is_empty() {
+                String::new()
+            } else {
+                format!(" class=\"language-{}\"", escape(language))
+            };
+            html.push_str(&format!(
+                "<pre><code{}>{}</code></pre>\n",
+                class,
+                escape(&code.join("\n"))
+            ));
+            continue;
+        }
+        if trimmed.is_empty() {
+            flush_paragraph(&mut html, &mut paragraph);
+            close_list(&mut html, &mut list);
+            continue;
+        }
+        if let Some((level, title)) = heading_line(trimmed) {
+            flush_paragraph(&mut html, &mut paragraph);
+            close_list(&mut html, &mut list);
+            html.push_str(&format!(
+                "<h{level} id=\"{}\">{}</h{level}>\n",
+                slugger.slug(title),
+                inline(title, delimiters, resolve)
+            ));
+            continue;
+        }
+        if is_rule(trimmed) {
+            flush_paragraph(&mut html, &mut paragraph);
+            close_list(&mut html, &mut list);
+            html.push_str("<hr>\n");
+            continue;
+        }
+        if trimmed.starts_with('>') {
+            flush_paragraph(&mut html, &mut paragraph);
+            close_list(&mut html, &mut list);
+            let mut quoted = vec![strip_quote(trimmed)];
+            while i < lines.len() && lines[i].trim().starts_with('>') {
+                quoted.push(strip_quote(lines[i].trim()));
+                i += 1;
+            }
+            html.push_str(&format!(
+                "<blockquote>\n{}</blockquote>\n",
+                render_blocks(&quoted, delimiters, resolve, slugger)
+            ));
+            continue;
+        }
+        if let Some((ordered, item)) = list_item(trimmed) {
+            flush_paragraph(&mut html, &mut paragraph);
+            if list.as_ref().is_some_and(|open| open.ordered != ordered) {
+                close_list(&mut html, &mut list);
+            }
+            if list.is_none() {
+                html.push_str(if ordered { "<ol>\n" } else { "<ul>\n" });
+                list = Some(OpenList { ordered });
+            }
+            let (checkbox, item) = match item.get(..3) {
+                Some("[ ]") => (
+                    "<input type=\"checkbox\" disabled> ",
+                    item[3..].trim_start(),
+                ),
+                Some("[x]") | Some("[X]") => (
+                    "<input type=\"checkbox\" disabled checked> ",
+                    item[3..].trim_start(),
+                ),
+                _ => ("", item),
+            };
+            html.push_str(&format!(
+                "<li>{}{}</li>\n",
+                checkbox,
+                inline(item, delimiters, resolve)
+            ));
+            continue;
+        }
+        close_list(&mut html, &mut list);
+        paragraph.push(trimmed);
+    }
+    flush_paragraph(&mut html, &mut paragraph);
+    close_list(&mut html, &mut list);
+    html
+}
+
+/// Splits an ATX heading line into its level and title.
+fn heading_line(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let rest = &line[level..];
+    ((1..=6).contains(&level) && (rest.is_empty() || rest.starts_with(' ')))
+        .then(|| (level, rest.trim().trim_end_matches('#').trim_end()))
+}
+
+/// Returns true for a thematic break such as `---`, `***` or `_ _ _`.
+fn is_rule(line: &str) -> bool {
+    let marks: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    marks.len() >= 3 && matches!(marks[0], '-' | '*' | '_') && marks.iter().all(|c| *c == marks[0])
+}
+
+fn strip_quote(line: &str) -> &str {
+    let rest = &line[1..];
+    rest.strip_prefix(' ').unwrap_or(rest)
+}
+
+/// Splits a list item line into whether it is ordered and the item text.
+fn list_item(line: &str) -> Option<(bool, &str)> {
+    if let Some(rest) = line.strip_prefix(['-', '*', '+']) {
+        return rest
+            .strip_prefix(' ')
+            .map(|item| (false, item.trim_start()));
+    }
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits == 0 {
+        return None;
+    }
+    let rest = line[digits..].strip_prefix(['.', ')'])?;
+    rest.strip_prefix(' ').map(|item| (true, item.trim_start()))
+}
+
+/// Renders inline markdown: code spans, emphasis, links, images, wiki-links and embeds.
+fn inline(
+    text: &str,
+    delimiters: &Delimiters,
+    resolve: &dyn Fn(&WikiLink) -> Option<String>,
+) -> String {
+    let mut html = String::new();
+    let mut strong = false;
+    let mut em = false;
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        if rest.starts_with('`') {
+            let ticks = rest.chars().take_while(|c| *c == '`').count();
+            let fence = &rest[..ticks];
+            if let Some(len) = rest[ticks..].find(fence) {
+                let code = &rest[ticks..ticks + len];
+                html.push_str(&format!("<code>{}</code>", escape(code.trim())));
+                i += ticks * 2 + len;
+                continue;
+            }
+        }
+        let embed = rest
+            .strip_prefix('!')
+            .is_some_and(|rest| rest.starts_with(&delimiters.open));
+        if embed || rest.starts_with(&delimiters.open) {
+            let open = if embed { 1 } else { 0 };
+            if let Some(link) = wikilink::find_wikilinks(&rest[open..], delimiters)
+                .into_iter()
+                .next()
+                .filter(|link| link.range.start == 0)
+            {
+                html.push_str(&wiki_html(&link, embed, resolve));
+                i += open + link.range.end;
+                continue;
+            }
+        }
+        let image = rest.starts_with("![");
+        if image || rest.starts_with('[') {
+            let open = if image { 2 } else { 1 };
+            if let Some((label, target, len)) = inline_link(&rest[open..]) {
+                if image {
+                    html.push_str(&format!(
+                        "<img src=\"{}\" alt=\"{}\">",
+                        escape(target),
+                        escape(label)
+                    ));
+                } else {
+                    html.push_str(&format!(
+                        "<a href=\"{}\">{}</a>",
+                        escape(&html_target(target)),
+                        inline(label, delimiters, resolve)
+                    ));
+                }
+                i += open + len;
+                continue;
+            }
+        }
+        if rest.starts_with("**") {
+            html.push_str(if strong { "</strong>" } else { "<strong>" });
+            strong = !strong;
+            i += 2;
+            continue;
+        }
+        if rest.starts_with('*') {
+            html.push_str(if em { "</em>" } else { "<em>" });
+            em = !em;
+            i += 1;
+            continue;
+        }
+        let c = rest.chars().next().unwrap();
+        html.push_str(&escape(&c.to_string()));
+        i += c.len_utf8();
+    }
+    if em {
+        html.push_str("</em>");
+    }
+    if strong {
+        html.push_str("</strong>");
+    }
+    html
+}
+
+/// Parses the `label](target)` following a link's opening bracket, returning the label,
+/// the target and the length consumed.
+fn inline_link(rest: &str) -> Option<(&str, &str, usize)> {
+    let label_end = rest.find("](")?;
+    let label = &rest[..label_end];
+    if label.contains(['[', '\n']) {
+        return None;
+    }
+    let target_start = label_end + 2;
+    let target_len = rest[target_start..].find(')')?;
+    let target = rest[target_start..target_start + target_len].trim();
+    Some((label, target, target_start + target_len + 1))
+}
+
+/// Adds the fragment of `link`'s anchor to `href`, the page of the note it points at,
+/// whose source is `target`. The fragment is the id the export gives the heading the
+/// anchor resolves to, so `Setup#2` points at the second `Setup`.
+pub fn anchored_href(href: String, link: &WikiLink, target: &str) -> String {
+    match &link.anchor {
+        Some(anchor) => format!(
+            "{}#{}",
+            href,
+            heading::anchor_slug(&heading::parse_headings(target), anchor)
+        ),
+        None => href,
+    }
+}
+
+/// The `href` a wiki-link written in the note `file` gets in its export: the path from
+/// the note's page to the page of the note the link resolves to, fragment included, or
+/// `None` when it resolves to no note.
+pub fn link_href(vault_dir: &Path, file: &Path, link: &WikiLink, pages: &[Page]) -> Option<String> {
+    let vpath = vault::virtual_path(vault_dir, file)?;
+    let note_dir = Path::new(&vpath).parent().unwrap_or(Path::new(""));
+    let target = vault::resolve_note_link(vault_dir, file, &link.path, pages)?;
+    let target_vpath = vault::virtual_path(vault_dir, &target)?;
+    let href = vault::relative_path(note_dir, &Path::new(&target_vpath).with_extension("html"));
+    let content = fs::read_to_string(&target).unwrap_or_default();
+    Some(anchored_href(href, link, &content))
+}
+
+/// Points a relative markdown link at another note's exported page instead of its source.
+fn html_target(target: &str) -> String {
+    if target.contains("://") {
+        return target.to_string();
+    }
+    let (path, fragment) = match target.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (target, None),
+    };
+    let path = match path.strip_suffix(".md") {
+        Some(stem) => format!("{}.html", stem),
+        None => path.to_string(),
+    };
+    match fragment {
+        Some(fragment) => format!("{}#{}", path, fragment),
+        None => path,
+    }
+}
+
+fn wiki_html(
+    link: &WikiLink,
+    embed: bool,
+    resolve: &dyn Fn(&WikiLink) -> Option<String>,
+) -> String {
+    let label = link
+        .alias
+        .clone()
+        .filter(|alias| !alias.trim().is_empty())
+        .unwrap_or_else(|| match &link.anchor {
+            Some(anchor) if link.path.is_empty() => anchor.clone(),
+            _ => link.path.clone(),
+        });
+    if embed && wikilink::is_image(&link.path) {
+        return format!(
+            "<img src=\"{}\" alt=\"{}\">",
+            escape(&link.path),
+            escape(&label)
+        );
+    }
+    match resolve(link) {
+        Some(href) => format!("<a href=\"{}\">{}</a>", escape(&href), escape(label.trim())),
+        None => escape(label.trim()),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_html() {
+        let text = "---\ntitle: Plan <A>\ntags: [work]\n---\n# Goals\n\n\
+                    Some **bold** and *soft* `code`, see [[other | Other]] and [[#Goals]].\n\
+                    Missing [[gone]], [guide](docs/guide.md).\n\n- [x] done\n- next\n\n\
+                    > quoted\n\n```rust\nlet a = 1 < 2;\n```\n![[pic.png]]\n";
+        let html = render_html(text, "plan", &Delimiters::default(), &|link| {
+            (link.path == "other").then(|| "../other.html".to_string())
+        });
+        assert!(html.contains("<title>Plan &lt;A&gt;</title>"));
+        assert!(html.contains("<dt>tags</dt><dd>[work]</dd>"));
+        assert!(html.contains("<h1 id=\"goals\">Goals</h1>"));
+        assert!(html.contains(
+            "<p>Some <strong>bold</strong> and <em>soft</em> <code>code</code>, see \
+             <a href=\"../other.html\">Other</a> and <a href=\"#goals\">Goals</a>.\n\
+             Missing gone, <a href=\"docs/guide.html\">guide</a>.</p>"
+        ));
+        assert!(html.contains(
+            "<ul>\n<li><input type=\"checkbox\" disabled checked> done</li>\n<li>next</li>\n</ul>"
+        ));
+        assert!(html.contains("<blockquote>\n<p>quoted</p>\n</blockquote>"));
+        assert!(html.contains("<pre><code class=\"language-rust\">let a = 1 &lt; 2;</code></pre>"));
+        assert!(html.contains("<p><img src=\"pic.png\" alt=\"pic.png\"></p>"));
+    }
+
+    #[test]
+    fn test_heading_slugs() {
+        let text = "## Setup\nSee [[guide#My Heading!]], [[guide#Setup#2]] and [[#Setup#2]].\n\
+                    ## Setup\n";
+        let guide = "# Guide\n## My Heading!\n## Setup\n## Setup\n";
+        let html = render_html(text, "plan", &Delimiters::default(), &|link| {
+            Some(anchored_href("guide.html".to_string(), link, guide))
+        });
+        assert!(html.contains("<h2 id=\"setup\">Setup</h2>"));
+        assert!(html.contains("<h2 id=\"setup-1\">Setup</h2>"));
+        assert!(html.contains(
+            "<a href=\"guide.html#my-heading\">guide</a>, \
+             <a href=\"guide.html#setup-1\">guide</a> and <a href=\"#setup-1\">Setup#2</a>."
+        ));
+    }
+
+    #[test]
+    fn test_link_href() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-export-link-href-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(vault_dir.join("notes")).unwrap();
+        fs::create_dir_all(vault_dir.join("other")).unwrap();
+        let plan = vault_dir.join("notes/plan.md");
+        fs::write(&plan, "").unwrap();
+        fs::write(vault_dir.join("notes/sibling.md"), "").unwrap();
+        fs::write(vault_dir.join("other/deep.md"), "# Deep\n## Intro\n").unwrap();
+        let pages = vec![Page {
+            file: vault_dir.join("other/deep.md"),
+            title: "Deep Note".to_string(),
+            aliases: Vec::new(),
+        }];
+
+        let text = "[[./sibling]] [[../other/deep#Intro]] [[Deep Note]] [[other/deep]] \
+                    [[../../outside]] [[missing]]";
+        let hrefs: Vec<_> = wikilink::find_wikilinks(text, &Delimiters::default())
+            .iter()
+            .map(|link| link_href(&vault_dir, &plan, link, &pages))
+            .collect();
+        assert_eq!(
+            hrefs,
+            vec![
+                Some("sibling.html".to_string()),
+                Some("../other/deep.html#intro".to_string()),
+                Some("../other/deep.html".to_string()),
+                Some("../other/deep.html".to_string()),
+                None,
+                None,
+            ]
+        );
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+
+    #[test]
+    fn test_output_path() {
+        let vault_dir = Path::new("/vault");
+        assert_eq!(
+            output_path(vault_dir, None, "notes/plan.md"),
+            Path::new("/vault/notes/plan.html")
+        );
+        assert_eq!(
+            output_path(vault_dir, Some(Path::new("/site")), "notes/plan.md"),
+            Path::new("/site/notes/plan.html")
+        );
+    }
+}
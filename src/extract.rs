@@ -0,0 +1,194 @@
+use std::path::{Path, PathBuf};
+
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Command, CreateFile, CreateFileOptions,
+    DocumentChangeOperation, DocumentChanges, OneOf, OptionalVersionedTextDocumentIdentifier,
+    Position, Range, ResourceOp, TextDocumentEdit, TextEdit, Url, WorkspaceEdit,
+};
+
+use crate::position::{self, Encoding};
+use crate::vault;
+use crate::wikilink::Delimiters;
+
+/// The command that moves a selection into a new note. Its arguments are the note's URI,
+/// the selected range and the new note's name.
+pub const EXTRACT_TO_NOTE_COMMAND: &str = "notemancy.extractToNote";
+
+/// Suggests a note name from the first line of `selection`, dropping heading markers and
+/// characters that don't belong in a file name or a wiki-link.
+pub fn suggested_name(selection: &str) -> String {
+    let first_line = selection
+        .lines()
+        .map(|line| line.trim().trim_start_matches('#').trim())
+        .find(|line| !line.is_empty())
+        .unwrap_or_default();
+    let cleaned: String = first_line
+        .chars()
+        .filter(|c| {
+            !matches!(
+                c,
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '[' | ']' | '#' | '^'
+            )
+        })
+        .take(40)
+        .collect();
+    let cleaned = cleaned.trim();
+    if cleaned.is_empty() {
+        "Untitled".to_string()
+    } else {
+        cleaned.to_string()
+    }
+}
+
+/// Returns the path for a new note called `name` in `dir`, adding `-1`, `-2`, … to the
+/// name when a note with that name already exists.
+pub fn available_path(dir: &Path, name: &str) -> PathBuf {
+    let mut candidate = dir.join(format!("{}.md", name));
+    let mut suffix = 1;
+    while candidate.exists() {
+        candidate = dir.join(format!("{}-{}.md", name, suffix));
+        suffix += 1;
+    }
+    candidate
+}
+
+/// Builds the edit that creates a note next to the current one holding the text in
+/// `selection`, and replaces the selection with a link to it.
+pub fn extract_edit(
+    vault_dir: &Path,
+    uri: &Url,
+    text: &str,
+    selection: std::ops::Range<usize>,
+    name: &str,
+    delimiters: &Delimiters,
+    encoding: Encoding,
+) -> Option<WorkspaceEdit> {
+    let name = name.trim();
+    if name.is_empty() || selection.is_empty() {
+        return None;
+    }
+    let dir = uri.to_file_path().ok()?.parent()?.to_path_buf();
+    let new_file = available_path(&dir, name);
+    let new_vpath = vault::virtual_path(vault_dir, &new_file)?;
+    let new_uri = Url::from_file_path(&new_file).ok()?;
+    let mut contents = text.get(selection.clone())?.to_string();
+    if !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    let link = delimiters.link(
+        new_vpath.strip_suffix(".md").unwrap_or(&new_vpath),
+        Some(name),
+    );
+
+    let operations = vec![
+        DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+            uri: new_uri.clone(),
+            options: Some(CreateFileOptions {
+                overwrite: Some(false),
+                ignore_if_exists: Some(false),
+            }),
+            annotation_id: None,
+        })),
+        DocumentChangeOperation::Edit(TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier {
+                uri: new_uri,
+                version: None,
+            },
+            edits: vec![OneOf::Left(TextEdit {
+                range: Range::new(Position::default(), Position::default()),
+                new_text: contents,
+            })],
+        }),
+        DocumentChangeOperation::Edit(TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier {
+                uri: uri.clone(),
+                version: None,
+            },
+            edits: vec![OneOf::Left(TextEdit {
+                range: position::offset_range(text, &selection, encoding),
+                new_text: link,
+            })],
+        }),
+    ];
+    Some(WorkspaceEdit {
+        document_changes: Some(DocumentChanges::Operations(operations)),
+        ..Default::default()
+    })
+}
+
+/// Offers to extract the non-empty `range` of the note, named after its first line.
+pub fn extract_action(uri: &Url, range: Range, selection: &str) -> Option<CodeActionOrCommand> {
+    if selection.trim().is_empty() {
+        return None;
+    }
+    let name = suggested_name(selection);
+    let title = format!("Extract to new note \"{}\"", name);
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: title.clone(),
+        kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+        command: Some(Command {
+            title,
+            command: EXTRACT_TO_NOTE_COMMAND.to_string(),
+            arguments: Some(vec![
+                serde_json::Value::String(uri.to_string()),
+                serde_json::to_value(range).ok()?,
+                serde_json::Value::String(name),
+            ]),
+        }),
+        ..Default::default()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_suggested_name() {
+        assert_eq!(
+            suggested_name("\n## Ideas: big/small\nmore"),
+            "Ideas bigsmall"
+        );
+        assert_eq!(suggested_name("  \n"), "Untitled");
+    }
+
+    #[test]
+    fn test_extract_edit() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-extract-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(vault_dir.join("notes")).unwrap();
+        fs::write(vault_dir.join("notes/Ideas.md"), "taken").unwrap();
+        let uri = Url::from_file_path(vault_dir.join("notes/source.md")).unwrap();
+
+        let text = "Intro\nBig idea\nOutro\n";
+        let edit = extract_edit(
+            &vault_dir,
+            &uri,
+            text,
+            6..14,
+            "Ideas",
+            &Delimiters::default(),
+            Encoding::default(),
+        )
+        .unwrap();
+        let Some(DocumentChanges::Operations(operations)) = edit.document_changes else {
+            panic!("expected document change operations");
+        };
+        let DocumentChangeOperation::Op(ResourceOp::Create(create)) = &operations[0] else {
+            panic!("expected the new note to be created first");
+        };
+        assert!(create.uri.path().ends_with("/notes/Ideas-1.md"));
+        let DocumentChangeOperation::Edit(source) = &operations[2] else {
+            panic!("expected an edit of the source note");
+        };
+        let OneOf::Left(replacement) = &source.edits[0] else {
+            panic!("expected a plain text edit");
+        };
+        assert_eq!(replacement.new_text, "[[notes/Ideas-1 | Ideas]]");
+        assert_eq!(replacement.range.start, Position::new(1, 0));
+        assert_eq!(replacement.range.end, Position::new(1, 8));
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+}
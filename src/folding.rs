@@ -0,0 +1,51 @@
+use tower_lsp::lsp_types::{FoldingRange, FoldingRangeKind};
+
+use crate::heading;
+use crate::markdown_context;
+
+/// Computes folding ranges for every heading section and fenced code block in `text`.
+/// A section folds from its heading to the line before the next heading of equal or
+/// higher level, so nested sections produce nested ranges.
+pub fn folding_ranges(text: &str) -> Vec<FoldingRange> {
+    let last_line = text.lines().count().saturating_sub(1);
+    let headings = heading::parse_headings(text);
+    let mut ranges = Vec::new();
+    for (index, heading) in headings.iter().enumerate() {
+        let end_line = heading::section_end_line(&headings, index, last_line);
+        if end_line > heading.line {
+            ranges.push(FoldingRange {
+                start_line: heading.line as u32,
+                end_line: end_line as u32,
+                kind: Some(FoldingRangeKind::Region),
+                ..Default::default()
+            });
+        }
+    }
+    for code in markdown_context::fenced_code_ranges(text) {
+        let start_line = text[..code.start].matches('\n').count() as u32;
+        let end_line = text[..code.end.saturating_sub(1)].matches('\n').count() as u32;
+        if end_line > start_line {
+            ranges.push(FoldingRange {
+                start_line,
+                end_line,
+                ..Default::default()
+            });
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_folding_ranges() {
+        let text = "# A\nintro\n## B\n```\ncode\n```\n# C\nlast";
+        let ranges: Vec<_> = folding_ranges(text)
+            .into_iter()
+            .map(|range| (range.start_line, range.end_line))
+            .collect();
+        assert_eq!(ranges, vec![(0, 5), (2, 5), (6, 7), (3, 5)]);
+    }
+}
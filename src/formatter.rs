@@ -1,17 +1,223 @@
+use crate::frontmatter;
+use crate::markdown_context::FenceState;
+
+/// The formatter's configurable rules. The defaults are the formatter's original
+/// behavior.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Marker every bullet list item is written with: `-`, `*` or `+`.
+    pub bullet: char,
+    /// Longest run of blank lines kept; longer runs are collapsed to this many.
+    pub max_blank_lines: usize,
+    /// Put exactly one blank line after every heading.
+    pub blank_line_after_heading: bool,
+    /// End the document with a newline even when the input didn't.
+    pub ensure_final_newline: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            bullet: '-',
+            max_blank_lines: 1,
+            blank_line_after_heading: true,
+            ensure_final_newline: true,
+        }
+    }
+}
+
 /// Formats a markdown document according to basic markdown styling rules:
 /// - Trims trailing whitespace from each line.
 /// - Ensures that any heading (lines starting with '#' markers) has exactly one space after the '#' characters.
-/// - For any heading line, inserts an empty line immediately after.
-/// - Trims leading whitespace from non-heading lines.
-/// - Collapses multiple blank lines into a single blank line.
-/// - Ensures the output ends with a newline.
-pub fn format_markdown(input: &str) -> String {
+/// - For any heading line, inserts an empty line immediately after, unless
+///   `blank_line_after_heading` is off.
+/// - Trims leading whitespace from non-heading lines, except inside lists, where
+///   indentation sets the nesting level.
+/// - Renumbers ordered lists from their first item's number, per nesting level, starting
+///   over after any block that isn't part of the list.
+/// - Writes every bullet with the `bullet` marker.
+/// - Collapses runs of blank lines to at most `max_blank_lines`.
+/// - Ensures the output ends with a newline when `ensure_final_newline` is on.
+///
+/// Fenced code blocks (``` or ~~~) and the leading frontmatter block are passed through
+/// verbatim.
+pub fn format_markdown(input: &str, options: &FormatOptions) -> String {
+    let (mut result, body) = match frontmatter::frontmatter_range(input) {
+        Some(range) => (input[range.clone()].to_string(), &input[range.end..]),
+        None => (String::new(), input),
+    };
+    result.push_str(&format_lines(body, options, FenceState::default()).join("\n"));
+    if !result.ends_with('\n') && (options.ensure_final_newline || input.ends_with('\n')) {
+        result.push('\n');
+    }
+    result
+}
+
+/// Formats lines `first..=last` of `text` with the same rules as `format_markdown`,
+/// returning them without a final newline. The lines before the selection decide whether
+/// it starts inside the frontmatter or a fenced code block, whose lines are passed
+/// through unchanged. A heading on the last selected line doesn't get its blank line,
+/// since that would land outside the selection.
+pub fn format_markdown_range(
+    text: &str,
+    first: usize,
+    last: usize,
+    options: &FormatOptions,
+) -> String {
+    let all: Vec<&str> = text.lines().collect();
+    let Some(selected) = all.get(first..=last.min(all.len().saturating_sub(1))) else {
+        return String::new();
+    };
+    let frontmatter_lines =
+        frontmatter::frontmatter_range(text).map_or(0, |range| text[range].lines().count());
+    let mut fence = FenceState::default();
+    for line in all.iter().take(first).skip(frontmatter_lines) {
+        fence.update(line);
+    }
+    let (kept, body) =
+        selected.split_at(frontmatter_lines.clamp(first, first + selected.len()) - first);
+    let mut body_lines = format_lines(&body.join("\n"), options, fence);
+    let ends_blank = body.last().is_some_and(|line| line.trim().is_empty());
+    if !ends_blank && body_lines.last().is_some_and(|line| line.is_empty()) {
+        body_lines.pop();
+    }
+    let mut lines: Vec<String> = kept.iter().map(|line| line.to_string()).collect();
+    lines.extend(body_lines);
+    lines.join("\n")
+}
+
+/// A list item marker: a bullet, or an ordered number with its `.` or `)` delimiter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ListMarker {
+    Bullet,
+    Ordered { number: u64, delimiter: char },
+}
+
+/// Splits a list item line (without its indentation) into its marker and the text after
+/// the marker and its space. Returns `None` for lines that aren't list items, such as a
+/// `***` rule or `2024.` at the start of a sentence without a following space.
+fn list_item(content: &str) -> Option<(ListMarker, &str)> {
+    let digits = content.len()
+        - content
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .len();
+    let (marker, rest) = if digits == 0 {
+        let rest = content.strip_prefix(['-', '*', '+'])?;
+        (ListMarker::Bullet, rest)
+    } else {
+        let delimiter = content[digits..]
+            .chars()
+            .next()
+            .filter(|c| *c == '.' || *c == ')')?;
+        let number = content[..digits].parse().ok().filter(|_| digits <= 9)?;
+        (
+            ListMarker::Ordered { number, delimiter },
+            &content[digits + 1..],
+        )
+    };
+    if rest.is_empty() {
+        return Some((marker, rest));
+    }
+    rest.strip_prefix([' ', '\t']).map(|rest| (marker, rest))
+}
+
+/// Width of the leading whitespace `indent`, counting a tab as four columns.
+fn indent_width(indent: &str) -> usize {
+    indent.chars().map(|c| if c == '\t' { 4 } else { 1 }).sum()
+}
+
+/// Tracks the open list levels while formatting: each level is its indentation width
+/// and, for ordered lists, the number the next item gets.
+#[derive(Default)]
+struct ListState {
+    levels: Vec<(usize, Option<u64>)>,
+}
+
+impl ListState {
+    fn is_open(&self) -> bool {
+        !self.levels.is_empty()
+    }
+
+    fn close(&mut self) {
+        self.levels.clear();
+    }
+
+    /// Rewrites the marker of an item at `indent`, returning the marker text to emit.
+    fn item(&mut self, indent: usize, marker: ListMarker, bullet: char) -> String {
+        while self.levels.last().is_some_and(|(level, _)| *level > indent) {
+            self.levels.pop();
+        }
+        let same_level = self
+            .levels
+            .last()
+            .filter(|(level, _)| *level == indent)
+            .copied();
+        match marker {
+            ListMarker::Bullet => {
+                if !matches!(same_level, Some((_, None))) {
+                    if same_level.is_some() {
+                        self.levels.pop();
+                    }
+                    self.levels.push((indent, None));
+                }
+                bullet.to_string()
+            }
+            ListMarker::Ordered { number, delimiter } => {
+                let number = match same_level {
+                    Some((_, Some(next))) => {
+                        self.levels.pop();
+                        next
+                    }
+                    Some((_, None)) => {
+                        self.levels.pop();
+                        number
+                    }
+                    None => number,
+                };
+                self.levels.push((indent, Some(number + 1)));
+                format!("{}{}", number, delimiter)
+            }
+        }
+    }
+}
+
+/// Formats the lines of `input`, starting in the `fence` state of the lines before it.
+fn format_lines(input: &str, options: &FormatOptions, mut fence: FenceState) -> Vec<String> {
+    let bullet = if matches!(options.bullet, '-' | '*' | '+') {
+        options.bullet
+    } else {
+        '-'
+    };
     let mut output_lines = Vec::new();
-    let mut prev_blank = false;
+    // Number of blank lines just written.
+    let mut blank_run = 0;
+    let mut list = ListState::default();
     for line in input.lines() {
+        if fence.update(line) {
+            // Fence lines and the code between them are passed through unchanged.
+            output_lines.push(line.to_string());
+            blank_run = 0;
+            if !line.starts_with(char::is_whitespace) {
+                list.close();
+            }
+            continue;
+        }
         // Remove trailing whitespace.
         let trimmed = line.trim_end();
-        let formatted_line = if trimmed.starts_with('#') {
+        let content = trimmed.trim_start();
+        let indent = &trimmed[..trimmed.len() - content.len()];
+        let formatted_line = if let Some((marker, rest)) = list_item(content) {
+            let marker = list.item(indent_width(indent), marker, bullet);
+            if rest.is_empty() {
+                format!("{}{}", indent, marker)
+            } else {
+                format!("{}{} {}", indent, marker, rest)
+            }
+        } else if list.is_open() && !indent.is_empty() && !content.is_empty() {
+            // An indented line continues the list item above it.
+            trimmed.to_string()
+        } else if trimmed.starts_with('#') {
+            list.close();
             // Count the '#' markers.
             let mut count = 0;
             for c in trimmed.chars() {
@@ -33,8 +239,11 @@ pub fn format_markdown(input: &str) -> String {
                 "#".repeat(count)
             }
         } else {
+            if !content.is_empty() {
+                list.close();
+            }
             // For non-heading lines, trim any leading whitespace.
-            trimmed.trim_start().to_string()
+            content.to_string()
         };
 
         let is_blank = formatted_line.trim().is_empty();
@@ -42,25 +251,46 @@ pub fn format_markdown(input: &str) -> String {
         if !is_blank {
             // Push the formatted line.
             output_lines.push(formatted_line.clone());
-            // If it's a heading, insert an extra blank line.
-            if formatted_line.starts_with("#") {
+            blank_run = 0;
+            // If it's a heading, insert an extra blank line and drop the ones that follow.
+            if options.blank_line_after_heading && formatted_line.starts_with("#") {
                 output_lines.push(String::new());
-                prev_blank = true;
-                continue;
+                blank_run = usize::MAX;
             }
         } else {
-            // Avoid multiple consecutive blank lines.
-            if !prev_blank {
+            // Avoid long runs of consecutive blank lines.
+            if blank_run < options.max_blank_lines {
                 output_lines.push(String::new());
             }
+            blank_run = blank_run.saturating_add(1);
         }
-        prev_blank = is_blank;
     }
-    let mut result = output_lines.join("\n");
-    if !result.ends_with('\n') {
-        result.push('\n');
-    }
-    result
+    output_lines
+}
+
+/// Adjusts the trailing newlines of `formatted` to the editor's formatting preferences.
+/// `insert_final_newline: Some(false)` keeps a missing final newline missing, and
+/// `trim_final_newlines: Some(false)` keeps the original number of trailing newlines.
+/// When the editor doesn't say, `ensure_final_newline` decides whether a missing final
+/// newline is added.
+pub fn apply_final_newline_options(
+    formatted: &str,
+    original: &str,
+    insert_final_newline: Option<bool>,
+    trim_final_newlines: Option<bool>,
+    ensure_final_newline: bool,
+) -> String {
+    let body = formatted.trim_end_matches('\n');
+    let original_newlines = original.len() - original.trim_end_matches('\n').len();
+    let insert = insert_final_newline.unwrap_or(ensure_final_newline);
+    let newlines = if original_newlines == 0 && !insert {
+        0
+    } else if trim_final_newlines == Some(false) {
+        original_newlines.max(1)
+    } else {
+        1
+    };
+    format!("{}{}", body, "\n".repeat(newlines))
 }
 
 #[cfg(test)]
@@ -87,7 +317,205 @@ More text
 
 Text under heading2
 ";
-        let output = format_markdown(input);
+        let output = format_markdown(input, &FormatOptions::default());
         assert_eq!(output, expected);
     }
+
+    #[test]
+    fn test_format_markdown_keeps_frontmatter() {
+        let input = "---\ntitle:   Note  \ntags:\n  - rust\n    - nested\n---\n#Body\n  text\n";
+        assert_eq!(
+            format_markdown(input, &FormatOptions::default()),
+            "---\ntitle:   Note  \ntags:\n  - rust\n    - nested\n---\n# Body\n\ntext\n"
+        );
+        let only = "---\ntags:\n  - a\n---\n";
+        assert_eq!(format_markdown(only, &FormatOptions::default()), only);
+    }
+
+    #[test]
+    fn test_format_markdown_preserves_indented_code() {
+        let input = "\
+#Example
+```python
+def greet(name):
+    if name:
+        print(name)
+```
+";
+        let expected = "\
+# Example
+
+```python
+def greet(name):
+    if name:
+        print(name)
+```
+";
+        assert_eq!(format_markdown(input, &FormatOptions::default()), expected);
+    }
+
+    #[test]
+    fn test_format_markdown_ignores_headings_in_fence() {
+        let input = "\
+~~~yaml
+steps:
+  - run: build
+# comment
+
+
+  - run: test
+~~~
+";
+        assert_eq!(format_markdown(input, &FormatOptions::default()), input);
+    }
+
+    #[test]
+    fn test_apply_final_newline_options() {
+        assert_eq!(
+            apply_final_newline_options("a\n", "a", Some(false), None, true),
+            "a"
+        );
+        assert_eq!(
+            apply_final_newline_options("a\n", "a", Some(true), None, false),
+            "a\n"
+        );
+        assert_eq!(
+            apply_final_newline_options("a\n", "a\n\n\n", None, Some(true), true),
+            "a\n"
+        );
+        assert_eq!(
+            apply_final_newline_options("a\n", "a\n\n\n", None, Some(false), true),
+            "a\n\n\n"
+        );
+        assert_eq!(
+            apply_final_newline_options("a", "a", None, None, false),
+            "a"
+        );
+        assert_eq!(
+            apply_final_newline_options("a", "a", None, None, true),
+            "a\n"
+        );
+    }
+
+    #[test]
+    fn test_format_options() {
+        let input = "#Title\nIntro\n\n\n\n## Part\n\n\nText";
+        let options = FormatOptions {
+            max_blank_lines: 2,
+            blank_line_after_heading: false,
+            ensure_final_newline: false,
+            ..FormatOptions::default()
+        };
+        assert_eq!(
+            format_markdown(input, &options),
+            "# Title\nIntro\n\n\n## Part\n\n\nText"
+        );
+        assert_eq!(
+            format_markdown("#Title\nIntro\n", &options),
+            "# Title\nIntro\n"
+        );
+        let options = FormatOptions {
+            blank_line_after_heading: false,
+            ..FormatOptions::default()
+        };
+        assert_eq!(
+            format_markdown(input, &options),
+            "# Title\nIntro\n\n## Part\n\nText\n"
+        );
+    }
+
+    #[test]
+    fn test_format_markdown_range() {
+        let options = FormatOptions::default();
+        assert_eq!(
+            format_markdown_range("  text  \n##Heading", 0, 1, &options),
+            "text\n## Heading"
+        );
+        assert_eq!(
+            format_markdown_range("#Heading\nbody", 0, 1, &options),
+            "# Heading\n\nbody"
+        );
+        assert_eq!(
+            format_markdown_range("text\n\n\n", 0, 2, &options),
+            "text\n"
+        );
+    }
+
+    #[test]
+    fn test_format_markdown_range_keeps_code_and_frontmatter() {
+        let options = FormatOptions::default();
+        let text = "---\ntitle:   x  \n---\n```\n    indented code\n*  not a list\n```\n*  item";
+        assert_eq!(
+            format_markdown_range(text, 4, 5, &options),
+            "    indented code\n*  not a list"
+        );
+        assert_eq!(
+            format_markdown_range(text, 5, 7, &options),
+            "*  not a list\n```\n-  item"
+        );
+        assert_eq!(
+            format_markdown_range(text, 1, 3, &options),
+            "title:   x  \n---\n```"
+        );
+    }
+
+    #[test]
+    fn test_format_markdown_renumbers_nested_ordered_list() {
+        let input = "\
+1. One
+1. Two
+   1. Two a
+   1. Two b
+1. Three
+   3) Three c
+   3) Three d
+
+Paragraph
+5. Five
+5. Six
+";
+        let expected = "\
+1. One
+2. Two
+   1. Two a
+   2. Two b
+3. Three
+   3) Three c
+   4) Three d
+
+Paragraph
+5. Five
+6. Six
+";
+        assert_eq!(format_markdown(input, &FormatOptions::default()), expected);
+    }
+
+    #[test]
+    fn test_format_markdown_normalizes_bullets() {
+        let input = "\
+* one
++ two
+  * nested
+    continued
+- three
+***
+";
+        let expected = "\
+- one
+- two
+  - nested
+    continued
+- three
+***
+";
+        assert_eq!(format_markdown(input, &FormatOptions::default()), expected);
+        assert!(format_markdown(
+            input,
+            &FormatOptions {
+                bullet: '*',
+                ..FormatOptions::default()
+            },
+        )
+        .starts_with("* one\n* two\n  * nested"));
+    }
 }
@@ -0,0 +1,409 @@
+use std::ops::Range;
+
+use crate::settings::ListStyle;
+
+/// The command that sorts a note's frontmatter keys and rewrites its `tags` and
+/// `aliases` in one list style. Its argument is the note's URI; it returns the workspace
+/// edit it applied, or nothing when the frontmatter was already normalized.
+pub const NORMALIZE_FRONTMATTER_COMMAND: &str = "notemancy.normalizeFrontmatter";
+
+/// Keys whose values are lists the normalizer rewrites in the configured style.
+const LIST_KEYS: [&str; 2] = ["tags", "aliases"];
+
+/// Returns the byte range of the leading YAML frontmatter block, delimiters and the line
+/// break after the closing `---` included. The block must open on the very first line;
+/// the first later `---` line closes it, so a horizontal rule further down the body is
+/// never mistaken for the delimiter.
+pub fn frontmatter_range(text: &str) -> Option<Range<usize>> {
+    let mut lines = text.split_inclusive('\n');
+    let first = lines.next()?;
+    if first.trim_end() != "---" {
+        return None;
+    }
+    let mut offset = first.len();
+    for line in lines {
+        offset += line.len();
+        if line.trim_end() == "---" {
+            return Some(0..offset);
+        }
+    }
+    None
+}
+
+/// Returns `text` without its leading frontmatter block.
+pub fn strip_frontmatter(text: &str) -> &str {
+    match frontmatter_range(text) {
+        Some(range) => &text[range.end..],
+        None => text,
+    }
+}
+
+/// Reads a top-level list field such as `tags` or `aliases` from the frontmatter.
+/// Accepts inline lists (`tags: [a, b]`), block lists (`- a` on the following indented
+/// lines) and a plain comma-separated value (`tags: a, b`).
+pub fn list_field(text: &str, key: &str) -> Vec<String> {
+    let Some(range) = frontmatter_range(text) else {
+        return Vec::new();
+    };
+    let mut values = Vec::new();
+    let mut lines = text[range].lines().skip(1);
+    while let Some(line) = lines.next() {
+        let Some(value) = line
+            .strip_prefix(key)
+            .and_then(|rest| rest.strip_prefix(':'))
+        else {
+            continue;
+        };
+        let value = value.trim();
+        if value.is_empty() {
+            // The closing delimiter would otherwise read as a `-` item.
+            for item in lines.by_ref().take_while(|item| item.trim_end() != "---") {
+                match item.trim_start().strip_prefix('-') {
+                    Some(entry)
+                        if item.starts_with(char::is_whitespace) || item.starts_with('-') =>
+                    {
+                        values.push(unquote(entry));
+                    }
+                    _ => break,
+                }
+            }
+        } else {
+            let value = value
+                .strip_prefix('[')
+                .and_then(|inner| inner.strip_suffix(']'))
+                .unwrap_or(value);
+            values.extend(value.split(',').map(unquote));
+        }
+        break;
+    }
+    values.retain(|value| !value.is_empty());
+    values
+}
+
+/// A top-level key in the frontmatter block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub key: String,
+    /// The inline value after the colon; empty for block values.
+    pub value: String,
+    /// Zero-based line number of the key.
+    pub line: usize,
+}
+
+/// Lists the top-level keys of the frontmatter block in order. Nested and list lines
+/// belong to the key above them and are skipped, as are comments.
+pub fn fields(text: &str) -> Vec<Field> {
+    let Some(range) = frontmatter_range(text) else {
+        return Vec::new();
+    };
+    text[range]
+        .lines()
+        .enumerate()
+        .skip(1)
+        .filter(|(_, line)| !line.starts_with(|c: char| c.is_whitespace() || c == '-' || c == '#'))
+        .filter_map(|(line_num, line)| {
+            let (key, value) = line.split_once(':')?;
+            Some(Field {
+                key: key.trim().to_string(),
+                value: value.trim().to_string(),
+                line: line_num,
+            })
+        })
+        .filter(|field| !field.key.is_empty())
+        .collect()
+}
+
+/// Returns the frontmatter `title`, unquoted, unless it is missing or empty.
+pub fn title(text: &str) -> Option<String> {
+    fields(text)
+        .into_iter()
+        .find(|field| field.key == "title")
+        .map(|field| unquote(&field.value))
+        .filter(|title| !title.is_empty())
+}
+
+/// A top-level key of the frontmatter with the comment lines above it and the nested or
+/// list lines below it.
+struct Entry<'a> {
+    key: &'a str,
+    comments: Vec<&'a str>,
+    lines: Vec<&'a str>,
+}
+
+/// Rewrites the frontmatter of `text` with its keys sorted, those in `key_order` first
+/// and the rest alphabetically, and with `tags` and `aliases` written in `style`.
+/// Comments move along with the key below them. Returns the frontmatter's range and its
+/// replacement, or `None` when there is no frontmatter or nothing to change.
+pub fn normalize_frontmatter(
+    text: &str,
+    key_order: &[String],
+    style: ListStyle,
+) -> Option<(Range<usize>, String)> {
+    let range = frontmatter_range(text)?;
+    let lines: Vec<&str> = text[range.clone()].split_inclusive('\n').collect();
+    let closing = lines[lines.len() - 1];
+
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut comments = Vec::new();
+    for line in &lines[1..lines.len() - 1] {
+        let line = line.trim_end_matches(['\r', '\n']);
+        if line.trim().is_empty() {
+            continue;
+        }
+        let key = line.split_once(':').map(|(key, _)| key.trim());
+        match key {
+            Some(key) if !key.is_empty() && !line.starts_with([' ', '\t', '-', '#']) => {
+                entries.push(Entry {
+                    key,
+                    comments: std::mem::take(&mut comments),
+                    lines: vec![line],
+                });
+            }
+            _ if line.starts_with('#') => comments.push(line),
+            _ => match entries.last_mut() {
+                Some(entry) => entry.lines.push(line),
+                None => comments.push(line),
+            },
+        }
+    }
+    let rank = |key: &str| {
+        key_order
+            .iter()
+            .position(|ordered| ordered == key)
+            .unwrap_or(key_order.len())
+    };
+    entries.sort_by(|a, b| (rank(a.key), a.key).cmp(&(rank(b.key), b.key)));
+
+    let mut normalized = String::from("---\n");
+    for entry in &entries {
+        for line in &entry.comments {
+            normalized.push_str(line);
+            normalized.push('\n');
+        }
+        if LIST_KEYS.contains(&entry.key) {
+            normalized.push_str(&list_lines(entry.key, &list_field(text, entry.key), style));
+            continue;
+        }
+        for line in &entry.lines {
+            normalized.push_str(line);
+            normalized.push('\n');
+        }
+    }
+    for line in comments {
+        normalized.push_str(line);
+        normalized.push('\n');
+    }
+    normalized.push_str(closing);
+    (normalized != text[range.clone()]).then_some((range, normalized))
+}
+
+/// Writes the list field `key` with `values` in `style`, ending with a line break.
+fn list_lines(key: &str, values: &[String], style: ListStyle) -> String {
+    let values: Vec<String> = values.iter().map(|value| list_value(value)).collect();
+    if values.is_empty() {
+        return format!("{}: []\n", key);
+    }
+    match style {
+        ListStyle::Inline => format!("{}: [{}]\n", key, values.join(", ")),
+        ListStyle::Block => {
+            let items: String = values
+                .iter()
+                .map(|value| format!("  - {}\n", value))
+                .collect();
+            format!("{}:\n{}", key, items)
+        }
+    }
+}
+
+/// Quotes a list value that YAML would otherwise read as something else, such as a
+/// comment or a nested collection.
+fn list_value(value: &str) -> String {
+    if value.contains([',', ':', '[', ']', '{', '}', '"'])
+        || value.starts_with(['#', '&', '*', '!'])
+    {
+        format!("'{}'", value.replace('\'', "''"))
+    } else {
+        value.to_string()
+    }
+}
+
+/// A problem found in the frontmatter block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyntaxError {
+    /// Zero-based line number of the offending line.
+    pub line: usize,
+    pub message: String,
+}
+
+/// Checks the frontmatter against the subset of YAML notes use: top-level `key: value`
+/// lines, block lists and nested mappings below keys without an inline value, flow lists
+/// in brackets and quoted strings. Returns nothing for notes without frontmatter; an
+/// opening `---` that is never closed isn't frontmatter and is left to the caller.
+pub fn syntax_errors(text: &str) -> Vec<SyntaxError> {
+    let Some(range) = frontmatter_range(text) else {
+        return Vec::new();
+    };
+    let mut errors = Vec::new();
+    let mut error = |line: usize, message: String| errors.push(SyntaxError { line, message });
+    let mut keys = Vec::new();
+    // Whether the last key left its value to the indented lines below it.
+    let mut open_block = false;
+    let lines: Vec<&str> = text[range].lines().collect();
+    for (line_num, line) in lines.iter().enumerate().take(lines.len() - 1).skip(1) {
+        let content = line.trim();
+        if content.is_empty() || content.starts_with('#') {
+            continue;
+        }
+        let indent = &line[..line.len() - line.trim_start().len()];
+        if indent.contains('\t') {
+            error(line_num, "tabs can't be used for indentation".to_string());
+            continue;
+        }
+        if !indent.is_empty() || content.starts_with("- ") || content == "-" {
+            if !open_block {
+                error(line_num, "unexpected indentation or list item".to_string());
+            }
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            error(line_num, "expected a `key: value` line".to_string());
+            open_block = false;
+            continue;
+        };
+        let key = key.trim();
+        let value = value.trim();
+        if key.is_empty() {
+            error(line_num, "missing key before `:`".to_string());
+        } else if keys.contains(&key) {
+            error(line_num, format!("duplicate key `{}`", key));
+        } else {
+            keys.push(key);
+        }
+        open_block = value.is_empty() || value.starts_with('#');
+        if let Some(message) = value_error(value) {
+            error(line_num, message);
+        }
+    }
+    errors
+}
+
+/// Checks an inline value for unclosed quotes and brackets, and for a plain value that
+/// YAML would read as another mapping.
+fn value_error(value: &str) -> Option<String> {
+    let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'');
+    if let Some(quote) = quote {
+        let closed = value.len() > 1 && value[1..].contains(quote);
+        return (!closed).then(|| format!("unterminated {} string", quote));
+    }
+    for (open, close) in [('[', ']'), ('{', '}')] {
+        if value.starts_with(open) && !value.trim_end().ends_with(close) {
+            return Some(format!("unclosed `{}`", open));
+        }
+    }
+    if matches!(value.chars().next(), Some('|' | '>' | '[' | '{')) {
+        return None;
+    }
+    (value.contains(": ") || value.ends_with(':'))
+        .then(|| "a plain value can't contain `: `; quote it".to_string())
+}
+
+fn unquote(value: &str) -> String {
+    value
+        .trim()
+        .trim_matches(|c| c == '"' || c == '\'')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_syntax_errors() {
+        let text = "---\ntitle: Ok: not\ntags:\n  - a\n- b\nname: \"open\n\
+                    list: [a, b\nno colon\n\tindented\ntitle: again\n# comment\n---\nbody: [\n";
+        let errors: Vec<_> = syntax_errors(text)
+            .into_iter()
+            .map(|error| (error.line, error.message))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (1, "a plain value can't contain `: `; quote it".to_string()),
+                (5, "unterminated \" string".to_string()),
+                (6, "unclosed `[`".to_string()),
+                (7, "expected a `key: value` line".to_string()),
+                (8, "tabs can't be used for indentation".to_string()),
+                (9, "duplicate key `title`".to_string()),
+            ]
+        );
+        assert!(syntax_errors("---\ntags: [a]\nlinks:\n  home: x\n---\n").is_empty());
+        assert!(syntax_errors("---\ntitle: open\n").is_empty());
+    }
+
+    #[test]
+    fn test_frontmatter_range() {
+        let text = "---\ntitle: Note\n---\nBody\n\n---\n\nMore";
+        let range = frontmatter_range(text).unwrap();
+        assert_eq!(&text[range], "---\ntitle: Note\n---\n");
+        assert_eq!(strip_frontmatter(text), "Body\n\n---\n\nMore");
+
+        assert_eq!(frontmatter_range("Body\n---\ntitle: x\n---\n"), None);
+        assert_eq!(frontmatter_range("---\nnever closed\n"), None);
+    }
+
+    #[test]
+    fn test_list_field() {
+        let inline = "---\ntags: [rust, \"lsp\"]\n---\n";
+        assert_eq!(list_field(inline, "tags"), vec!["rust", "lsp"]);
+
+        let block = "---\ntitle: x\ntags:\n  - rust\n  - 'notes'\ndate: today\n---\n";
+        assert_eq!(list_field(block, "tags"), vec!["rust", "notes"]);
+        let last = "---\naliases:\n- Ada\n---\n";
+        assert_eq!(list_field(last, "aliases"), vec!["Ada"]);
+
+        assert_eq!(list_field("---\ntags: a, b\n---\n", "tags"), vec!["a", "b"]);
+        assert!(list_field("---\ntitle: x\n---\n", "tags").is_empty());
+        assert!(list_field("no frontmatter", "tags").is_empty());
+    }
+
+    #[test]
+    fn test_fields() {
+        let text = "---\ntitle: My Note\ntags:\n  - a\n# comment\ndate: 2024-01-01\n---\n# Body";
+        let fields = fields(text);
+        let keys: Vec<_> = fields.iter().map(|f| (f.key.as_str(), f.line)).collect();
+        assert_eq!(keys, vec![("title", 1), ("tags", 2), ("date", 5)]);
+        assert_eq!(fields[0].value, "My Note");
+        assert_eq!(title(text).as_deref(), Some("My Note"));
+        assert_eq!(title("---\ntitle: \"\"\n---\n"), None);
+        // The closing delimiter line is never a field.
+        assert!(super::fields("---\n---\n").is_empty());
+    }
+
+    #[test]
+    fn test_normalize_frontmatter() {
+        let order = ["title", "date", "tags", "aliases"].map(String::from);
+        let text = "---\nzone: x\ntags:\n  - rust\n  - lsp\n# when\ndate: 2024-01-01\n\
+                    extra:\n  nested: yes\ntitle: Note\naliases: Ada, \"Lady: A\"\n---\nBody\n";
+        let (range, normalized) = normalize_frontmatter(text, &order, ListStyle::Inline).unwrap();
+        assert_eq!(range, 0..text.len() - "Body\n".len());
+        assert_eq!(
+            normalized,
+            "---\ntitle: Note\n# when\ndate: 2024-01-01\ntags: [rust, lsp]\naliases: [Ada, 'Lady: A']\n\
+             extra:\n  nested: yes\nzone: x\n---\n"
+        );
+
+        let block = normalize_frontmatter(&normalized, &order, ListStyle::Block)
+            .unwrap()
+            .1;
+        assert!(block.contains("tags:\n  - rust\n  - lsp\naliases:\n  - Ada\n  - 'Lady: A'\n"));
+        assert_eq!(
+            normalize_frontmatter(&normalized, &order, ListStyle::Inline),
+            None
+        );
+        assert_eq!(
+            normalize_frontmatter("# No frontmatter\n", &order, ListStyle::Inline),
+            None
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use std::path::Path;
+
+use serde::Serialize;
+use tower_lsp::lsp_types::{Location, Url};
+
+use crate::link_index::LinkIndex;
+use crate::position::{self, Encoding};
+use crate::vault;
+use crate::wikilink;
+
+/// The command that lists a note's neighbors in the link graph. Its argument is the
+/// note's URI; it returns `{"outbound": [...], "inbound": [...]}` of `Neighbor`s.
+pub const LINK_NEIGHBORS_COMMAND: &str = "notemancy.linkNeighbors";
+
+/// A note linked to or from the current one.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Neighbor {
+    pub vpath: String,
+    pub title: String,
+    /// Where the link is: in the current note for outbound links, in the neighbor for
+    /// inbound ones.
+    pub location: Location,
+}
+
+/// The notes the current note links to, and the notes linking to it.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Neighbors {
+    pub outbound: Vec<Neighbor>,
+    pub inbound: Vec<Neighbor>,
+}
+
+/// Names a note by its indexed title, falling back to its file name.
+fn title_of(file: &Path, pages: &[vault::Page]) -> String {
+    pages
+        .iter()
+        .find(|page| page.file == file)
+        .map(|page| page.title.clone())
+        .unwrap_or_else(|| {
+            file.file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        })
+}
+
+/// Collects the neighbors of the note at `uri`, whose text is `text`. Each neighbor is
+/// listed once per direction, at its first link. Unresolved outbound links are left out.
+pub fn link_neighbors(
+    vault_dir: &Path,
+    uri: &Url,
+    text: &str,
+    pages: &[vault::Page],
+    index: &LinkIndex,
+    encoding: Encoding,
+) -> Neighbors {
+    let mut neighbors = Neighbors::default();
+    for link in wikilink::find_wikilinks(text, index.delimiters()) {
+        let Some(target) = vault::resolve_link(vault_dir, &link.path, pages) else {
+            continue;
+        };
+        let Some(vpath) = vault::virtual_path(vault_dir, &target) else {
+            continue;
+        };
+        if neighbors.outbound.iter().any(|n| n.vpath == vpath) {
+            continue;
+        }
+        neighbors.outbound.push(Neighbor {
+            title: title_of(&target, pages),
+            vpath,
+            location: Location {
+                uri: uri.clone(),
+                range: position::offset_range(text, &link.range, encoding),
+            },
+        });
+    }
+
+    let Some(vpath) = uri
+        .to_file_path()
+        .ok()
+        .and_then(|path| vault::virtual_path(vault_dir, &path))
+    else {
+        return neighbors;
+    };
+    for note in index.note_links(&vpath) {
+        let (Ok(file), Some(link)) = (note.uri.to_file_path(), note.links.first()) else {
+            continue;
+        };
+        if note.uri == *uri {
+            continue;
+        }
+        neighbors.inbound.push(Neighbor {
+            vpath: vault::virtual_path(vault_dir, &file).unwrap_or_default(),
+            title: title_of(&file, pages),
+            location: Location {
+                uri: note.uri.clone(),
+                range: position::offset_range(&note.content, &link.range, encoding),
+            },
+        });
+    }
+    neighbors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    use crate::wikilink::Delimiters;
+
+    #[test]
+    fn test_link_neighbors() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-graph-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(&vault_dir).unwrap();
+        fs::write(vault_dir.join("hub.md"), "").unwrap();
+        fs::write(vault_dir.join("out.md"), "").unwrap();
+        fs::write(vault_dir.join("in.md"), "First\n[[hub]] and [[hub#Top]]\n").unwrap();
+        let pages = vec![vault::Page {
+            file: vault_dir.join("out.md"),
+            title: "Outbound Note".to_string(),
+            aliases: Vec::new(),
+        }];
+        let uri = Url::from_file_path(vault_dir.join("hub.md")).unwrap();
+        let text = "[[out]] [[out.md]] [[missing]] [[hub]]\n";
+
+        let neighbors = link_neighbors(
+            &vault_dir,
+            &uri,
+            text,
+            &pages,
+            &LinkIndex::build(&vault_dir, &Delimiters::default()),
+            Encoding::default(),
+        );
+        let outbound: Vec<_> = neighbors
+            .outbound
+            .iter()
+            .map(|n| (n.vpath.as_str(), n.title.as_str()))
+            .collect();
+        assert_eq!(
+            outbound,
+            vec![("out.md", "Outbound Note"), ("hub.md", "hub")]
+        );
+        assert_eq!(neighbors.inbound.len(), 1);
+        assert_eq!(neighbors.inbound[0].vpath, "in.md");
+        assert_eq!(neighbors.inbound[0].location.range.start.line, 1);
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+}
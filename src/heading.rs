@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+
+use crate::frontmatter;
+use crate::markdown_context;
+use crate::wikilink;
+
+/// An ATX (`# Title`) or setext (`Title` underlined with `===` or `---`) heading in a
+/// markdown document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Heading {
+    pub level: usize,
+    pub title: String,
+    /// Zero-based line number of the heading.
+    pub line: usize,
+    /// Length of the heading line in bytes, excluding the line terminator.
+    pub line_len: usize,
+}
+
+/// Extracts the headings of `text` in document order. Lines inside fenced code blocks
+/// are never headings. A setext heading is reported on its text line; the underline is
+/// not part of it.
+pub fn parse_headings(text: &str) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    let mut fence = markdown_context::FenceState::default();
+    let body_start =
+        frontmatter::frontmatter_range(text).map_or(0, |range| text[range].lines().count());
+    // The previous line, when it could be the text of a setext heading.
+    let mut setext_text: Option<(usize, &str)> = None;
+    for (line_num, line) in text.lines().enumerate() {
+        if fence.update(line) {
+            setext_text = None;
+            continue;
+        }
+        if let Some(stripped) = line.strip_prefix('#') {
+            setext_text = None;
+            let mut level = 1;
+            let mut rest = stripped;
+            while rest.starts_with('#') {
+                level += 1;
+                rest = &rest[1..];
+            }
+            let title = rest.trim();
+            if title.is_empty() {
+                continue;
+            }
+            headings.push(Heading {
+                level,
+                title: title.to_string(),
+                line: line_num,
+                line_len: line.len(),
+            });
+            continue;
+        }
+        if let (Some(level), Some((text_line, text))) = (setext_level(line), setext_text) {
+            headings.push(Heading {
+                level,
+                title: text.trim().to_string(),
+                line: text_line,
+                line_len: text.len(),
+            });
+            setext_text = None;
+            continue;
+        }
+        setext_text =
+            (line_num >= body_start && can_be_setext_text(line)).then_some((line_num, line));
+    }
+    headings
+}
+
+/// Returns the heading level a setext underline stands for: 1 for `===`, 2 for `---`.
+fn setext_level(line: &str) -> Option<usize> {
+    if line.len() - line.trim_start().len() > 3 {
+        return None;
+    }
+    let underline = line.trim();
+    if !underline.is_empty() && underline.chars().all(|c| c == '=') {
+        Some(1)
+    } else if !underline.is_empty() && underline.chars().all(|c| c == '-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+/// Whether `line` can be the text of a setext heading: a plain paragraph line, not a list
+/// item, block quote, indented code or another underline.
+fn can_be_setext_text(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    let indent = line.len() - trimmed.len();
+    let numbered = trimmed
+        .split_once(['.', ')'])
+        .is_some_and(|(number, rest)| {
+            !number.is_empty()
+                && number.chars().all(|c| c.is_ascii_digit())
+                && (rest.is_empty() || rest.starts_with(' '))
+        });
+    !trimmed.is_empty()
+        && indent < 4
+        && !trimmed.starts_with('>')
+        && !["- ", "* ", "+ "]
+            .iter()
+            .any(|marker| trimmed.starts_with(marker))
+        && !numbered
+        && setext_level(line).is_none()
+}
+
+/// Returns the last line of the section started by `headings[index]`: the line before the
+/// next heading of equal or higher level, or `last_line` when the section runs to the end.
+pub fn section_end_line(headings: &[Heading], index: usize, last_line: usize) -> usize {
+    let level = headings[index].level;
+    headings[index + 1..]
+        .iter()
+        .find(|heading| heading.level <= level)
+        .map_or(last_line, |heading| heading.line.saturating_sub(1))
+}
+
+/// Finds the heading that a link anchor points at, comparing names case-insensitively
+/// and ignoring differences in whitespace.
+///
+/// When a note repeats a heading name, the plain anchor picks the first occurrence.
+/// Later ones are numbered in two ways:
+/// - `Notes#2` counts occurrences from 1, so it picks the second `Notes`;
+/// - `Notes-1` follows GitHub's duplicate-slug suffixes, which count repeats from 1, so
+///   it also picks the second `Notes`.
+///
+/// A heading whose name matches the whole anchor, such as `Step-1`, always wins over
+/// reading the anchor as numbered.
+pub fn resolve_anchor<'a>(headings: &'a [Heading], anchor: &str) -> Option<&'a Heading> {
+    let nth = |name: &str, n: usize| {
+        let wanted = wikilink::normalize_heading(name);
+        headings
+            .iter()
+            .filter(|heading| wikilink::normalize_heading(&heading.title) == wanted)
+            .nth(n)
+    };
+    if let Some(heading) = nth(anchor, 0) {
+        return Some(heading);
+    }
+    if let Some((name, n)) = anchor.rsplit_once('#') {
+        if let Ok(n) = n.trim().parse::<usize>() {
+            return n.checked_sub(1).and_then(|index| nth(name, index));
+        }
+    }
+    if let Some((name, n)) = anchor.rsplit_once('-') {
+        if let Ok(n) = n.trim().parse::<usize>() {
+            return nth(name, n);
+        }
+    }
+    None
+}
+
+/// The GitHub-style anchor slug of a heading `title`: lowercased, with spaces as dashes
+/// and punctuation other than `-` and `_` dropped, so `My Heading!` becomes `my-heading`.
+pub fn slugify_heading(title: &str) -> String {
+    title
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Hands out the slugs of a document's headings in order, numbering repeats as GitHub
+/// does: the second `Notes` becomes `notes-1` and the third `notes-2`, skipping any
+/// number another heading already took.
+#[derive(Debug, Default)]
+pub struct Slugger {
+    occurrences: HashMap<String, usize>,
+}
+
+impl Slugger {
+    /// The slug of the next heading, titled `title`.
+    pub fn slug(&mut self, title: &str) -> String {
+        let base = slugify_heading(title);
+        let mut slug = base.clone();
+        while self.occurrences.contains_key(&slug) {
+            let count = self.occurrences.entry(base.clone()).or_default();
+            *count += 1;
+            slug = format!("{}-{}", base, count);
+        }
+        self.occurrences.insert(slug.clone(), 0);
+        slug
+    }
+}
+
+/// The slugs of `headings`, in the same order.
+pub fn heading_slugs(headings: &[Heading]) -> Vec<String> {
+    let mut slugger = Slugger::default();
+    headings
+        .iter()
+        .map(|heading| slugger.slug(&heading.title))
+        .collect()
+}
+
+/// The slug that the link `anchor` stands for in a document with `headings`: that of the
+/// heading it resolves to, so a repeated heading gets its numbered slug, or the anchor
+/// slugified when no heading matches.
+pub fn anchor_slug(headings: &[Heading], anchor: &str) -> String {
+    resolve_anchor(headings, anchor)
+        .and_then(|target| {
+            headings
+                .iter()
+                .position(|heading| heading.line == target.line)
+        })
+        .map(|index| heading_slugs(headings).swap_remove(index))
+        .unwrap_or_else(|| slugify_heading(anchor))
+}
+
+/// Finds the heading whose slug is `slug`, the reverse of `anchor_slug`.
+pub fn heading_by_slug<'a>(headings: &'a [Heading], slug: &str) -> Option<&'a Heading> {
+    heading_slugs(headings)
+        .iter()
+        .position(|candidate| candidate == slug)
+        .map(|index| &headings[index])
+}
+
+/// The anchor a wiki-link writes to point at `target`, one of `headings`: its title,
+/// with `#n` added when it is the nth heading of that name, as `resolve_anchor` reads it.
+pub fn heading_anchor(headings: &[Heading], target: &Heading) -> String {
+    let name = wikilink::normalize_heading(&target.title);
+    let earlier = headings
+        .iter()
+        .take_while(|heading| heading.line != target.line)
+        .filter(|heading| wikilink::normalize_heading(&heading.title) == name)
+        .count();
+    match earlier {
+        0 => target.title.clone(),
+        n => format!("{}#{}", target.title, n + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_headings_skips_code() {
+        let text = "# Title\n```python\n# comment\n```\n## Section\n";
+        let headings = parse_headings(text);
+        assert_eq!(headings.len(), 2);
+        assert_eq!(headings[0].title, "Title");
+        assert_eq!(headings[1].level, 2);
+        assert_eq!(headings[1].line, 4);
+    }
+
+    #[test]
+    fn test_section_end_line() {
+        let text = "# A\n## B\ntext\n## C\n# D\nend";
+        let headings = parse_headings(text);
+        assert_eq!(section_end_line(&headings, 0, 5), 3);
+        assert_eq!(section_end_line(&headings, 1, 5), 2);
+        assert_eq!(section_end_line(&headings, 3, 5), 5);
+    }
+
+    #[test]
+    fn test_resolve_anchor_duplicates() {
+        let text = "# Log\n## Notes\na\n## Notes\nb\n## Notes\nc\n## Step-1\n";
+        let headings = parse_headings(text);
+        let line = |anchor: &str| resolve_anchor(&headings, anchor).map(|h| h.line);
+        assert_eq!(line("notes"), Some(1));
+        assert_eq!(line("Notes#2"), Some(3));
+        assert_eq!(line("Notes#3"), Some(5));
+        assert_eq!(line("Notes-1"), Some(3));
+        assert_eq!(line("Notes-2"), Some(5));
+        assert_eq!(line("Notes#4"), None);
+        assert_eq!(line("Notes#0"), None);
+        assert_eq!(line("Step-1"), Some(7));
+    }
+
+    #[test]
+    fn test_setext_headings() {
+        let text = "---\ntitle: x\n---\nMain Title\n==========\nIntro\n\nSection\n---\n- item\n---\n```\ncode\n---\n```\n";
+        let headings = parse_headings(text);
+        let found: Vec<_> = headings
+            .iter()
+            .map(|h| (h.level, h.title.as_str(), h.line, h.line_len))
+            .collect();
+        assert_eq!(found, vec![(1, "Main Title", 3, 10), (2, "Section", 7, 7)]);
+    }
+
+    #[test]
+    fn test_slugify_heading() {
+        assert_eq!(slugify_heading("My Heading!"), "my-heading");
+        assert_eq!(slugify_heading(" What's new? (v2.0) "), "whats-new-v20");
+        assert_eq!(
+            slugify_heading("snake_case & kebab-case"),
+            "snake_case--kebab-case"
+        );
+        assert_eq!(slugify_heading("Ünïcode Títle"), "ünïcode-títle");
+    }
+
+    #[test]
+    fn test_duplicate_slugs() {
+        let text = "# Notes\n## Notes\n## Notes-1\n## notes!\n## Other\n";
+        let headings = parse_headings(text);
+        assert_eq!(
+            heading_slugs(&headings),
+            vec!["notes", "notes-1", "notes-1-1", "notes-2", "other"]
+        );
+        assert_eq!(anchor_slug(&headings, "Notes#2"), "notes-1");
+        assert_eq!(anchor_slug(&headings, "Other"), "other");
+        assert_eq!(anchor_slug(&headings, "Missing Part!"), "missing-part");
+        assert_eq!(
+            heading_by_slug(&headings, "notes-2").map(|h| h.line),
+            Some(3)
+        );
+        assert_eq!(heading_by_slug(&headings, "gone"), None);
+        let anchor = |slug| heading_anchor(&headings, heading_by_slug(&headings, slug).unwrap());
+        assert_eq!(anchor("notes-1"), "Notes#2");
+        assert_eq!(anchor("notes"), "Notes");
+        assert_eq!(
+            resolve_anchor(&headings, &anchor("notes-1")).map(|h| h.line),
+            Some(1)
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use std::path::Path;
+
+use tower_lsp::lsp_types::{DocumentHighlight, DocumentHighlightKind};
+
+use crate::position::{self, Encoding};
+use crate::vault;
+use crate::wikilink::{self, Delimiters};
+
+/// Highlights every wiki-link in `text` that points at the same note as the link at
+/// `offset`. Links are compared by the file they resolve to, so `[[a/b]]`, `[[a/b.md]]`
+/// and `[[a/b | alias]]` match; unresolved links are compared by their path text.
+pub fn link_highlights(
+    text: &str,
+    offset: usize,
+    vault_dir: &Path,
+    pages: &[vault::Page],
+    delimiters: &Delimiters,
+    encoding: Encoding,
+) -> Vec<DocumentHighlight> {
+    let Some(current) = wikilink::wikilink_at(text, offset, delimiters) else {
+        return Vec::new();
+    };
+    let target_of = |link: &wikilink::WikiLink| {
+        vault::resolve_link(vault_dir, &link.path, pages)
+            .map(|target| vault::normalize_path(&target))
+    };
+    let key_of = |link: &wikilink::WikiLink| {
+        link.path
+            .strip_suffix(".md")
+            .unwrap_or(&link.path)
+            .to_lowercase()
+    };
+    let target = target_of(&current);
+    let key = key_of(&current);
+    wikilink::find_wikilinks(text, delimiters)
+        .into_iter()
+        .filter(|link| match &target {
+            Some(target) => target_of(link).as_ref() == Some(target),
+            None => !link.path.is_empty() && key_of(link) == key,
+        })
+        .map(|link| DocumentHighlight {
+            range: position::offset_range(text, &link.range, encoding),
+            kind: Some(DocumentHighlightKind::TEXT),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_link_highlights() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-highlight-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(vault_dir.join("a")).unwrap();
+        fs::write(vault_dir.join("a/b.md"), "").unwrap();
+        fs::write(vault_dir.join("c.md"), "").unwrap();
+
+        let text = "[[a/b]] [[c]]\n[[a/b.md | alias]] [[a/b#Heading]]\n[[gone]] [[Gone]]";
+        let lines = |offset: usize| {
+            link_highlights(
+                text,
+                offset,
+                &vault_dir,
+                &[],
+                &Delimiters::default(),
+                Encoding::default(),
+            )
+            .into_iter()
+            .map(|highlight| (highlight.range.start.line, highlight.range.start.character))
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(lines(3), vec![(0, 0), (1, 0), (1, 19)]);
+        assert_eq!(lines(10), vec![(0, 8)]);
+        assert_eq!(lines(text.rfind("gone").unwrap()), vec![(2, 0), (2, 9)]);
+        assert!(lines(7).is_empty());
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+}
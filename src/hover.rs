@@ -0,0 +1,207 @@
+use crate::frontmatter;
+use crate::heading;
+use crate::markdown_context;
+use crate::tags;
+use crate::wikilink::WikiLink;
+
+/// Number of body lines shown under the metadata card.
+const CARD_PREVIEW_LINES: usize = 5;
+
+/// Builds the hover preview for `link` from the target note's `content`: just the
+/// referenced paragraph when the link has a `^blockid`, otherwise the note body without
+/// its frontmatter, cut off after `max_lines` lines.
+pub fn link_preview(link: &WikiLink, content: &str, max_lines: usize) -> String {
+    if let Some(id) = &link.block {
+        if let Some((start, end)) = markdown_context::find_block(content, id) {
+            let lines: Vec<&str> = content.lines().collect();
+            let block = lines[start..=end].join("\n");
+            let marker = format!("^{}", id);
+            return match block.trim_end().strip_suffix(&marker) {
+                Some(stripped) => stripped.trim_end().to_string(),
+                None => block,
+            };
+        }
+    }
+    truncate_lines(frontmatter::strip_frontmatter(content), max_lines)
+}
+
+/// Builds the hover card for `link`: a table with the target note's title, tags, word
+/// count and number of `backlinks`, followed by the start of the preview. The title comes
+/// from the frontmatter `title`, then the first level-one heading, then `fallback_title`.
+pub fn link_card(link: &WikiLink, content: &str, fallback_title: &str, backlinks: usize) -> String {
+    let title = frontmatter::title(content)
+        .or_else(|| {
+            heading::parse_headings(content)
+                .into_iter()
+                .find(|heading| heading.level == 1)
+                .map(|heading| heading.title)
+        })
+        .unwrap_or_else(|| fallback_title.to_string());
+    let tags = tags::note_tags(content)
+        .iter()
+        .map(|tag| format!("#{}", tag))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let words = frontmatter::strip_frontmatter(content)
+        .split_whitespace()
+        .count();
+    format!(
+        "| Title | Tags | Words | Backlinks |\n| --- | --- | --- | --- |\n| {} | {} | {} | {} |\n\n{}",
+        table_cell(&title),
+        table_cell(&tags),
+        words,
+        backlinks,
+        link_preview(link, content, CARD_PREVIEW_LINES)
+    )
+}
+
+/// Builds the hover preview for a `[[#anchor]]` link into the same document: the section
+/// of `content` under the heading `anchor` names, cut off after `max_lines` lines.
+pub fn section_preview(content: &str, anchor: &str, max_lines: usize) -> Option<String> {
+    let headings = heading::parse_headings(content);
+    let target = heading::resolve_anchor(&headings, anchor)?;
+    let index = headings
+        .iter()
+        .position(|heading| heading.line == target.line)?;
+    let lines: Vec<&str> = content.lines().collect();
+    let end = heading::section_end_line(&headings, index, lines.len().saturating_sub(1));
+    Some(truncate_lines(
+        &lines[target.line..=end].join("\n"),
+        max_lines,
+    ))
+}
+
+/// Builds the hover for a link to an image: its file name, then its dimensions when the
+/// header of `bytes` can be read, and its size.
+pub fn image_card(file_name: &str, bytes: &[u8]) -> String {
+    let size = if bytes.len() < 1024 {
+        format!("{} bytes", bytes.len())
+    } else {
+        format!("{:.1} KB", bytes.len() as f64 / 1024.0)
+    };
+    match image_dimensions(bytes) {
+        Some((width, height)) => {
+            format!("**{}**\n\n{} × {} px, {}", file_name, width, height, size)
+        }
+        None => format!("**{}**\n\n{}", file_name, size),
+    }
+}
+
+/// Reads the width and height of a PNG, GIF or JPEG image from its header.
+fn image_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let be16 = |at: usize| Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?) as u32);
+    let be32 = |at: usize| Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+    let le16 = |at: usize| Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?) as u32);
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some((be32(16)?, be32(20)?));
+    }
+    if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        return Some((le16(6)?, le16(8)?));
+    }
+    if !bytes.starts_with(&[0xff, 0xd8]) {
+        return None;
+    }
+    // Walk the JPEG segments up to the start-of-frame marker that holds the size.
+    let mut at = 2;
+    while *bytes.get(at)? == 0xff {
+        let marker = *bytes.get(at + 1)?;
+        let len = be16(at + 2)? as usize;
+        if matches!(marker, 0xc0..=0xcf) && !matches!(marker, 0xc4 | 0xc8 | 0xcc) {
+            return Some((be16(at + 7)?, be16(at + 5)?));
+        }
+        at += 2 + len;
+    }
+    None
+}
+
+/// Escapes the pipes that would otherwise end a markdown table cell.
+fn table_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+/// Keeps the first `max_lines` lines of `text`, noting how many were left out.
+fn truncate_lines(text: &str, max_lines: usize) -> String {
+    let lines: Vec<&str> = text.trim_start_matches('\n').lines().collect();
+    if lines.len() <= max_lines {
+        return lines.join("\n");
+    }
+    format!(
+        "{}\n\n… ({} more lines)",
+        lines[..max_lines].join("\n"),
+        lines.len() - max_lines
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wikilink::{self, Delimiters};
+
+    #[test]
+    fn test_image_card() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend(640u32.to_be_bytes());
+        png.extend(480u32.to_be_bytes());
+        assert_eq!(
+            image_card("cat.png", &png),
+            "**cat.png**\n\n640 × 480 px, 24 bytes"
+        );
+
+        let mut jpeg = vec![0xff, 0xd8, 0xff, 0xe0, 0, 4, 0, 0];
+        jpeg.extend([0xff, 0xc0, 0, 17, 8, 0, 200, 1, 44]);
+        assert_eq!(image_dimensions(&jpeg), Some((300, 200)));
+
+        let gif = b"GIF89a\x10\0\x20\0";
+        assert_eq!(image_dimensions(gif), Some((16, 32)));
+        assert_eq!(image_card("x.svg", &[0; 2048]), "**x.svg**\n\n2.0 KB");
+    }
+
+    #[test]
+    fn test_link_preview_block() {
+        let content = "# Note\n\nIntro.\n\nThe key idea\nspans two lines ^key\n\nOutro.";
+        let link = &wikilink::find_wikilinks("[[note^key]]", &Delimiters::default())[0];
+        assert_eq!(
+            link_preview(link, content, 20),
+            "The key idea\nspans two lines"
+        );
+    }
+
+    #[test]
+    fn test_link_preview_truncates_after_frontmatter() {
+        let content = "---\ntitle: Long\n---\none\ntwo\nthree\nfour\n";
+        let link = &wikilink::find_wikilinks("[[long]]", &Delimiters::default())[0];
+        assert_eq!(
+            link_preview(link, content, 2),
+            "one\ntwo\n\n… (2 more lines)"
+        );
+        assert_eq!(link_preview(link, content, 4), "one\ntwo\nthree\nfour");
+    }
+
+    #[test]
+    fn test_section_preview() {
+        let content = "# Note\nIntro.\n## Setup\nStep one.\n### Detail\nMore.\n## Usage\nRun it.\n";
+        assert_eq!(
+            section_preview(content, "setup", 20).as_deref(),
+            Some("## Setup\nStep one.\n### Detail\nMore.")
+        );
+        assert_eq!(
+            section_preview(content, "Usage", 1).as_deref(),
+            Some("## Usage\n\n… (1 more lines)")
+        );
+        assert_eq!(section_preview(content, "Missing", 20), None);
+    }
+
+    #[test]
+    fn test_link_card() {
+        let content =
+            "---\ntitle: \"Plans | 2024\"\ntags: [work]\n---\n# Heading\nSome #todo words here.\n";
+        let link = &wikilink::find_wikilinks("[[plans]]", &Delimiters::default())[0];
+        assert_eq!(
+            link_card(link, content, "plans", 3),
+            "| Title | Tags | Words | Backlinks |\n| --- | --- | --- | --- |\n\
+             | Plans \\| 2024 | #work, #todo | 6 | 3 |\n\n# Heading\nSome #todo words here."
+        );
+        let untitled = link_card(link, "Just text.", "plans", 0);
+        assert!(untitled.contains("| plans |  | 2 | 0 |"));
+    }
+}
@@ -0,0 +1,121 @@
+use std::fs;
+use std::path::Path;
+
+/// Files at the vault root whose patterns are honored alongside the configured ones.
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".notemancyignore"];
+
+/// Glob patterns for notes left out of vault scans, in the spirit of `.gitignore`:
+/// `*` and `?` match within one path segment and `**` across segments. A pattern without
+/// a `/` matches a file or folder name at any depth; one with a `/` (or a leading `/`)
+/// matches from the vault root; a trailing `/` only matches folders. Negated `!`
+/// patterns are not supported and are skipped.
+#[derive(Clone, Debug, Default)]
+pub struct IgnoreRules {
+    patterns: Vec<String>,
+}
+
+impl IgnoreRules {
+    /// Builds the rules from `patterns`, plus those in `.gitignore` and `.notemancyignore`
+    /// at the root of `vault_dir`.
+    pub fn load(vault_dir: &Path, patterns: &[String]) -> Self {
+        let mut lines: Vec<String> = patterns.to_vec();
+        for name in IGNORE_FILES {
+            if let Ok(contents) = fs::read_to_string(vault_dir.join(name)) {
+                lines.extend(contents.lines().map(str::to_string));
+            }
+        }
+        IgnoreRules::new(&lines)
+    }
+
+    /// Builds the rules from `patterns` alone, skipping blanks and `#` comments.
+    pub fn new(patterns: &[String]) -> Self {
+        IgnoreRules {
+            patterns: patterns
+                .iter()
+                .map(|pattern| pattern.trim())
+                .filter(|pattern| {
+                    !pattern.is_empty() && !pattern.starts_with('#') && !pattern.starts_with('!')
+                })
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+
+    /// Whether the note at the virtual path `vpath` is ignored, either itself or through
+    /// one of its folders.
+    pub fn is_ignored(&self, vpath: &str) -> bool {
+        let segments: Vec<&str> = vpath.split('/').filter(|s| !s.is_empty()).collect();
+        self.patterns.iter().any(|pattern| {
+            let dir_only = pattern.ends_with('/');
+            let pattern = pattern.trim_end_matches('/');
+            let anchored = pattern.contains('/');
+            let pattern: Vec<&str> = pattern
+                .trim_start_matches('/')
+                .split('/')
+                .filter(|s| !s.is_empty())
+                .collect();
+            // Try the pattern against the path up to each folder, and the whole path
+            // unless only folders can match.
+            let ends = (1..segments.len()).chain((!dir_only).then_some(segments.len()));
+            ends.into_iter().any(|end| {
+                let prefix = &segments[..end];
+                if anchored {
+                    match_segments(&pattern, prefix)
+                } else {
+                    pattern.len() == 1 && match_segment(pattern[0], prefix[end - 1])
+                }
+            })
+        })
+    }
+}
+
+/// Matches path segments against pattern segments, where `**` spans any number of them.
+fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_segments(rest, &path[skip..])),
+        Some((first, rest)) => path.split_first().is_some_and(|(segment, path)| {
+            match_segment(first, segment) && match_segments(rest, path)
+        }),
+    }
+}
+
+/// Matches one path segment against a pattern of literal characters, `*` and `?`.
+fn match_segment(pattern: &str, segment: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let segment: Vec<char> = segment.chars().collect();
+    fn matches(pattern: &[char], segment: &[char]) -> bool {
+        match pattern.split_first() {
+            None => segment.is_empty(),
+            Some(('*', rest)) => (0..=segment.len()).any(|skip| matches(rest, &segment[skip..])),
+            Some(('?', rest)) => !segment.is_empty() && matches(rest, &segment[1..]),
+            Some((c, rest)) => segment.first() == Some(c) && matches(rest, &segment[1..]),
+        }
+    }
+    matches(&pattern, &segment)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_ignored() {
+        let rules = IgnoreRules::new(&[
+            "archive/".to_string(),
+            "*.draft.md".to_string(),
+            "/templates".to_string(),
+            "journal/**/private.md".to_string(),
+            "# a comment".to_string(),
+        ]);
+        assert!(rules.is_ignored("archive/old.md"));
+        assert!(rules.is_ignored("projects/archive/old.md"));
+        assert!(!rules.is_ignored("archive.md"));
+        assert!(rules.is_ignored("notes/idea.draft.md"));
+        assert!(rules.is_ignored("templates/daily.md"));
+        assert!(!rules.is_ignored("notes/templates/daily.md"));
+        assert!(rules.is_ignored("journal/2024/05/private.md"));
+        assert!(rules.is_ignored("journal/private.md"));
+        assert!(!rules.is_ignored("notes/idea.md"));
+    }
+}
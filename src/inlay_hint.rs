@@ -0,0 +1,101 @@
+use std::ops::Range;
+use std::path::Path;
+
+use tower_lsp::lsp_types::{InlayHint, InlayHintLabel};
+
+use crate::position::{self, Encoding};
+use crate::vault;
+use crate::wikilink::{self, Delimiters};
+
+/// Shows the title of the linked note just before the `]]` of every path-only wiki-link
+/// that starts within the byte `range` of `text`. Links with an alias, unresolved links
+/// and links whose file name already reads as the title get no hint.
+pub fn title_hints(
+    text: &str,
+    range: Range<usize>,
+    vault_dir: &Path,
+    pages: &[vault::Page],
+    delimiters: &Delimiters,
+    encoding: Encoding,
+) -> Vec<InlayHint> {
+    wikilink::find_wikilinks(text, delimiters)
+        .into_iter()
+        .filter(|link| link.alias.is_none() && !link.path.is_empty())
+        .filter(|link| range.contains(&link.range.start))
+        .filter_map(|link| {
+            let target = vault::normalize_path(&vault::resolve_link(vault_dir, &link.path, pages)?);
+            let page = pages
+                .iter()
+                .find(|page| vault::normalize_path(&page.file) == target)?;
+            let name = link.path.rsplit('/').next().unwrap_or(&link.path);
+            let name = name.strip_suffix(".md").unwrap_or(name);
+            if page.title.trim().is_empty() || page.title.trim().eq_ignore_ascii_case(name) {
+                return None;
+            }
+            Some(InlayHint {
+                position: position::offset_to_position(text, link.range.end - 2, encoding),
+                label: InlayHintLabel::String(page.title.trim().to_string()),
+                kind: None,
+                text_edits: None,
+                tooltip: None,
+                padding_left: Some(true),
+                padding_right: None,
+                data: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_title_hints() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-inlay-hint-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(vault_dir.join("people")).unwrap();
+        fs::write(vault_dir.join("people/ada.md"), "").unwrap();
+        fs::write(vault_dir.join("rust.md"), "").unwrap();
+        let page = |file: &str, title: &str| vault::Page {
+            file: vault_dir.join(file),
+            title: title.to_string(),
+            aliases: Vec::new(),
+        };
+        let pages = vec![
+            page("people/ada.md", "Ada Lovelace"),
+            page("rust.md", "Rust"),
+        ];
+
+        let text = "[[people/ada]] [[people/ada | Ada]] [[rust]]\n[[gone]] [[people/ada.md]]";
+        let hints = |range: Range<usize>| {
+            title_hints(
+                text,
+                range,
+                &vault_dir,
+                &pages,
+                &Delimiters::default(),
+                Encoding::default(),
+            )
+            .into_iter()
+            .map(|hint| match hint.label {
+                InlayHintLabel::String(label) => {
+                    (hint.position.line, hint.position.character, label)
+                }
+                _ => panic!("expected a string label"),
+            })
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            hints(0..text.len()),
+            vec![
+                (0, 12, "Ada Lovelace".to_string()),
+                (1, 24, "Ada Lovelace".to_string())
+            ]
+        );
+        assert_eq!(hints(1..text.len()).len(), 1);
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+}
@@ -0,0 +1,795 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tower_lsp::lsp_types::Location;
+use url::Url;
+
+use crate::frontmatter;
+use crate::position::{self, Encoding};
+use crate::vault::{self, NoteLinks};
+use crate::wikilink::{self, Delimiters, WikiLink};
+
+/// The command that rebuilds the link index from the files on disk. It returns the number
+/// of notes indexed as `{"notes": n}`.
+pub const REBUILD_LINK_INDEX_COMMAND: &str = "notemancy.rebuildLinkIndex";
+
+/// The command that rescans the whole vault after outside changes: it refreshes the
+/// notemancy database and the server's indexes, reporting progress on the request's
+/// work-done token. It returns `{files, symbols, pagesChanged}`.
+pub const REINDEX_COMMAND: &str = "notemancy.reindex";
+
+/// The command listing notes cut off from the link graph. Its optional argument is the
+/// mode: `noInbound`, `noOutbound` or `isolated` (the default). It returns
+/// `{vpath, title}` entries.
+pub const FIND_ORPHANS_COMMAND: &str = "notemancy.findOrphans";
+
+/// The command ranking notes by the links pointing at them. Its optional argument is the
+/// number of notes to return, `TOP_LINKED_LIMIT` by default; it returns
+/// `{vpath, title, inbound, outbound}` entries.
+pub const TOP_LINKED_NOTES_COMMAND: &str = "notemancy.topLinkedNotes";
+
+/// How many notes the top-linked-notes command returns without a limit argument.
+pub const TOP_LINKED_LIMIT: usize = 20;
+
+/// The command listing every unresolved wiki-link in the vault, for a problems panel. Its
+/// optional argument is a folder, by virtual path, to restrict the search to; it returns
+/// `{sourceVpath, linkText, location}` entries.
+pub const VAULT_BROKEN_LINKS_COMMAND: &str = "notemancy.vaultBrokenLinks";
+
+/// Which missing links make a note an orphan.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrphanMode {
+    /// No other note links to it.
+    NoInbound,
+    /// It links to no other note.
+    NoOutbound,
+    /// Both: no links in or out.
+    Isolated,
+}
+
+impl OrphanMode {
+    /// Reads a mode from its command argument name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "noInbound" => Some(OrphanMode::NoInbound),
+            "noOutbound" => Some(OrphanMode::NoOutbound),
+            "isolated" => Some(OrphanMode::Isolated),
+            _ => None,
+        }
+    }
+}
+
+/// A note returned by the find-orphans command.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Orphan {
+    pub vpath: String,
+    pub title: String,
+}
+
+/// A note returned by the top-linked-notes command, with the number of links into it
+/// from other notes and out of it to other notes.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct LinkCount {
+    pub vpath: String,
+    pub title: String,
+    pub inbound: usize,
+    pub outbound: usize,
+}
+
+/// A wiki-link returned by the vault-broken-links command.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BrokenLink {
+    /// The note the link is in.
+    pub source_vpath: String,
+    /// The whole link as written, brackets included.
+    pub link_text: String,
+    pub location: Location,
+}
+
+/// A note's text and the wiki-links found in it.
+#[derive(Clone, Debug)]
+struct IndexedNote {
+    uri: Url,
+    content: String,
+    links: Vec<WikiLink>,
+    /// The path each of `links` points at from the vault root, `./` and `../` resolved.
+    /// A link naming a note by title or alias points at that note's virtual path.
+    targets: Vec<String>,
+    /// The title and aliases links can name the note by, as `name_key` gives them.
+    names: Vec<String>,
+}
+
+impl IndexedNote {
+    /// The note's links that point at `vpath`.
+    fn links_to<'a>(&'a self, vpath: &'a str) -> impl Iterator<Item = &'a WikiLink> {
+        self.links
+            .iter()
+            .zip(&self.targets)
+            .filter(move |(_, target)| vault::link_matches(target, vpath))
+            .map(|(link, _)| link)
+    }
+}
+
+/// The wiki-links of every note in the vault, with the reverse mapping from link targets
+/// to the notes linking there, so backlink queries don't rescan the vault. Open documents
+/// are indexed with their in-memory text.
+#[derive(Clone, Debug, Default)]
+pub struct LinkIndex {
+    /// The vault relative links are resolved in.
+    vault_dir: PathBuf,
+    /// The delimiters links are found with.
+    delimiters: Delimiters,
+    /// Each note, by its file.
+    notes: HashMap<PathBuf, IndexedNote>,
+    /// The notes with a link to each target, keyed by the link path without `.md`.
+    inbound: HashMap<String, BTreeSet<PathBuf>>,
+    /// The notes going by each title or alias, keyed by `name_key`.
+    named: HashMap<String, BTreeSet<PathBuf>>,
+    /// The notes with a link that could name a note by title or alias, keyed by the
+    /// link path's `name_key`.
+    mentions: HashMap<String, BTreeSet<PathBuf>>,
+}
+
+/// The key a link path and a virtual path share when the link points at that note, as
+/// decided by `vault::link_matches`.
+fn target_key(path: &str) -> String {
+    let path = vault::normalize_separators(path);
+    match path.strip_suffix(".md") {
+        Some(stem) => stem.to_string(),
+        None => path,
+    }
+}
+
+/// The form titles, aliases and link paths are compared in, as `vault::pages_named`
+/// compares them.
+fn name_key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// The names links can refer to the note `file` by: its title and frontmatter aliases.
+fn note_names(file: &Path, content: &str) -> Vec<String> {
+    let mut names: Vec<String> = std::iter::once(note_title(file, content))
+        .chain(frontmatter::list_field(content, "aliases"))
+        .map(|name| name_key(&name))
+        .filter(|name| !name.is_empty())
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Removes `file` from the set under `key`, dropping the set once it is empty.
+fn forget(map: &mut HashMap<String, BTreeSet<PathBuf>>, key: &str, file: &Path) {
+    if let Some(files) = map.get_mut(key) {
+        files.remove(file);
+        if files.is_empty() {
+            map.remove(key);
+        }
+    }
+}
+
+/// A note's frontmatter title, or the name of its file.
+pub fn note_title(file: &Path, content: &str) -> String {
+    frontmatter::title(content).unwrap_or_else(|| {
+        file.file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned()
+    })
+}
+
+impl LinkIndex {
+    /// An empty index of the notes of `vault_dir`, whose links are written with
+    /// `delimiters`.
+    pub fn new(vault_dir: &Path, delimiters: &Delimiters) -> Self {
+        LinkIndex {
+            vault_dir: vault_dir.to_path_buf(),
+            delimiters: delimiters.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Indexes every markdown file in the vault.
+    pub fn build(vault_dir: &Path, delimiters: &Delimiters) -> Self {
+        let mut index = LinkIndex::new(vault_dir, delimiters);
+        for file in vault::collect_markdown_files(vault_dir) {
+            if let Ok(content) = fs::read_to_string(&file) {
+                index.update(&file, content);
+            }
+        }
+        index
+    }
+
+    /// The delimiters links are found with.
+    pub fn delimiters(&self) -> &Delimiters {
+        &self.delimiters
+    }
+
+    /// Number of notes in the index.
+    pub fn note_count(&self) -> usize {
+        self.notes.len()
+    }
+
+    /// Replaces what the index knows about `file` with the links in `content`. When the
+    /// note's title or aliases change, the links naming them are resolved again.
+    pub fn update(&mut self, file: &Path, content: String) {
+        let renamed = self.insert(file, content);
+        self.retarget_named(&renamed);
+    }
+
+    /// Drops `file` from the index.
+    pub fn remove(&mut self, file: &Path) {
+        let renamed = self.detach(file);
+        self.retarget_named(&renamed);
+    }
+
+    /// Indexes `file` without revisiting the other notes, returning the names it gained
+    /// or lost.
+    fn insert(&mut self, file: &Path, content: String) -> Vec<String> {
+        let Ok(uri) = Url::from_file_path(file) else {
+            return Vec::new();
+        };
+        let old_names = self.detach(file);
+        let names = note_names(file, &content);
+        for name in &names {
+            self.named
+                .entry(name.clone())
+                .or_default()
+                .insert(file.to_path_buf());
+        }
+        let links = wikilink::find_wikilinks(&content, &self.delimiters);
+        for link in links.iter().filter(|link| !link.path.is_empty()) {
+            self.mentions
+                .entry(name_key(&link.path))
+                .or_default()
+                .insert(file.to_path_buf());
+        }
+        let targets: Vec<String> = links
+            .iter()
+            .map(|link| self.link_target(file, &link.path))
+            .collect();
+        self.link(file, &targets);
+        let mut renamed: Vec<String> = old_names
+            .iter()
+            .filter(|name| !names.contains(name))
+            .chain(names.iter().filter(|name| !old_names.contains(name)))
+            .cloned()
+            .collect();
+        renamed.sort();
+        self.notes.insert(
+            file.to_path_buf(),
+            IndexedNote {
+                uri,
+                content,
+                links,
+                targets,
+                names,
+            },
+        );
+        renamed
+    }
+
+    /// Takes `file` out of the index, returning the names it went by.
+    fn detach(&mut self, file: &Path) -> Vec<String> {
+        let Some(note) = self.notes.remove(file) else {
+            return Vec::new();
+        };
+        self.unlink(file, &note.targets);
+        for name in &note.names {
+            forget(&mut self.named, name, file);
+        }
+        for link in &note.links {
+            forget(&mut self.mentions, &name_key(&link.path), file);
+        }
+        note.names
+    }
+
+    /// The target of a link in `file` written as `link_path`: the file it names inside
+    /// the vault, else the one note going by that title or alias, else the path as
+    /// written.
+    fn link_target(&self, file: &Path, link_path: &str) -> String {
+        let path = vault::vault_link_path(&self.vault_dir, file, link_path);
+        if path.is_empty() || vault::resolve_link_target(&self.vault_dir, &path).is_some() {
+            return path;
+        }
+        let named = self.named.get(&name_key(link_path));
+        match named
+            .map(|files| files.iter().collect::<Vec<_>>())
+            .as_deref()
+        {
+            Some([target]) => vault::virtual_path(&self.vault_dir, target).unwrap_or(path),
+            _ => path,
+        }
+    }
+
+    /// Resolves the links of the notes that mention one of `names` again.
+    fn retarget_named(&mut self, names: &[String]) {
+        let files: BTreeSet<PathBuf> = names
+            .iter()
+            .filter_map(|name| self.mentions.get(name))
+            .flatten()
+            .cloned()
+            .collect();
+        for file in files {
+            self.retarget(&file);
+        }
+    }
+
+    /// Resolves the links of `file` again.
+    fn retarget(&mut self, file: &Path) {
+        let Some(old) = self.notes.get(file).map(|note| note.targets.clone()) else {
+            return;
+        };
+        let targets: Vec<String> = self.notes[file]
+            .links
+            .iter()
+            .map(|link| self.link_target(file, &link.path))
+            .collect();
+        if targets != old {
+            self.unlink(file, &old);
+            self.link(file, &targets);
+            if let Some(note) = self.notes.get_mut(file) {
+                note.targets = targets;
+            }
+        }
+    }
+
+    /// Records `file` as linking to each of `targets`.
+    fn link(&mut self, file: &Path, targets: &[String]) {
+        for target in targets {
+            if !target.is_empty() {
+                self.inbound
+                    .entry(target_key(target))
+                    .or_default()
+                    .insert(file.to_path_buf());
+            }
+        }
+    }
+
+    /// Forgets that `file` links to each of `targets`.
+    fn unlink(&mut self, file: &Path, targets: &[String]) {
+        for target in targets {
+            forget(&mut self.inbound, &target_key(target), file);
+        }
+    }
+
+    /// Every indexed note's file, URI and text.
+    pub fn contents(&self) -> impl Iterator<Item = (&Path, &Url, &str)> {
+        self.notes
+            .iter()
+            .map(|(file, note)| (file.as_path(), &note.uri, note.content.as_str()))
+    }
+
+    /// The wiki-links pointing at `vpath`, grouped by note, in file order.
+    pub fn note_links(&self, vpath: &str) -> Vec<NoteLinks> {
+        let Some(sources) = self.inbound.get(&target_key(vpath)) else {
+            return Vec::new();
+        };
+        sources
+            .iter()
+            .filter_map(|file| self.notes.get(file))
+            .filter_map(|note| {
+                let links: Vec<_> = note.links_to(vpath).cloned().collect();
+                (!links.is_empty()).then(|| NoteLinks {
+                    uri: note.uri.clone(),
+                    content: note.content.clone(),
+                    links,
+                })
+            })
+            .collect()
+    }
+
+    /// Every wiki-link pointing at `vpath`, with ranges in `encoding`'s units.
+    pub fn backlinks(&self, vpath: &str, encoding: Encoding) -> Vec<Location> {
+        self.note_links(vpath)
+            .into_iter()
+            .flat_map(|note| {
+                let NoteLinks {
+                    uri,
+                    content,
+                    links,
+                } = note;
+                links.into_iter().map(move |link| Location {
+                    uri: uri.clone(),
+                    range: position::offset_range(&content, &link.range, encoding),
+                })
+            })
+            .collect()
+    }
+
+    /// Lists the notes of `vault_dir` missing the links `mode` asks about, by virtual path.
+    /// Only links between indexed notes count, and a note linking to itself is still an
+    /// orphan.
+    pub fn orphans(&self, vault_dir: &Path, mode: OrphanMode) -> Vec<Orphan> {
+        let mut linked_from = BTreeSet::new();
+        let mut linked_to = BTreeSet::new();
+        for file in self.notes.keys() {
+            let Some(vpath) = vault::virtual_path(vault_dir, file) else {
+                continue;
+            };
+            for source in self.inbound.get(&target_key(&vpath)).into_iter().flatten() {
+                if source != file {
+                    linked_from.insert(source.as_path());
+                    linked_to.insert(file.as_path());
+                }
+            }
+        }
+        let mut orphans: Vec<Orphan> = self
+            .notes
+            .iter()
+            .filter(|(file, _)| {
+                let no_inbound = !linked_to.contains(file.as_path());
+                let no_outbound = !linked_from.contains(file.as_path());
+                match mode {
+                    OrphanMode::NoInbound => no_inbound,
+                    OrphanMode::NoOutbound => no_outbound,
+                    OrphanMode::Isolated => no_inbound && no_outbound,
+                }
+            })
+            .filter_map(|(file, note)| {
+                let vpath = vault::virtual_path(vault_dir, file)?;
+                let title = note_title(file, &note.content);
+                Some(Orphan { vpath, title })
+            })
+            .collect();
+        orphans.sort_by(|a, b| a.vpath.cmp(&b.vpath));
+        orphans
+    }
+
+    /// The notes of `vault_dir` with the most links pointing at them from other notes,
+    /// at most `limit` of them, most linked first. Every link counts, so a note linking
+    /// twice to a hub adds two; notes nothing links to are left out.
+    pub fn top_linked(&self, vault_dir: &Path, limit: usize) -> Vec<LinkCount> {
+        let mut counts: Vec<LinkCount> = self
+            .notes
+            .iter()
+            .filter_map(|(file, note)| {
+                let vpath = vault::virtual_path(vault_dir, file)?;
+                let inbound = self
+                    .inbound
+                    .get(&target_key(&vpath))
+                    .into_iter()
+                    .flatten()
+                    .filter(|source| *source != file)
+                    .filter_map(|source| self.notes.get(source))
+                    .flat_map(|source| source.links_to(&vpath))
+                    .count();
+                let outbound = note
+                    .targets
+                    .iter()
+                    .filter(|target| !target.is_empty() && !vault::link_matches(target, &vpath))
+                    .count();
+                (inbound > 0).then(|| LinkCount {
+                    title: note_title(file, &note.content),
+                    vpath,
+                    inbound,
+                    outbound,
+                })
+            })
+            .collect();
+        counts.sort_by(|a, b| {
+            b.inbound
+                .cmp(&a.inbound)
+                .then_with(|| a.vpath.cmp(&b.vpath))
+        });
+        counts.truncate(limit);
+        counts
+    }
+
+    /// Finds the wiki-links of the indexed notes whose target is neither a file of
+    /// `vault_dir` nor the title or alias of one of `pages`, the same links the
+    /// diagnostics report as unresolved. With a `folder`, only the notes inside it are
+    /// searched. Links are ordered by note, then by position.
+    pub fn broken_links(
+        &self,
+        vault_dir: &Path,
+        pages: &[vault::Page],
+        folder: Option<&str>,
+        encoding: Encoding,
+    ) -> Vec<BrokenLink> {
+        let folder = folder
+            .map(|folder| vault::normalize_separators(folder.trim()))
+            .map(|folder| folder.trim_matches('/').to_string())
+            .filter(|folder| !folder.is_empty());
+        let mut broken: Vec<BrokenLink> = Vec::new();
+        for (file, note) in &self.notes {
+            let Some(vpath) = vault::virtual_path(vault_dir, file) else {
+                continue;
+            };
+            let in_folder = folder.as_ref().is_none_or(|folder| {
+                vpath
+                    .strip_prefix(folder.as_str())
+                    .is_some_and(|rest| rest.starts_with('/'))
+            });
+            if !in_folder {
+                continue;
+            }
+            for link in &note.links {
+                let path = vault::vault_link_path(vault_dir, file, &link.path);
+                if link.path.is_empty()
+                    || vault::resolve_link_target(vault_dir, &path).is_some()
+                    || !vault::pages_named(pages, &link.path).is_empty()
+                {
+                    continue;
+                }
+                broken.push(BrokenLink {
+                    source_vpath: vpath.clone(),
+                    link_text: note.content[link.range.clone()].to_string(),
+                    location: Location {
+                        uri: note.uri.clone(),
+                        range: position::offset_range(&note.content, &link.range, encoding),
+                    },
+                });
+            }
+        }
+        broken.sort_by(|a, b| {
+            a.source_vpath
+                .cmp(&b.source_vpath)
+                .then_with(|| a.location.range.start.cmp(&b.location.range.start))
+        });
+        broken
+    }
+
+    /// The notes linking to `vpath`, each once.
+    pub fn backlink_files(&self, vpath: &str) -> Vec<Url> {
+        self.note_links(vpath)
+            .into_iter()
+            .map(|note| note.uri)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_follows_document_changes() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-link-index-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(vault_dir.join("notes")).unwrap();
+        fs::write(vault_dir.join("notes/target.md"), "# Target\n").unwrap();
+        fs::write(vault_dir.join("a.md"), "See [[notes/target]].\n").unwrap();
+        fs::write(vault_dir.join("b.md"), "Nothing yet.\n").unwrap();
+
+        let mut index = LinkIndex::build(&vault_dir, &Delimiters::default());
+        assert_eq!(index.note_count(), 3);
+        assert_eq!(
+            index
+                .backlinks("notes/target.md", Encoding::default())
+                .len(),
+            1
+        );
+
+        let b = vault_dir.join("b.md");
+        index.update(
+            &b,
+            "Now [[notes/target.md | here]] and [[notes/target#Target]]\n".to_string(),
+        );
+        let backlinks = index.backlinks("notes/target.md", Encoding::default());
+        assert_eq!(backlinks.len(), 3);
+        assert_eq!(index.backlink_files("notes/target.md").len(), 2);
+
+        index.update(&b, "Links removed.\n".to_string());
+        assert_eq!(
+            index
+                .backlinks("notes/target.md", Encoding::default())
+                .len(),
+            1
+        );
+        index.remove(&vault_dir.join("a.md"));
+        assert!(index
+            .backlinks("notes/target.md", Encoding::default())
+            .is_empty());
+        assert!(index.inbound.is_empty());
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+
+    #[test]
+    fn test_links_by_title_and_alias() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-link-index-names-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(vault_dir.join("notes")).unwrap();
+        let target = vault_dir.join("notes/target.md");
+        fs::write(&target, "---\ntitle: Big Idea\naliases: [bi]\n---\n").unwrap();
+        fs::write(
+            vault_dir.join("a.md"),
+            "[[big idea]] [[BI]] [[notes/target]]\n",
+        )
+        .unwrap();
+        fs::write(vault_dir.join("b.md"), "[[Renamed]]\n").unwrap();
+
+        let mut index = LinkIndex::build(&vault_dir, &Delimiters::default());
+        assert_eq!(
+            index
+                .backlinks("notes/target.md", Encoding::default())
+                .len(),
+            3
+        );
+        let orphans = index.orphans(&vault_dir, OrphanMode::NoInbound);
+        let orphans: Vec<_> = orphans.iter().map(|orphan| orphan.vpath.as_str()).collect();
+        assert_eq!(orphans, vec!["a.md", "b.md"]);
+
+        index.update(&target, "---\ntitle: Renamed\n---\n".to_string());
+        let sources: Vec<_> = index
+            .backlink_files("notes/target.md")
+            .into_iter()
+            .map(|uri| uri.to_file_path().unwrap())
+            .collect();
+        assert_eq!(
+            sources,
+            vec![vault_dir.join("a.md"), vault_dir.join("b.md")]
+        );
+        assert_eq!(
+            index
+                .backlinks("notes/target.md", Encoding::default())
+                .len(),
+            2
+        );
+
+        // A name two notes share points at neither.
+        let other = vault_dir.join("other.md");
+        index.update(&other, "---\ntitle: renamed\n---\n".to_string());
+        assert_eq!(
+            index
+                .backlinks("notes/target.md", Encoding::default())
+                .len(),
+            1
+        );
+        assert!(index.backlinks("other.md", Encoding::default()).is_empty());
+        index.remove(&other);
+        assert_eq!(
+            index
+                .backlinks("notes/target.md", Encoding::default())
+                .len(),
+            2
+        );
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+
+    #[test]
+    fn test_orphans() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-orphans-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(&vault_dir).unwrap();
+        fs::write(vault_dir.join("hub.md"), "[[leaf]] [[missing]]\n").unwrap();
+        fs::write(vault_dir.join("leaf.md"), "---\ntitle: Leaf Note\n---\n").unwrap();
+        fs::write(vault_dir.join("alone.md"), "Only [[alone]] itself.\n").unwrap();
+
+        let index = LinkIndex::build(&vault_dir, &Delimiters::default());
+        let vpaths = |mode| {
+            index
+                .orphans(&vault_dir, mode)
+                .into_iter()
+                .map(|orphan| orphan.vpath)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vpaths(OrphanMode::Isolated), vec!["alone.md"]);
+        assert_eq!(vpaths(OrphanMode::NoInbound), vec!["alone.md", "hub.md"]);
+        assert_eq!(vpaths(OrphanMode::NoOutbound), vec!["alone.md", "leaf.md"]);
+        assert_eq!(
+            index.orphans(&vault_dir, OrphanMode::NoOutbound)[1].title,
+            "Leaf Note"
+        );
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+
+    #[test]
+    fn test_top_linked() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-top-linked-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(&vault_dir).unwrap();
+        fs::write(
+            vault_dir.join("hub.md"),
+            "---\ntitle: Hub\n---\n[[a]] [[hub]]\n",
+        )
+        .unwrap();
+        fs::write(vault_dir.join("a.md"), "[[hub]] and [[hub#Part]], [[b]]\n").unwrap();
+        fs::write(vault_dir.join("b.md"), "[[hub.md]] [[missing]]\n").unwrap();
+        fs::write(vault_dir.join("c.md"), "[[b]]\n").unwrap();
+
+        let index = LinkIndex::build(&vault_dir, &Delimiters::default());
+        let top = index.top_linked(&vault_dir, TOP_LINKED_LIMIT);
+        let counts: Vec<_> = top
+            .iter()
+            .map(|count| (count.vpath.as_str(), count.inbound, count.outbound))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![("hub.md", 3, 1), ("b.md", 2, 2), ("a.md", 1, 3)]
+        );
+        assert_eq!(top[0].title, "Hub");
+        assert_eq!(index.top_linked(&vault_dir, 1).len(), 1);
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+
+    #[test]
+    fn test_broken_links() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-broken-links-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(vault_dir.join("notes")).unwrap();
+        fs::write(vault_dir.join("target.md"), "# Target\n").unwrap();
+        fs::write(
+            vault_dir.join("a.md"),
+            "[[target]] [[missing | gone]] [[Known Title]] [[#Local]]\n",
+        )
+        .unwrap();
+        fs::write(
+            vault_dir.join("notes/b.md"),
+            "[[../target]]\n```\n[[in code]]\n```\n[[./nowhere]]\n",
+        )
+        .unwrap();
+        let pages = vec![vault::Page {
+            file: vault_dir.join("target.md"),
+            title: "Known Title".to_string(),
+            aliases: Vec::new(),
+        }];
+        let index = LinkIndex::build(&vault_dir, &Delimiters::default());
+
+        let broken = index.broken_links(&vault_dir, &pages, None, Encoding::default());
+        let found: Vec<_> = broken
+            .iter()
+            .map(|link| {
+                let start = link.location.range.start;
+                (
+                    link.source_vpath.as_str(),
+                    link.link_text.as_str(),
+                    start.line,
+                    start.character,
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("a.md", "[[missing | gone]]", 0, 11),
+                ("notes/b.md", "[[./nowhere]]", 4, 0),
+            ]
+        );
+        let in_notes = index.broken_links(&vault_dir, &pages, Some("/notes/"), Encoding::default());
+        assert_eq!(in_notes.len(), 1);
+        assert_eq!(in_notes[0].source_vpath, "notes/b.md");
+        assert!(index
+            .broken_links(&vault_dir, &pages, Some("note"), Encoding::default())
+            .is_empty());
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+
+    #[test]
+    fn test_relative_links() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-link-index-relative-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(vault_dir.join("notes")).unwrap();
+        fs::write(vault_dir.join("target.md"), "# Target\n").unwrap();
+        fs::write(vault_dir.join("notes/sibling.md"), "# Sibling\n").unwrap();
+        fs::write(
+            vault_dir.join("notes/a.md"),
+            "[[../target]] [[./sibling]] [[../../outside]]\n",
+        )
+        .unwrap();
+
+        let index = LinkIndex::build(&vault_dir, &Delimiters::default());
+        assert_eq!(index.backlinks("target.md", Encoding::default()).len(), 1);
+        assert_eq!(
+            index
+                .backlinks("notes/sibling.md", Encoding::default())
+                .len(),
+            1
+        );
+        let orphans = index.orphans(&vault_dir, OrphanMode::NoInbound);
+        let orphans: Vec<_> = orphans.iter().map(|orphan| orphan.vpath.as_str()).collect();
+        assert_eq!(orphans, vec!["notes/a.md"]);
+        let top = index.top_linked(&vault_dir, TOP_LINKED_LIMIT);
+        assert_eq!(top.len(), 2);
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+}
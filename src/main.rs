@@ -4,8 +4,51 @@ use std::fs;
 use std::io::{self, Read};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
+mod backlinks;
+mod callout;
+mod code_action;
+mod code_lens;
+mod command;
+mod completion;
+mod create_note;
+mod daily;
+mod date;
+mod debounce;
+mod diagnostics;
+mod document_link;
+mod export;
+mod extract;
+mod folding;
 mod formatter;
+mod frontmatter;
+mod graph;
+mod heading;
+mod highlight;
+mod hover;
+mod ignore;
+mod inlay_hint;
+mod link_index;
+mod markdown_context;
+mod markdown_link;
+mod mentions;
+mod merge;
+mod outline;
+mod position;
+mod progress;
+mod rename;
+mod selection;
+mod semantic_tokens;
+mod settings;
+mod split;
+mod stats;
+mod tags;
+mod task;
+mod template;
+mod toc;
+mod vault;
+mod wikilink;
 
 use notemancy_core::config; // Import the config module from notemancy-core crate
 use notemancy_core::config::Config;
@@ -14,330 +57,2808 @@ use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
 use url::Url;
 
-use notemancy_core::db::crud;
-use tower_lsp::lsp_types::{
-    CompletionContext, CompletionItem, CompletionItemKind, CompletionParams, CompletionResponse,
-    InsertTextFormat,
-};
+use tower_lsp::lsp_types::{CompletionItem, CompletionParams, CompletionResponse};
+
+/// How long `shutdown` waits for cancelled background tasks to stop.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
 
 #[derive(Clone, Debug)]
 struct Backend {
     client: Client,
     /// A map from document URI to its full text.
     documents: Arc<Mutex<HashMap<Url, String>>>,
+    /// Outline symbols of open documents, dropped whenever the document changes.
+    symbol_cache: Arc<Mutex<HashMap<Url, Vec<DocumentSymbol>>>>,
+    /// Markdown files in the vault, kept current through watched-file notifications.
+    file_index: Arc<Mutex<HashSet<PathBuf>>>,
+    /// Frontmatter `aliases` of the vault's notes, by file; notes without aliases are absent.
+    aliases: Arc<Mutex<HashMap<PathBuf, Vec<String>>>>,
+    /// Wiki-links across the vault, built at startup and kept current as notes change.
+    link_index: Arc<Mutex<link_index::LinkIndex>>,
+    /// Options sent by the client during `initialize`.
+    settings: Arc<Mutex<settings::Settings>>,
+    /// Holds back the diagnostics of a changing document until typing pauses.
+    diagnostics_debounce: debounce::Debouncer,
+    /// What position columns count, as negotiated during `initialize`.
+    encoding: Arc<Mutex<position::Encoding>>,
 }
 
-#[tower_lsp::async_trait]
-impl LanguageServer for Backend {
-    async fn initialize(
-        &self,
-        _params: tower_lsp::lsp_types::InitializeParams,
-    ) -> tower_lsp::jsonrpc::Result<tower_lsp::lsp_types::InitializeResult> {
-        Ok(tower_lsp::lsp_types::InitializeResult {
-            capabilities: ServerCapabilities {
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
-                )),
-                document_symbol_provider: Some(OneOf::Left(true)),
-                workspace_symbol_provider: Some(OneOf::Left(true)),
-                // Register the completion provider with trigger character "["
-                completion_provider: Some(CompletionOptions {
-                    resolve_provider: Some(false),
-                    trigger_characters: Some(vec!["[".to_string()]),
-                    ..Default::default()
-                }),
-                ..Default::default()
-            },
-            server_info: None,
-        })
+impl Backend {
+    fn new(client: Client) -> Self {
+        Backend {
+            client,
+            documents: Arc::new(Mutex::new(HashMap::new())),
+            symbol_cache: Arc::new(Mutex::new(HashMap::new())),
+            file_index: Arc::new(Mutex::new(HashSet::new())),
+            aliases: Arc::new(Mutex::new(HashMap::new())),
+            link_index: Arc::new(Mutex::new(link_index::LinkIndex::default())),
+            settings: Arc::new(Mutex::new(settings::Settings::default())),
+            diagnostics_debounce: debounce::Debouncer::default(),
+            encoding: Arc::new(Mutex::new(position::Encoding::default())),
+        }
     }
 
-    async fn initialized(&self, _params: InitializedParams) {
-        self.client
-            .log_message(MessageType::INFO, "notemancy-lsp initialized!")
-            .await;
+    /// Records the frontmatter aliases declared by `text`, the current content of `path`.
+    fn update_aliases(&self, path: PathBuf, text: &str) {
+        let names = frontmatter::list_field(text, "aliases");
+        let mut aliases = self.aliases.lock().unwrap();
+        if names.is_empty() {
+            aliases.remove(&path);
+        } else {
+            aliases.insert(path, names);
+        }
     }
 
-    async fn shutdown(&self) -> Result<()> {
-        Ok(())
+    /// Returns the notes indexed in the database, along with their aliases.
+    fn pages(&self, vault_dir: &Path) -> Vec<vault::Page> {
+        let aliases = self.aliases.lock().unwrap().clone();
+        vault::with_aliases(vault::indexed_pages(vault_dir), &aliases)
     }
 
-    async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        let uri = params.text_document.uri;
-        let text = params.text_document.text;
-        self.documents.lock().unwrap().insert(uri, text);
+    /// Returns the link delimiters configured in the settings.
+    fn delimiters(&self) -> wikilink::Delimiters {
+        self.settings.lock().unwrap().delimiters()
     }
 
-    async fn did_change(&self, params: DidChangeTextDocumentParams) {
-        let uri = params.text_document.uri;
-        if let Some(change) = params.content_changes.into_iter().next() {
-            self.documents.lock().unwrap().insert(uri, change.text);
+    /// Returns the position encoding negotiated with the client.
+    fn encoding(&self) -> position::Encoding {
+        *self.encoding.lock().unwrap()
+    }
+
+    /// Returns the text of a document: the in-memory copy when the client has it open,
+    /// otherwise the file on disk.
+    fn document_text(&self, uri: &Url) -> Option<String> {
+        // Unsaved and untitled buffers only exist in memory; notes the client hasn't
+        // opened are read from disk.
+        let open_text = self.documents.lock().unwrap().get(uri).cloned();
+        match open_text {
+            Some(text) => Some(text),
+            None => fs::read_to_string(uri.to_file_path().ok()?).ok(),
         }
     }
 
-    async fn document_symbol(
+    /// Returns the wiki-link under the cursor, with its range. The document is read from the
+    /// in-memory copy when the client has it open.
+    fn link_at(&self, params: &TextDocumentPositionParams) -> Option<(wikilink::WikiLink, Range)> {
+        let text = self.document_text(&params.text_document.uri)?;
+        let encoding = self.encoding();
+        let offset = position::position_to_offset(&text, params.position, encoding)?;
+        let link = wikilink::wikilink_at(&text, offset, &self.delimiters())?;
+        let range = position::offset_range(&text, &link.range, encoding);
+        Some((link, range))
+    }
+
+    /// Runs the insert-template command: expands the named template and inserts it into
+    /// the open note.
+    async fn insert_template(
         &self,
-        params: DocumentSymbolParams,
-    ) -> Result<Option<DocumentSymbolResponse>> {
-        let uri = params.text_document.uri;
-        let docs = self.documents.lock().unwrap();
-        if let Some(text) = docs.get(&uri) {
-            let symbols = parse_markdown_symbols(text);
-            Ok(Some(DocumentSymbolResponse::Nested(symbols)))
-        } else {
-            Ok(None)
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let uri = command::uri_arg(arguments, 0)?;
+        let name = command::string_arg(arguments, 1, "a template name")?;
+        let text = match self.documents.lock().unwrap().get(&uri) {
+            Some(text) => text.clone(),
+            None => return Ok(None),
+        };
+        let config =
+            config::read_config().map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let templates_dir = self
+            .settings
+            .lock()
+            .unwrap()
+            .templates_path(Path::new(&config.vault_dir));
+        let Some(contents) = template::read_template(&templates_dir, name) else {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+                "unknown template: {}",
+                name
+            )));
+        };
+        let title = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+            .unwrap_or_default();
+        let expanded = template::expand(&contents, &title, &date::today());
+        let edit = template::insert_edit(&uri, &text, &expanded, self.encoding());
+        if let Err(err) = self.client.apply_edit(edit).await {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("Failed to insert template: {}", err),
+                )
+                .await;
         }
+        Ok(None)
     }
 
-    async fn symbol(
+    /// Runs the show-backlinks command, returning the URIs of the notes linking to the
+    /// given note.
+    async fn show_backlinks(
         &self,
-        params: WorkspaceSymbolParams,
-    ) -> tower_lsp::jsonrpc::Result<Option<Vec<SymbolInformation>>> {
-        let query = params.query;
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let uri = command::uri_arg(arguments, 0)?;
+        let index = self.link_index.clone();
         let inner_result = tokio::task::spawn_blocking(move || {
-            // Read configuration and get the vault directory.
             let config = config::read_config().map_err(|e| e.to_string())?;
             let vault_dir = Path::new(&config.vault_dir);
-            // Collect markdown files (deduplicated).
-            let files = collect_markdown_files(vault_dir);
-            let mut all_symbols = Vec::new();
-            for file in files {
-                let file_syms = extract_workspace_symbols_from_file(&file);
-                all_symbols.extend(file_syms);
-            }
-            // Apply fuzzy filtering if a query is provided.
-            let filtered = if query.trim().is_empty() {
-                all_symbols
-            } else {
-                let mut matches: Vec<(usize, SymbolInformation)> = all_symbols
-                    .into_iter()
-                    .filter_map(|sym| fuzzy_match(&query, &sym.name).map(|score| (score, sym)))
-                    .collect();
-                matches.sort_by_key(|(score, _)| *score);
-                matches.into_iter().map(|(_, sym)| sym).collect()
-            };
-            // Deduplicate symbols by using a key composed of (name, file URI, start line).
-            let mut seen = HashSet::new();
-            let deduped: Vec<_> = filtered
-                .into_iter()
-                .filter(|sym| {
-                    let key = (
-                        sym.name.clone(),
-                        sym.location.uri.to_string(),
-                        sym.location.range.start.line,
-                    );
-                    seen.insert(key)
-                })
-                .collect();
-            Ok::<_, String>(deduped)
+            let files = uri
+                .to_file_path()
+                .ok()
+                .and_then(|path| vault::virtual_path(vault_dir, &path))
+                .map(|vpath| index.lock().unwrap().backlink_files(&vpath))
+                .unwrap_or_default();
+            Ok::<_, String>(files)
         })
         .await
         .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
-        let symbols = inner_result.map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
-        Ok(Some(symbols))
+        let files = inner_result.map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        Ok(Some(serde_json::Value::Array(
+            files
+                .into_iter()
+                .map(|uri| serde_json::Value::String(uri.to_string()))
+                .collect(),
+        )))
     }
 
-    async fn completion(
+    /// Runs the extract-to-note command: moves the selected text into a new note and links
+    /// to it from where it was.
+    async fn extract_to_note(
         &self,
-        params: CompletionParams,
-    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
-        // Retrieve document URI and cursor position.
-        let uri = params.text_document_position.text_document.uri;
-        let position = params.text_document_position.position;
-        let docs = self.documents.lock().unwrap();
-        let text = if let Some(text) = docs.get(&uri) {
-            text
-        } else {
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let uri = command::uri_arg(arguments, 0)?;
+        let range = command::range_arg(arguments, 1)?;
+        let name = command::string_arg(arguments, 2, "a note name")?;
+        let text = match self.documents.lock().unwrap().get(&uri) {
+            Some(text) => text.clone(),
+            None => return Ok(None),
+        };
+        let encoding = self.encoding();
+        let (Some(start), Some(end)) = (
+            position::position_to_offset(&text, range.start, encoding),
+            position::position_to_offset(&text, range.end, encoding),
+        ) else {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                "range is outside the document",
+            ));
+        };
+        let config =
+            config::read_config().map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let Some(edit) = extract::extract_edit(
+            Path::new(&config.vault_dir),
+            &uri,
+            &text,
+            start..end,
+            name,
+            &self.delimiters(),
+            encoding,
+        ) else {
             return Ok(None);
         };
+        if let Err(err) = self.client.apply_edit(edit).await {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("Failed to extract note: {}", err),
+                )
+                .await;
+        }
+        Ok(None)
+    }
 
-        // Check if the text up to the cursor ends with "[[".
-        let lines: Vec<&str> = text.lines().collect();
-        if position.line as usize >= lines.len() {
+    /// Runs the create-note command: creates the note a link path points at, titled by the
+    /// second argument, and opens it.
+    async fn create_note(
+        &self,
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let link_path = command::string_arg(arguments, 0, "a link path")?;
+        let title = command::string_arg(arguments, 1, "a note title")?;
+        let config =
+            config::read_config().map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let Some(file) = create_note::note_path(Path::new(&config.vault_dir), link_path) else {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                "link path is outside the vault",
+            ));
+        };
+        if !self.create_note_file(&file, title).await {
             return Ok(None);
         }
-        let line = lines[position.line as usize];
-        let col = position.character as usize;
-        if col < 2 || !line[..col].ends_with("[[") {
-            return Ok(None);
+        if let Ok(uri) = Url::from_file_path(&file) {
+            let params = ShowDocumentParams {
+                uri,
+                external: None,
+                take_focus: Some(true),
+                selection: None,
+            };
+            // Opening the note is a convenience; clients without showDocument still get it.
+            let _ = self.client.show_document(params).await;
         }
+        Ok(None)
+    }
 
-        // Get the vault directory from the config.
-        let config: Config = notemancy_core::config::read_config().map_err(|_e| {
-            tower_lsp::jsonrpc::Error::new(tower_lsp::jsonrpc::ErrorCode::InternalError)
-        })?;
-        let vault_dir = std::path::Path::new(&config.vault_dir);
-
-        // Query the database for pages (notes).
-        let mut items = Vec::new();
-        let db = notemancy_core::db::crud::global();
-        if let Ok(mut stmt) = db.conn.prepare("SELECT vpath, title FROM pagetable") {
-            let rows = stmt.query_map([], |row| {
-                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
-            });
-            if let Ok(rows) = rows {
-                for row in rows.flatten() {
-                    let (vpath, title) = row;
-                    // Strip the vault dir from the vpath.
-                    let relative_vpath = std::path::Path::new(&vpath)
-                        .strip_prefix(vault_dir)
-                        .map(|p| p.to_string_lossy().to_string())
-                        .unwrap_or(vpath.clone());
-                    // Create a text edit that inserts our desired text at the current cursor position.
-                    let text_edit = TextEdit {
-                        range: Range {
-                            start: position,
-                            end: position,
-                        },
-                        new_text: format!("{} | {}", relative_vpath, title),
-                    };
-                    let item = CompletionItem {
-                        label: title.clone(),
-                        kind: Some(CompletionItemKind::FILE),
-                        detail: Some(relative_vpath),
-                        text_edit: Some(CompletionTextEdit::Edit(text_edit)),
-                        ..Default::default()
-                    };
-                    items.push(item);
-                }
+    /// Asks the client to create `file` with a frontmatter `title`, unless it exists
+    /// already. Returns whether the note is there afterwards.
+    async fn create_note_file(&self, file: &Path, title: &str) -> bool {
+        if file.exists() {
+            return true;
+        }
+        let Some(edit) = create_note::create_note_edit(file, title) else {
+            return false;
+        };
+        match self.client.apply_edit(edit).await {
+            Ok(response) => response.applied,
+            Err(err) => {
+                self.client
+                    .log_message(
+                        MessageType::WARNING,
+                        format!("Failed to create note: {}", err),
+                    )
+                    .await;
+                false
             }
         }
+    }
 
-        Ok(Some(CompletionResponse::Array(items)))
+    /// Runs the follow-link command: returns where the link at the given URI and position
+    /// leads, like go-to-definition, first creating the note when the link is unresolved.
+    async fn follow_link(
+        &self,
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let params = TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier {
+                uri: command::uri_arg(arguments, 0)?,
+            },
+            position: command::position_arg(arguments, 1)?,
+        };
+        let Some((link, _)) = self.link_at(&params) else {
+            return Ok(None);
+        };
+        let location = match self.link_location(&params.text_document.uri, &link)? {
+            Some(location) => location,
+            None => {
+                let config = config::read_config()
+                    .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+                let Some(file) = create_note::note_path(Path::new(&config.vault_dir), &link.path)
+                else {
+                    return Ok(None);
+                };
+                if !self
+                    .create_note_file(&file, &create_note::note_title(&link))
+                    .await
+                {
+                    return Ok(None);
+                }
+                let uri = Url::from_file_path(&file)
+                    .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+                self.file_index.lock().unwrap().insert(file);
+                Location {
+                    uri,
+                    range: Range::default(),
+                }
+            }
+        };
+        serde_json::to_value(location)
+            .map(Some)
+            .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())
     }
-}
 
-/// Parses markdown text and extracts headings as document symbols.
-fn parse_markdown_symbols(text: &str) -> Vec<DocumentSymbol> {
-    let mut symbols = Vec::new();
-    for (line_num, line) in text.lines().enumerate() {
-        if let Some(stripped) = line.strip_prefix('#') {
-            let mut level = 1;
-            let mut rest = stripped;
-            while rest.starts_with('#') {
-                level += 1;
-                rest = &rest[1..];
-            }
-            let title = rest.trim();
-            if title.is_empty() {
-                continue;
+    /// Resolves `link`, found in the document `uri`, to the location it points at: the
+    /// referenced block or anchored heading when there is one, otherwise the top of the
+    /// note. A link without a path, such as `[[#Heading]]`, points into the document itself.
+    fn link_location(&self, uri: &Url, link: &wikilink::WikiLink) -> Result<Option<Location>> {
+        if link.path.is_empty() {
+            if link.anchor.is_none() && link.block.is_none() {
+                return Ok(None);
             }
-            let start = Position {
-                line: line_num as u32,
-                character: 0,
+            let Some(text) = self.document_text(uri) else {
+                return Ok(None);
             };
-            let end = Position {
-                line: line_num as u32,
-                character: line.len() as u32,
-            };
-            let range = Range { start, end };
+            return Ok(
+                link_target_range(&text, link, self.encoding()).map(|range| Location {
+                    uri: uri.clone(),
+                    range,
+                }),
+            );
+        }
+        let config =
+            config::read_config().map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let vault_dir = Path::new(&config.vault_dir);
+        let pages = self.pages(vault_dir);
+        let target = match uri.to_file_path() {
+            Ok(file) => vault::resolve_note_link(vault_dir, &file, &link.path, &pages),
+            Err(_) => vault::resolve_link(vault_dir, &link.path, &pages),
+        };
+        let Some(target) = target else {
+            return Ok(None);
+        };
+        let target_uri = Url::from_file_path(&target)
+            .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
 
-            symbols.push(DocumentSymbol {
-                name: title.to_string(),
-                detail: Some(format!("Heading level {}", level)),
-                kind: SymbolKind::NAMESPACE,
-                tags: None,
-                range,
-                selection_range: range,
-                children: None,
-                deprecated: None,
-            });
+        let range = fs::read_to_string(&target)
+            .ok()
+            .and_then(|content| link_target_range(&content, link, self.encoding()))
+            .unwrap_or_default();
+        Ok(Some(Location {
+            uri: target_uri,
+            range,
+        }))
+    }
+
+    /// Runs the daily-note command, returning the URI of the daily note for today or for
+    /// the day at the optional offset argument.
+    async fn open_daily_note(
+        &self,
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let offset = match arguments.first() {
+            Some(_) => command::int_arg(arguments, 0, "a day offset")?,
+            None => 0,
+        };
+        let settings = self.settings.lock().unwrap().clone();
+        let inner_result = tokio::task::spawn_blocking(move || {
+            let config = config::read_config().map_err(|e| e.to_string())?;
+            let vault_dir = Path::new(&config.vault_dir);
+            let template = template::read_template(
+                &settings.templates_path(vault_dir),
+                &settings.daily_note_template,
+            );
+            daily::ensure_daily_note(
+                vault_dir,
+                &settings.daily_note_path,
+                date::day_from_today(offset),
+                template.as_deref(),
+            )
+        })
+        .await
+        .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let file = inner_result.map_err(tower_lsp::jsonrpc::Error::invalid_params)?;
+        let uri =
+            Url::from_file_path(&file).map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        self.file_index.lock().unwrap().insert(file);
+        Ok(Some(serde_json::Value::String(uri.to_string())))
+    }
+
+    /// Runs the document-stats command, counting the open note's words, headings and links.
+    async fn document_stats(
+        &self,
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let uri = command::uri_arg(arguments, 0)?;
+        let open_text = self.documents.lock().unwrap().get(&uri).cloned();
+        let text = match open_text {
+            Some(text) => text,
+            None => match uri
+                .to_file_path()
+                .ok()
+                .and_then(|path| fs::read_to_string(path).ok())
+            {
+                Some(text) => text,
+                None => return Ok(None),
+            },
+        };
+        let words_per_minute = self.settings.lock().unwrap().reading_words_per_minute;
+        serde_json::to_value(stats::document_stats(
+            &text,
+            words_per_minute,
+            &self.delimiters(),
+        ))
+        .map(Some)
+        .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())
+    }
+
+    /// Runs the rebuild-link-index command: indexes the vault's files from disk again, then
+    /// the open documents on top, and reports how many notes the index holds.
+    async fn rebuild_link_index(&self) -> Result<Option<serde_json::Value>> {
+        let delimiters = self.delimiters();
+        let inner_result = tokio::task::spawn_blocking(move || {
+            let config = config::read_config().map_err(|e| e.to_string())?;
+            Ok::<_, String>(link_index::LinkIndex::build(
+                Path::new(&config.vault_dir),
+                &delimiters,
+            ))
+        })
+        .await
+        .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let mut index = inner_result.map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        for (uri, text) in self.documents.lock().unwrap().iter() {
+            if let Ok(path) = uri.to_file_path() {
+                index.update(&path, text.clone());
+            }
         }
+        let notes = index.note_count();
+        *self.link_index.lock().unwrap() = index;
+        Ok(Some(serde_json::json!({ "notes": notes })))
     }
-    symbols
-}
 
-/// Reads a markdown file, extracts headings, and returns them as SymbolInformation.
-fn extract_workspace_symbols_from_file(file_path: &Path) -> Vec<SymbolInformation> {
-    let mut symbols = Vec::new();
-    if let Ok(content) = fs::read_to_string(file_path) {
-        let doc_symbols = parse_markdown_symbols(&content);
-        if let Ok(uri) = Url::from_file_path(file_path) {
-            for ds in doc_symbols {
-                let sym_info = SymbolInformation {
-                    name: ds.name,
-                    kind: ds.kind,
-                    location: Location {
-                        uri: uri.clone(),
-                        range: ds.range,
-                    },
-                    container_name: Some(
-                        file_path
-                            .file_name()
-                            .unwrap_or_default()
-                            .to_string_lossy()
-                            .into_owned(),
-                    ),
-                    deprecated: ds.deprecated,
-                    tags: ds.tags,
+    /// Runs the reindex command: rescans the vault to refresh the notemancy database, the
+    /// file and alias indexes and the link index, reporting progress on the work-done
+    /// token. Returns the number of files scanned, symbols found and database rows changed.
+    async fn reindex(&self, token: Option<ProgressToken>) -> Result<Option<serde_json::Value>> {
+        let mut work_done = progress::WorkDone::new(self.client.clone(), token);
+        let settings = self.settings.lock().unwrap().clone();
+        let encoding = self.encoding();
+        let inner_result = tokio::task::spawn_blocking(move || {
+            let config = config::read_config().map_err(|e| e.to_string())?;
+            let vault_dir = Path::new(&config.vault_dir);
+            work_done.begin("Reindexing notes");
+            let rules = settings.ignore_rules(vault_dir);
+            let files = vault::collect_markdown_files(vault_dir);
+            let mut index = link_index::LinkIndex::new(vault_dir, &settings.delimiters());
+            let mut aliases = HashMap::new();
+            let mut titles = Vec::new();
+            let mut notes = Vec::new();
+            let mut symbols = 0;
+            for (done, file) in files.iter().enumerate() {
+                work_done.report(done, files.len());
+                let Ok(content) = fs::read_to_string(file) else {
+                    continue;
                 };
-                symbols.push(sym_info);
+                let title = frontmatter::title(&content).unwrap_or_else(|| {
+                    file.file_stem()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned()
+                });
+                titles.push((file.clone(), title));
+                if vault::virtual_path(vault_dir, file)
+                    .is_none_or(|vpath| !rules.is_ignored(&vpath))
+                {
+                    let names = frontmatter::list_field(&content, "aliases");
+                    if !names.is_empty() {
+                        aliases.insert(file.clone(), names);
+                    }
+                    symbols += extract_workspace_symbols_from_file(file, &settings, encoding).len();
+                    notes.push(file.clone());
+                }
+                index.update(file, content);
+            }
+            let changed =
+                vault::reindex_pages(&notemancy_core::db::crud::global().conn, vault_dir, &titles);
+            work_done.end(Some(format!("Reindexed {} notes", files.len())));
+            let changed =
+                changed.map_err(|e| format!("Failed to update the notemancy database: {}", e))?;
+            Ok::<_, String>((files.len(), symbols, changed, notes, aliases, index))
+        })
+        .await
+        .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let (scanned, symbols, changed, notes, aliases, mut index) =
+            inner_result.map_err(|message| tower_lsp::jsonrpc::Error {
+                code: tower_lsp::jsonrpc::ErrorCode::InternalError,
+                message: message.into(),
+                data: None,
+            })?;
+        for (uri, text) in self.documents.lock().unwrap().iter() {
+            if let Ok(path) = uri.to_file_path() {
+                index.update(&path, text.clone());
             }
         }
+        *self.link_index.lock().unwrap() = index;
+        *self.file_index.lock().unwrap() = notes.into_iter().collect();
+        *self.aliases.lock().unwrap() = aliases;
+        self.symbol_cache.lock().unwrap().clear();
+        Ok(Some(serde_json::json!({
+            "files": scanned,
+            "symbols": symbols,
+            "pagesChanged": changed,
+        })))
     }
-    symbols
-}
 
-/// A simple fuzzy matching function that returns a “gap” score if all query characters
-/// are found in order within the candidate (ignoring case). Lower score indicates a better match.
-fn fuzzy_match(query: &str, candidate: &str) -> Option<usize> {
-    if query.trim().is_empty() {
-        return Some(0);
-    }
-    let query = query.to_lowercase();
-    let candidate = candidate.to_lowercase();
-    let candidate_chars: Vec<char> = candidate.chars().collect();
-    let mut pos_candidate = 0;
-    let mut total_gap = 0;
-    for qc in query.chars() {
-        let mut found = false;
-        while pos_candidate < candidate_chars.len() {
-            if candidate_chars[pos_candidate] == qc {
-                found = true;
-                pos_candidate += 1;
-                break;
-            }
-            total_gap += 1;
-            pos_candidate += 1;
-        }
-        if !found {
-            return None;
-        }
-    }
-    Some(total_gap)
-}
+    /// Runs the find-orphans command, listing the notes without links in, out or both.
+    async fn find_orphans(
+        &self,
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let mode = match arguments.first() {
+            Some(_) => {
+                let name = command::string_arg(arguments, 0, "an orphan mode")?;
+                link_index::OrphanMode::from_name(name).ok_or_else(|| {
+                    tower_lsp::jsonrpc::Error::invalid_params(format!(
+                        "unknown orphan mode '{}'",
+                        name
+                    ))
+                })?
+            }
+            None => link_index::OrphanMode::Isolated,
+        };
+        let index = self.link_index.clone();
+        let inner_result = tokio::task::spawn_blocking(move || {
+            let config = config::read_config().map_err(|e| e.to_string())?;
+            Ok::<_, String>(
+                index
+                    .lock()
+                    .unwrap()
+                    .orphans(Path::new(&config.vault_dir), mode),
+            )
+        })
+        .await
+        .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let orphans = inner_result.map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        serde_json::to_value(orphans)
+            .map(Some)
+            .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())
+    }
 
-#[tokio::main]
-async fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    if args.iter().any(|arg| arg == "--fmt") {
-        // In formatting mode: read all from stdin, format the markdown, and output to stdout.
-        let mut input = String::new();
-        io::stdin()
-            .read_to_string(&mut input)
-            .expect("Failed to read from stdin");
-        let formatted = formatter::format_markdown(&input);
-        println!("{}", formatted);
-        return;
+    /// Runs the top-linked-notes command: the notes with the most inbound links, building
+    /// the link index first when it hasn't been yet.
+    async fn top_linked_notes(
+        &self,
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let limit = match arguments.first() {
+            Some(_) => {
+                let limit = command::int_arg(arguments, 0, "a note limit")?;
+                usize::try_from(limit).map_err(|_| {
+                    tower_lsp::jsonrpc::Error::invalid_params("the limit can't be negative")
+                })?
+            }
+            None => link_index::TOP_LINKED_LIMIT,
+        };
+        if self.link_index.lock().unwrap().note_count() == 0 {
+            self.rebuild_link_index().await?;
+        }
+        let index = self.link_index.clone();
+        let inner_result = tokio::task::spawn_blocking(move || {
+            let config = config::read_config().map_err(|e| e.to_string())?;
+            Ok::<_, String>(
+                index
+                    .lock()
+                    .unwrap()
+                    .top_linked(Path::new(&config.vault_dir), limit),
+            )
+        })
+        .await
+        .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let notes = inner_result.map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        serde_json::to_value(notes)
+            .map(Some)
+            .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())
     }
 
-    let (service, socket) = LspService::build(|client| Backend {
-        client,
-        documents: Arc::new(Mutex::new(HashMap::new())),
-    })
-    .finish();
+    /// Runs the vault-broken-links command: every unresolved wiki-link in the notes, or
+    /// only in those of a folder, building the link index first when it hasn't been yet.
+    async fn vault_broken_links(
+        &self,
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let folder = match arguments.first() {
+            Some(_) => Some(command::string_arg(arguments, 0, "a folder")?.to_string()),
+            None => None,
+        };
+        if self.link_index.lock().unwrap().note_count() == 0 {
+            self.rebuild_link_index().await?;
+        }
+        let config =
+            config::read_config().map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let vault_dir = PathBuf::from(&config.vault_dir);
+        let pages = self.pages(&vault_dir);
+        let index = self.link_index.clone();
+        let encoding = self.encoding();
+        let links = tokio::task::spawn_blocking(move || {
+            index
+                .lock()
+                .unwrap()
+                .broken_links(&vault_dir, &pages, folder.as_deref(), encoding)
+        })
+        .await
+        .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        serde_json::to_value(links)
+            .map(Some)
+            .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())
+    }
 
-    let stdin = tokio::io::stdin();
-    let stdout = tokio::io::stdout();
-    Server::new(stdin, stdout, socket).serve(service).await;
-}
+    /// Runs the export-HTML command: renders the note to HTML, with wiki-links pointing at
+    /// the exports of their targets, and returns the path of the file written.
+    async fn export_html(
+        &self,
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let uri = command::uri_arg(arguments, 0)?;
+        let Some(text) = self.document_text(&uri) else {
+            return Ok(None);
+        };
+        let file = uri
+            .to_file_path()
+            .map_err(|_| tower_lsp::jsonrpc::Error::invalid_params("not a file URI"))?;
+        let config =
+            config::read_config().map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let vault_dir = Path::new(&config.vault_dir);
+        let Some(vpath) = vault::virtual_path(vault_dir, &file) else {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                "note is outside the vault",
+            ));
+        };
+        let pages = self.pages(vault_dir);
+        let resolve = |link: &wikilink::WikiLink| export::link_href(vault_dir, &file, link, &pages);
+        let fallback_title = file.file_stem().unwrap_or_default().to_string_lossy();
+        let html = export::render_html(&text, &fallback_title, &self.delimiters(), &resolve);
+        let export_dir = self.settings.lock().unwrap().export_path(vault_dir);
+        let output = export::output_path(vault_dir, export_dir.as_deref(), &vpath);
+        let written = output
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&output, html));
+        if let Err(err) = written {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("could not write {}: {}", output.display(), err),
+                )
+                .await;
+            return Err(tower_lsp::jsonrpc::Error::internal_error());
+        }
+        Ok(Some(serde_json::Value::String(
+            output.to_string_lossy().into_owned(),
+        )))
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::sync::{Arc, Mutex};
+    /// Runs the table-of-contents command: refreshes the note's table of contents, or
+    /// inserts one at the cursor.
+    async fn insert_toc(
+        &self,
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let uri = command::uri_arg(arguments, 0)?;
+        let cursor = command::position_arg(arguments, 1)?;
+        let text = match self.documents.lock().unwrap().get(&uri) {
+            Some(text) => text.clone(),
+            None => return Ok(None),
+        };
+        let encoding = self.encoding();
+        let Some(offset) = position::position_to_offset(&text, cursor, encoding) else {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                "position is outside the document",
+            ));
+        };
+        let edit = toc::toc_edit(&uri, &text, offset, &self.delimiters(), encoding);
+        if let Err(err) = self.client.apply_edit(edit).await {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("Failed to insert table of contents: {}", err),
+                )
+                .await;
+        }
+        Ok(None)
+    }
 
-    use tower_lsp::lsp_types::Url;
+    /// Runs the insert-backlinks command: lists the notes linking to this one in a
+    /// "Backlinks" section, refreshing the section when the note already has one.
+    async fn insert_backlinks(
+        &self,
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let uri = command::uri_arg(arguments, 0)?;
+        let Some(text) = self.document_text(&uri) else {
+            return Ok(None);
+        };
+        let config =
+            config::read_config().map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let vault_dir = Path::new(&config.vault_dir);
+        let Some(vpath) = uri
+            .to_file_path()
+            .ok()
+            .and_then(|file| vault::virtual_path(vault_dir, &file))
+        else {
+            return Ok(None);
+        };
+        let mut notes: Vec<(String, String)> = self
+            .link_index
+            .lock()
+            .unwrap()
+            .note_links(&vpath)
+            .into_iter()
+            .filter(|note| note.uri != uri)
+            .filter_map(|note| {
+                let file = note.uri.to_file_path().ok()?;
+                let title = frontmatter::title(&note.content).unwrap_or_else(|| {
+                    file.file_stem()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned()
+                });
+                Some((vault::virtual_path(vault_dir, &file)?, title))
+            })
+            .collect();
+        notes.sort();
+        let edit =
+            backlinks::backlinks_edit(&uri, &text, &notes, &self.delimiters(), self.encoding());
+        if let Err(err) = self.client.apply_edit(edit).await {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("Failed to insert backlinks: {}", err),
+                )
+                .await;
+        }
+        Ok(None)
+    }
+
+    /// Runs the split-at-heading command: moves the section under the heading at the given
+    /// position into a new note, and reports how many links were pointed at it.
+    async fn split_at_heading(
+        &self,
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let uri = command::uri_arg(arguments, 0)?;
+        let cursor = command::position_arg(arguments, 1)?;
+        let Some(text) = self.document_text(&uri) else {
+            return Ok(None);
+        };
+        let file = uri
+            .to_file_path()
+            .map_err(|_| tower_lsp::jsonrpc::Error::invalid_params("not a file URI"))?;
+        let config =
+            config::read_config().map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let split = split::split_edit(
+            Path::new(&config.vault_dir),
+            &file,
+            &text,
+            cursor.line as usize,
+            &self.link_index.lock().unwrap(),
+            self.encoding(),
+        );
+        let Some((edit, rewritten)) = split else {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                "the position must be on a heading",
+            ));
+        };
+        match self.client.apply_edit(edit).await {
+            Ok(response) if response.applied => {
+                Ok(Some(serde_json::json!({ "linksRewritten": rewritten })))
+            }
+            Ok(_) => Ok(Some(serde_json::json!({ "linksRewritten": 0 }))),
+            Err(err) => {
+                self.client
+                    .log_message(
+                        MessageType::WARNING,
+                        format!("Failed to split note: {}", err),
+                    )
+                    .await;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Runs the merge-note command: moves the source note's body into the target, points
+    /// the links to the source at the target and deletes the source.
+    async fn merge_note(
+        &self,
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let source_vpath = command::string_arg(arguments, 0, "a source note path")?;
+        let target_vpath = command::string_arg(arguments, 1, "a target note path")?;
+        let config =
+            config::read_config().map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let vault_dir = Path::new(&config.vault_dir);
+        let note_text = |vpath: &str| {
+            let file = vault::resolve_link_target(vault_dir, vpath)?;
+            let text = self.document_text(&Url::from_file_path(&file).ok()?)?;
+            Some((file, text))
+        };
+        let (Some((source_file, source_text)), Some((target_file, target_text))) =
+            (note_text(source_vpath), note_text(target_vpath))
+        else {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                "both notes must exist in the vault",
+            ));
+        };
+        let settings = self.settings.lock().unwrap().clone();
+        let title = frontmatter::title(&source_text).unwrap_or_else(|| {
+            source_file
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        });
+        let heading = settings.merge_heading.replace("{{title}}", &title);
+        let merged = merge::merge_edit(
+            vault_dir,
+            merge::MergeNote {
+                file: &source_file,
+                text: &source_text,
+            },
+            merge::MergeNote {
+                file: &target_file,
+                text: &target_text,
+            },
+            &heading,
+            settings.merge_link_to_section,
+            &self.link_index.lock().unwrap(),
+            self.encoding(),
+        );
+        let Some((edit, rewritten)) = merged else {
+            return Err(tower_lsp::jsonrpc::Error::invalid_params(
+                "a note can't be merged into itself",
+            ));
+        };
+        match self.client.apply_edit(edit).await {
+            Ok(response) if response.applied => {
+                Ok(Some(serde_json::json!({ "linksRewritten": rewritten })))
+            }
+            Ok(_) => Ok(Some(serde_json::json!({ "linksRewritten": 0 }))),
+            Err(err) => {
+                self.client
+                    .log_message(
+                        MessageType::WARNING,
+                        format!("Failed to merge notes: {}", err),
+                    )
+                    .await;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Runs the tag-hierarchy command, returning the vault's tag tree with note counts.
+    async fn tag_hierarchy(&self) -> Result<Option<serde_json::Value>> {
+        let inner_result = tokio::task::spawn_blocking(move || {
+            let config = config::read_config().map_err(|e| e.to_string())?;
+            Ok::<_, String>(tags::vault_tag_tree(Path::new(&config.vault_dir)))
+        })
+        .await
+        .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let tree = inner_result.map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        serde_json::to_value(tree)
+            .map(Some)
+            .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())
+    }
+
+    /// Runs the global-outline command: the headings of every note not ignored, optionally
+    /// only of the notes matching a query.
+    async fn global_outline(
+        &self,
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let query = match arguments.first() {
+            Some(_) => Some(command::string_arg(arguments, 0, "a query")?.to_string()),
+            None => None,
+        };
+        let settings = self.settings.lock().unwrap().clone();
+        let inner_result = tokio::task::spawn_blocking(move || {
+            let config = config::read_config().map_err(|e| e.to_string())?;
+            let vault_dir = Path::new(&config.vault_dir);
+            let files = vault::collect_notes(vault_dir, &settings.ignore_rules(vault_dir));
+            Ok::<_, String>(outline::vault_outline(vault_dir, &files, query.as_deref()))
+        })
+        .await
+        .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let outlines = inner_result.map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        serde_json::to_value(outlines)
+            .map(Some)
+            .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())
+    }
+
+    /// Runs the add-tags command: merges the given tags into the note's frontmatter and
+    /// reports how many of them were new.
+    async fn add_tags(&self, arguments: &[serde_json::Value]) -> Result<Option<serde_json::Value>> {
+        let uri = command::uri_arg(arguments, 0)?;
+        let new_tags = command::string_list_arg(arguments, 1, "a list of tags")?;
+        let text = match self.documents.lock().unwrap().get(&uri) {
+            Some(text) => text.clone(),
+            None => return Ok(None),
+        };
+        let mut added = 0;
+        if let Some((range, new_text, count)) = tags::add_tags_change(&text, &new_tags) {
+            let mut changes = HashMap::new();
+            changes.insert(
+                uri.clone(),
+                vec![TextEdit {
+                    range: position::offset_range(&text, &range, self.encoding()),
+                    new_text,
+                }],
+            );
+            let edit = WorkspaceEdit {
+                changes: Some(changes),
+                ..Default::default()
+            };
+            match self.client.apply_edit(edit).await {
+                Ok(response) if response.applied => added = count,
+                Ok(_) => {}
+                Err(err) => {
+                    self.client
+                        .log_message(MessageType::WARNING, format!("Failed to add tags: {}", err))
+                        .await;
+                }
+            }
+        }
+        Ok(Some(serde_json::json!({ "added": added })))
+    }
+
+    /// Runs the normalize-frontmatter command: sorts the open note's frontmatter keys and
+    /// rewrites its lists in the configured style, returning the edit applied.
+    async fn normalize_frontmatter(
+        &self,
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let uri = command::uri_arg(arguments, 0)?;
+        let text = match self.documents.lock().unwrap().get(&uri) {
+            Some(text) => text.clone(),
+            None => return Ok(None),
+        };
+        let settings = self.settings.lock().unwrap().clone();
+        let Some((range, new_text)) = frontmatter::normalize_frontmatter(
+            &text,
+            &settings.frontmatter_key_order,
+            settings.frontmatter_list_style,
+        ) else {
+            return Ok(None);
+        };
+        let mut changes = HashMap::new();
+        changes.insert(
+            uri,
+            vec![TextEdit {
+                range: position::offset_range(&text, &range, self.encoding()),
+                new_text,
+            }],
+        );
+        let edit = WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        };
+        if let Err(err) = self.client.apply_edit(edit.clone()).await {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("Failed to normalize frontmatter: {}", err),
+                )
+                .await;
+        }
+        serde_json::to_value(edit)
+            .map(Some)
+            .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())
+    }
+
+    /// Runs the consolidate-tags command: moves the open note's tags into its frontmatter
+    /// or out into its body, returning the edit applied.
+    async fn consolidate_tags(
+        &self,
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let uri = command::uri_arg(arguments, 0)?;
+        let direction = match arguments.get(1) {
+            Some(_) => {
+                let name = command::string_arg(arguments, 1, "a tag direction")?;
+                tags::TagDirection::from_name(name).ok_or_else(|| {
+                    tower_lsp::jsonrpc::Error::invalid_params(format!(
+                        "unknown tag direction '{}'",
+                        name
+                    ))
+                })?
+            }
+            None => tags::TagDirection::ToFrontmatter,
+        };
+        let remove = match arguments.get(2) {
+            Some(_) => command::bool_arg(arguments, 2, "whether to remove the moved tags")?,
+            None => false,
+        };
+        let Some(text) = self.document_text(&uri) else {
+            return Ok(None);
+        };
+        let changes = tags::consolidate_tags_changes(&text, direction, remove);
+        if changes.is_empty() {
+            return Ok(None);
+        }
+        let encoding = self.encoding();
+        let edits = changes
+            .into_iter()
+            .map(|(range, new_text)| TextEdit {
+                range: position::offset_range(&text, &range, encoding),
+                new_text,
+            })
+            .collect();
+        let edit = WorkspaceEdit {
+            changes: Some(HashMap::from([(uri, edits)])),
+            ..Default::default()
+        };
+        if let Err(err) = self.client.apply_edit(edit.clone()).await {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("Failed to consolidate tags: {}", err),
+                )
+                .await;
+        }
+        serde_json::to_value(edit)
+            .map(Some)
+            .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())
+    }
+
+    /// Runs the related-notes command: the notes not ignored sharing the most tags with
+    /// the given one.
+    async fn related_notes(
+        &self,
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let uri = command::uri_arg(arguments, 0)?;
+        let file = uri
+            .to_file_path()
+            .map_err(|_| tower_lsp::jsonrpc::Error::invalid_params("not a file URI"))?;
+        let Some(text) = self.document_text(&uri) else {
+            return Ok(None);
+        };
+        let settings = self.settings.lock().unwrap().clone();
+        let inner_result = tokio::task::spawn_blocking(move || {
+            let config = config::read_config().map_err(|e| e.to_string())?;
+            let vault_dir = Path::new(&config.vault_dir);
+            let files = vault::collect_notes(vault_dir, &settings.ignore_rules(vault_dir));
+            Ok::<_, String>(tags::related_notes(
+                vault_dir,
+                &files,
+                &file,
+                &text,
+                tags::RELATED_NOTES_LIMIT,
+            ))
+        })
+        .await
+        .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let notes = inner_result.map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        serde_json::to_value(notes)
+            .map(Some)
+            .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())
+    }
+
+    /// Runs the find-tag-usages command, returning the locations of every use of a tag.
+    async fn find_tag_usages(
+        &self,
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let tag = command::string_arg(arguments, 0, "a tag")?.to_string();
+        let encoding = self.encoding();
+        let inner_result = tokio::task::spawn_blocking(move || {
+            let config = config::read_config().map_err(|e| e.to_string())?;
+            Ok::<_, String>(tags::vault_tag_usages(
+                Path::new(&config.vault_dir),
+                &tag,
+                encoding,
+            ))
+        })
+        .await
+        .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let locations = inner_result.map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        serde_json::to_value(locations)
+            .map(Some)
+            .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())
+    }
+
+    /// Runs the unlinked-mentions command: the first argument is the note title, and an
+    /// optional second argument is the URI of the note itself, whose own text is skipped.
+    async fn find_unlinked_mentions(
+        &self,
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let title = command::string_arg(arguments, 0, "a note title")?.to_string();
+        let exclude = match arguments.get(1) {
+            Some(_) => command::uri_arg(arguments, 1)?.to_file_path().ok(),
+            None => None,
+        };
+        let index = self.link_index.clone();
+        let encoding = self.encoding();
+        let inner_result = tokio::task::spawn_blocking(move || {
+            Ok::<_, String>(mentions::vault_unlinked_mentions(
+                &index.lock().unwrap(),
+                &title,
+                exclude.as_deref(),
+                encoding,
+            ))
+        })
+        .await
+        .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let locations = inner_result.map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        serde_json::to_value(locations)
+            .map(Some)
+            .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())
+    }
+
+    /// Runs the link-neighbors command, returning the notes the given note links to and
+    /// the notes linking to it.
+    async fn link_neighbors(
+        &self,
+        arguments: &[serde_json::Value],
+    ) -> Result<Option<serde_json::Value>> {
+        let uri = command::uri_arg(arguments, 0)?;
+        let open_docs = self.documents.lock().unwrap().clone();
+        let aliases = self.aliases.lock().unwrap().clone();
+        let index = self.link_index.clone();
+        let encoding = self.encoding();
+        let inner_result = tokio::task::spawn_blocking(move || {
+            let config = config::read_config().map_err(|e| e.to_string())?;
+            let vault_dir = Path::new(&config.vault_dir);
+            let text = match open_docs.get(&uri) {
+                Some(text) => text.clone(),
+                None => uri
+                    .to_file_path()
+                    .ok()
+                    .and_then(|path| fs::read_to_string(path).ok())
+                    .unwrap_or_default(),
+            };
+            let pages = vault::with_aliases(vault::indexed_pages(vault_dir), &aliases);
+            Ok::<_, String>(graph::link_neighbors(
+                vault_dir,
+                &uri,
+                &text,
+                &pages,
+                &index.lock().unwrap(),
+                encoding,
+            ))
+        })
+        .await
+        .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let neighbors = inner_result.map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        serde_json::to_value(neighbors)
+            .map(Some)
+            .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())
+    }
+
+    /// Recomputes the diagnostics for a document and publishes them, replacing any
+    /// previously published set. Links are only checked when the vault is known; the
+    /// headings and frontmatter are checked either way.
+    async fn publish_diagnostics(&self, uri: Url, text: &str, version: i32) {
+        let encoding = self.encoding();
+        let mut diagnostics = Vec::new();
+        if let Ok(config) = config::read_config() {
+            let vault_dir = Path::new(&config.vault_dir);
+            let pages = self.pages(vault_dir);
+            let file = uri.to_file_path().ok();
+            let delimiters = self.delimiters();
+            diagnostics = diagnostics::broken_link_diagnostics(
+                text,
+                file.as_deref(),
+                vault_dir,
+                &pages,
+                &delimiters,
+                encoding,
+            );
+            if self.settings.lock().unwrap().self_link_diagnostics {
+                if let Some(file) = &file {
+                    diagnostics.extend(diagnostics::self_link_diagnostics(
+                        text,
+                        file,
+                        vault_dir,
+                        &pages,
+                        &delimiters,
+                        encoding,
+                    ));
+                }
+            }
+        }
+        diagnostics.extend(diagnostics::duplicate_heading_diagnostics(text, encoding));
+        diagnostics.extend(diagnostics::frontmatter_diagnostics(text, encoding));
+        self.client
+            .publish_diagnostics(uri, diagnostics, Some(version))
+            .await;
+    }
+}
+
+#[tower_lsp::async_trait]
+impl LanguageServer for Backend {
+    async fn initialize(
+        &self,
+        params: tower_lsp::lsp_types::InitializeParams,
+    ) -> tower_lsp::jsonrpc::Result<tower_lsp::lsp_types::InitializeResult> {
+        let (settings, rejected) =
+            settings::Settings::from_initialization_options(params.initialization_options);
+        for message in rejected {
+            self.client.log_message(MessageType::WARNING, message).await;
+        }
+        let delimiters = settings.delimiters();
+        let mut trigger_characters = vec![
+            delimiters.open.chars().next().unwrap_or('[').to_string(),
+            "#".to_string(),
+        ];
+        trigger_characters.extend(settings.date_trigger.chars().next().map(String::from));
+        *self.settings.lock().unwrap() = settings;
+        let encoding = position::negotiate_encoding(&params.capabilities);
+        *self.encoding.lock().unwrap() = encoding;
+        Ok(tower_lsp::lsp_types::InitializeResult {
+            capabilities: ServerCapabilities {
+                position_encoding: Some(encoding.kind()),
+                text_document_sync: Some(TextDocumentSyncCapability::Kind(
+                    TextDocumentSyncKind::INCREMENTAL,
+                )),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: Some(vec![
+                            CodeActionKind::REFACTOR_REWRITE,
+                            CodeActionKind::REFACTOR_EXTRACT,
+                            CodeActionKind::SOURCE,
+                            CodeActionKind::QUICKFIX,
+                        ]),
+                        ..Default::default()
+                    },
+                )),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: vec![
+                        template::INSERT_TEMPLATE_COMMAND.to_string(),
+                        code_lens::SHOW_BACKLINKS_COMMAND.to_string(),
+                        extract::EXTRACT_TO_NOTE_COMMAND.to_string(),
+                        toc::INSERT_TOC_COMMAND.to_string(),
+                        tags::ADD_TAGS_COMMAND.to_string(),
+                        tags::FIND_TAG_USAGES_COMMAND.to_string(),
+                        graph::LINK_NEIGHBORS_COMMAND.to_string(),
+                        mentions::FIND_UNLINKED_MENTIONS_COMMAND.to_string(),
+                        create_note::CREATE_NOTE_COMMAND.to_string(),
+                        daily::OPEN_DAILY_NOTE_COMMAND.to_string(),
+                        stats::DOCUMENT_STATS_COMMAND.to_string(),
+                        link_index::REBUILD_LINK_INDEX_COMMAND.to_string(),
+                        frontmatter::NORMALIZE_FRONTMATTER_COMMAND.to_string(),
+                        create_note::FOLLOW_LINK_COMMAND.to_string(),
+                        link_index::FIND_ORPHANS_COMMAND.to_string(),
+                        export::EXPORT_HTML_COMMAND.to_string(),
+                        backlinks::INSERT_BACKLINKS_COMMAND.to_string(),
+                        link_index::REINDEX_COMMAND.to_string(),
+                        link_index::TOP_LINKED_NOTES_COMMAND.to_string(),
+                        link_index::VAULT_BROKEN_LINKS_COMMAND.to_string(),
+                        merge::MERGE_NOTE_COMMAND.to_string(),
+                        tags::TAG_HIERARCHY_COMMAND.to_string(),
+                        outline::GLOBAL_OUTLINE_COMMAND.to_string(),
+                        split::SPLIT_AT_HEADING_COMMAND.to_string(),
+                        tags::CONSOLIDATE_TAGS_COMMAND.to_string(),
+                        tags::RELATED_NOTES_COMMAND.to_string(),
+                    ],
+                    work_done_progress_options: WorkDoneProgressOptions {
+                        work_done_progress: Some(true),
+                    },
+                }),
+                code_lens_provider: Some(CodeLensOptions {
+                    resolve_provider: Some(false),
+                }),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
+                document_link_provider: Some(DocumentLinkOptions {
+                    resolve_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                }),
+                workspace_symbol_provider: Some(OneOf::Right(WorkspaceSymbolOptions {
+                    work_done_progress_options: WorkDoneProgressOptions {
+                        work_done_progress: Some(true),
+                    },
+                    resolve_provider: None,
+                })),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
+                linked_editing_range_provider: Some(LinkedEditingRangeServerCapabilities::Simple(
+                    true,
+                )),
+                inlay_hint_provider: Some(OneOf::Left(true)),
+                color_provider: Some(ColorProviderCapability::Simple(true)),
+                semantic_tokens_provider: Some(
+                    SemanticTokensServerCapabilities::SemanticTokensOptions(
+                        SemanticTokensOptions {
+                            legend: semantic_tokens::legend(),
+                            full: Some(SemanticTokensFullOptions::Bool(true)),
+                            range: None,
+                            work_done_progress_options: Default::default(),
+                        },
+                    ),
+                ),
+                definition_provider: Some(OneOf::Left(true)),
+                references_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                })),
+                // Register the completion provider with trigger characters for notes (the
+                // first character of the link delimiter), "#" (heading anchors and tags)
+                // and dates (the first character of the date trigger).
+                completion_provider: Some(CompletionOptions {
+                    resolve_provider: Some(true),
+                    trigger_characters: Some(trigger_characters),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            server_info: None,
+        })
+    }
+
+    async fn initialized(&self, _params: InitializedParams) {
+        self.client
+            .log_message(MessageType::INFO, "notemancy-lsp initialized!")
+            .await;
+
+        // Watch markdown files so notes created or deleted outside the editor are seen.
+        let watchers = DidChangeWatchedFilesRegistrationOptions {
+            watchers: vec![FileSystemWatcher {
+                glob_pattern: GlobPattern::String("**/*.md".to_string()),
+                kind: None,
+            }],
+        };
+        let registration = Registration {
+            id: "notemancy-watched-files".to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: serde_json::to_value(watchers).ok(),
+        };
+        if let Err(err) = self.client.register_capability(vec![registration]).await {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("Could not watch markdown files: {}", err),
+                )
+                .await;
+        }
+
+        let settings = self.settings.lock().unwrap().clone();
+        let (files, aliases, index) = tokio::task::spawn_blocking(move || {
+            config::read_config()
+                .map(|config| {
+                    let vault_dir = Path::new(&config.vault_dir);
+                    let files = vault::collect_notes(vault_dir, &settings.ignore_rules(vault_dir));
+                    let aliases: HashMap<_, _> = files
+                        .iter()
+                        .filter_map(|file| {
+                            let text = fs::read_to_string(file).ok()?;
+                            let names = frontmatter::list_field(&text, "aliases");
+                            (!names.is_empty()).then(|| (file.clone(), names))
+                        })
+                        .collect();
+                    let index = link_index::LinkIndex::build(vault_dir, &settings.delimiters());
+                    (files, aliases, index)
+                })
+                .unwrap_or_default()
+        })
+        .await
+        .unwrap_or_default();
+        self.file_index.lock().unwrap().extend(files);
+        self.aliases.lock().unwrap().extend(aliases);
+        let mut link_index = self.link_index.lock().unwrap();
+        *link_index = index;
+        for (uri, text) in self.documents.lock().unwrap().iter() {
+            if let Ok(path) = uri.to_file_path() {
+                link_index.update(&path, text.clone());
+            }
+        }
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        // The link index lives in memory only, so there is nothing to write back; pending
+        // diagnostics would only be published to a client that is going away.
+        self.diagnostics_debounce.shutdown(SHUTDOWN_TIMEOUT).await;
+        Ok(())
+    }
+
+    async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        let uri = params.text_document.uri;
+        let text = params.text_document.text;
+        // The opened text supersedes any edit still waiting to be checked.
+        self.diagnostics_debounce.cancel(&uri);
+        self.symbol_cache.lock().unwrap().remove(&uri);
+        self.documents
+            .lock()
+            .unwrap()
+            .insert(uri.clone(), text.clone());
+        if let Ok(path) = uri.to_file_path() {
+            self.link_index.lock().unwrap().update(&path, text.clone());
+            self.update_aliases(path, &text);
+        }
+        self.publish_diagnostics(uri, &text, params.text_document.version)
+            .await;
+    }
+
+    async fn did_change(&self, params: DidChangeTextDocumentParams) {
+        let uri = params.text_document.uri;
+        let encoding = self.encoding();
+        let text = {
+            let mut docs = self.documents.lock().unwrap();
+            let text = docs.entry(uri.clone()).or_default();
+            for change in params.content_changes {
+                position::apply_change(text, change, encoding);
+            }
+            text.clone()
+        };
+        self.symbol_cache.lock().unwrap().remove(&uri);
+        if let Ok(path) = uri.to_file_path() {
+            self.link_index.lock().unwrap().update(&path, text.clone());
+            self.update_aliases(path, &text);
+        }
+        // Only this document's links are resolved again, once the edits pause.
+        let delay = Duration::from_millis(self.settings.lock().unwrap().diagnostics_delay_ms);
+        let backend = self.clone();
+        let version = params.text_document.version;
+        self.diagnostics_debounce
+            .schedule(uri.clone(), delay, async move {
+                backend.publish_diagnostics(uri, &text, version).await;
+            });
+    }
+
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri;
+        // Nothing is left to check once the buffer is gone.
+        self.diagnostics_debounce.cancel(&uri);
+        self.documents.lock().unwrap().remove(&uri);
+        self.symbol_cache.lock().unwrap().remove(&uri);
+        let Ok(path) = uri.to_file_path() else {
+            return;
+        };
+        // Unsaved edits are discarded with the buffer, so the saved file is indexed again.
+        match fs::read_to_string(&path) {
+            Ok(text) => {
+                self.link_index.lock().unwrap().update(&path, text.clone());
+                self.update_aliases(path, &text);
+            }
+            Err(_) => {
+                self.link_index.lock().unwrap().remove(&path);
+                self.aliases.lock().unwrap().remove(&path);
+            }
+        }
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        let vault = config::read_config().ok().map(|config| {
+            let vault_dir = PathBuf::from(&config.vault_dir);
+            let rules = self.settings.lock().unwrap().ignore_rules(&vault_dir);
+            (vault_dir, rules)
+        });
+        for event in params.changes {
+            self.symbol_cache.lock().unwrap().remove(&event.uri);
+            let Ok(path) = event.uri.to_file_path() else {
+                continue;
+            };
+            let ignored = vault.as_ref().is_some_and(|(vault_dir, rules)| {
+                vault::virtual_path(vault_dir, &path).is_some_and(|vpath| rules.is_ignored(&vpath))
+            });
+            if event.typ == FileChangeType::DELETED || ignored {
+                self.aliases.lock().unwrap().remove(&path);
+                self.link_index.lock().unwrap().remove(&path);
+                self.file_index.lock().unwrap().remove(&path);
+            } else {
+                // An open document's in-memory text is newer than what was saved.
+                if !self.documents.lock().unwrap().contains_key(&event.uri) {
+                    if let Ok(text) = fs::read_to_string(&path) {
+                        self.update_aliases(path.clone(), &text);
+                        self.link_index.lock().unwrap().update(&path, text);
+                    }
+                }
+                self.file_index.lock().unwrap().insert(path);
+            }
+        }
+    }
+
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        let uri = params.text_document.uri;
+        if let Some(symbols) = self.symbol_cache.lock().unwrap().get(&uri) {
+            return Ok(Some(DocumentSymbolResponse::Nested(symbols.clone())));
+        }
+        let open_text = self.documents.lock().unwrap().get(&uri).cloned();
+        let settings = self.settings.lock().unwrap().clone();
+        let encoding = self.encoding();
+        let symbols = match open_text {
+            Some(text) => {
+                let symbols = outline_symbols(&text, &settings, encoding);
+                self.symbol_cache
+                    .lock()
+                    .unwrap()
+                    .insert(uri, symbols.clone());
+                symbols
+            }
+            // Documents that aren't open are read from disk and not cached, since
+            // nothing would tell us when they change.
+            None => match uri
+                .to_file_path()
+                .ok()
+                .and_then(|p| fs::read_to_string(p).ok())
+            {
+                Some(text) => outline_symbols(&text, &settings, encoding),
+                None => return Ok(None),
+            },
+        };
+        Ok(Some(DocumentSymbolResponse::Nested(symbols)))
+    }
+
+    async fn folding_range(&self, params: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        let docs = self.documents.lock().unwrap();
+        Ok(docs
+            .get(&params.text_document.uri)
+            .map(|text| folding::folding_ranges(text)))
+    }
+
+    async fn document_link(&self, params: DocumentLinkParams) -> Result<Option<Vec<DocumentLink>>> {
+        let text = match self
+            .documents
+            .lock()
+            .unwrap()
+            .get(&params.text_document.uri)
+        {
+            Some(text) => text.clone(),
+            None => return Ok(None),
+        };
+        Ok(Some(document_link::document_links(
+            &text,
+            &self.delimiters(),
+            self.encoding(),
+        )))
+    }
+
+    async fn document_link_resolve(&self, link: DocumentLink) -> Result<DocumentLink> {
+        let aliases = self.aliases.lock().unwrap().clone();
+        let inner_result = tokio::task::spawn_blocking(move || {
+            let config = config::read_config().map_err(|e| e.to_string())?;
+            let vault_dir = Path::new(&config.vault_dir);
+            let pages = vault::with_aliases(vault::indexed_pages(vault_dir), &aliases);
+            Ok::<_, String>(document_link::resolve_document_link(
+                link, vault_dir, &pages,
+            ))
+        })
+        .await
+        .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        inner_result.map_err(|_| tower_lsp::jsonrpc::Error::internal_error())
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let uri = params.text_document.uri;
+        let text = match self.documents.lock().unwrap().get(&uri) {
+            Some(text) => text.clone(),
+            None => return Ok(None),
+        };
+        let encoding = self.encoding();
+        let offset = match position::position_to_offset(&text, params.range.start, encoding) {
+            Some(offset) => offset,
+            None => return Ok(None),
+        };
+        let config =
+            config::read_config().map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let vault_dir = Path::new(&config.vault_dir);
+        let delimiters = self.delimiters();
+        let pages = self.pages(vault_dir);
+        let mut actions = code_action::link_conversion_actions(
+            &uri,
+            &text,
+            offset,
+            vault_dir,
+            &pages,
+            &delimiters,
+            encoding,
+        );
+        actions.extend(code_action::heading_case_actions(
+            &uri,
+            &text,
+            params.range.start.line as usize,
+            &delimiters,
+            encoding,
+        ));
+        if let Some(link) = wikilink::wikilink_at(&text, offset, &delimiters) {
+            let file = uri.to_file_path().unwrap_or_default();
+            if vault::resolve_note_link(vault_dir, &file, &link.path, &pages).is_none() {
+                let mut notes: Vec<(String, String)> = pages
+                    .iter()
+                    .filter_map(|page| {
+                        let vpath = vault::virtual_path(vault_dir, &page.file)?;
+                        Some((vpath, page.title.clone()))
+                    })
+                    .collect();
+                for file in self.file_index.lock().unwrap().iter() {
+                    let Some(vpath) = vault::virtual_path(vault_dir, file) else {
+                        continue;
+                    };
+                    if notes.iter().all(|(known, _)| *known != vpath) {
+                        let title = file.file_stem().unwrap_or_default().to_string_lossy();
+                        notes.push((vpath, title.into_owned()));
+                    }
+                }
+                actions.extend(code_action::link_suggestion_actions(
+                    &uri, &text, &link, &notes, encoding,
+                ));
+                actions.extend(create_note::create_note_action(&link));
+            }
+        }
+        if let Some(end) = position::position_to_offset(&text, params.range.end, encoding) {
+            if end > offset {
+                actions.extend(code_action::callout_actions(
+                    &uri,
+                    &text,
+                    offset..end,
+                    encoding,
+                ));
+                actions.extend(extract::extract_action(
+                    &uri,
+                    params.range,
+                    &text[offset..end],
+                ));
+            }
+        }
+        let templates_dir = self.settings.lock().unwrap().templates_path(vault_dir);
+        actions.extend(template::template_actions(
+            &uri,
+            &template::list_templates(&templates_dir),
+        ));
+        Ok(Some(actions))
+    }
+
+    async fn execute_command(
+        &self,
+        params: ExecuteCommandParams,
+    ) -> Result<Option<serde_json::Value>> {
+        match params.command.as_str() {
+            template::INSERT_TEMPLATE_COMMAND => self.insert_template(&params.arguments).await,
+            code_lens::SHOW_BACKLINKS_COMMAND => self.show_backlinks(&params.arguments).await,
+            extract::EXTRACT_TO_NOTE_COMMAND => self.extract_to_note(&params.arguments).await,
+            toc::INSERT_TOC_COMMAND => self.insert_toc(&params.arguments).await,
+            tags::ADD_TAGS_COMMAND => self.add_tags(&params.arguments).await,
+            tags::FIND_TAG_USAGES_COMMAND => self.find_tag_usages(&params.arguments).await,
+            graph::LINK_NEIGHBORS_COMMAND => self.link_neighbors(&params.arguments).await,
+            mentions::FIND_UNLINKED_MENTIONS_COMMAND => {
+                self.find_unlinked_mentions(&params.arguments).await
+            }
+            create_note::CREATE_NOTE_COMMAND => self.create_note(&params.arguments).await,
+            daily::OPEN_DAILY_NOTE_COMMAND => self.open_daily_note(&params.arguments).await,
+            stats::DOCUMENT_STATS_COMMAND => self.document_stats(&params.arguments).await,
+            link_index::REBUILD_LINK_INDEX_COMMAND => self.rebuild_link_index().await,
+            link_index::REINDEX_COMMAND => {
+                self.reindex(params.work_done_progress_params.work_done_token)
+                    .await
+            }
+            create_note::FOLLOW_LINK_COMMAND => self.follow_link(&params.arguments).await,
+            link_index::FIND_ORPHANS_COMMAND => self.find_orphans(&params.arguments).await,
+            link_index::TOP_LINKED_NOTES_COMMAND => self.top_linked_notes(&params.arguments).await,
+            link_index::VAULT_BROKEN_LINKS_COMMAND => {
+                self.vault_broken_links(&params.arguments).await
+            }
+            export::EXPORT_HTML_COMMAND => self.export_html(&params.arguments).await,
+            backlinks::INSERT_BACKLINKS_COMMAND => self.insert_backlinks(&params.arguments).await,
+            merge::MERGE_NOTE_COMMAND => self.merge_note(&params.arguments).await,
+            tags::TAG_HIERARCHY_COMMAND => self.tag_hierarchy().await,
+            outline::GLOBAL_OUTLINE_COMMAND => self.global_outline(&params.arguments).await,
+            split::SPLIT_AT_HEADING_COMMAND => self.split_at_heading(&params.arguments).await,
+            tags::CONSOLIDATE_TAGS_COMMAND => self.consolidate_tags(&params.arguments).await,
+            tags::RELATED_NOTES_COMMAND => self.related_notes(&params.arguments).await,
+            frontmatter::NORMALIZE_FRONTMATTER_COMMAND => {
+                self.normalize_frontmatter(&params.arguments).await
+            }
+            _ => Err(tower_lsp::jsonrpc::Error::method_not_found()),
+        }
+    }
+
+    async fn completion_resolve(&self, item: CompletionItem) -> Result<CompletionItem> {
+        // Heading items carry their note's URI and line, note items their virtual path;
+        // the rest are already complete.
+        let heading = item.data.as_ref().and_then(|data| {
+            let uri = Url::parse(data.get("uri")?.as_str()?).ok()?;
+            Some((uri, data.get("line")?.as_u64()? as usize))
+        });
+        if let Some((uri, line)) = heading {
+            let documentation = self
+                .document_text(&uri)
+                .and_then(|content| completion::heading_documentation(&content, line));
+            return Ok(CompletionItem {
+                documentation,
+                ..item
+            });
+        }
+        let Some(vpath) = item.data.as_ref().and_then(|data| data.as_str()) else {
+            return Ok(item);
+        };
+        let vpath = vpath.to_string();
+        let inner_result = tokio::task::spawn_blocking(move || {
+            let config = config::read_config().map_err(|e| e.to_string())?;
+            let content = vault::resolve_link_target(Path::new(&config.vault_dir), &vpath)
+                .and_then(|file| fs::read_to_string(file).ok());
+            Ok::<_, String>(content.map(|content| completion::note_documentation(&content)))
+        })
+        .await
+        .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let documentation =
+            inner_result.map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        Ok(CompletionItem {
+            documentation,
+            ..item
+        })
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
+        let uri = params.text_document.uri;
+        let text = match self.documents.lock().unwrap().get(&uri) {
+            Some(text) => text.clone(),
+            None => return Ok(None),
+        };
+        let lens_uri = uri.clone();
+        let index = self.link_index.clone();
+        let inner_result = tokio::task::spawn_blocking(move || {
+            let config = config::read_config().map_err(|e| e.to_string())?;
+            let vault_dir = Path::new(&config.vault_dir);
+            let count = uri
+                .to_file_path()
+                .ok()
+                .and_then(|path| vault::virtual_path(vault_dir, &path))
+                .map_or(0, |vpath| {
+                    index.lock().unwrap().backlink_files(&vpath).len()
+                });
+            Ok::<_, String>(count)
+        })
+        .await
+        .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let count = inner_result.map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        Ok(Some(vec![code_lens::backlink_lens(
+            &lens_uri,
+            &text,
+            count,
+            self.encoding(),
+        )]))
+    }
+
+    async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
+        let encoding = self.encoding();
+        let docs = self.documents.lock().unwrap();
+        let text = match docs.get(&params.text_document.uri) {
+            Some(text) => text,
+            None => return Ok(None),
+        };
+        let options = self.settings.lock().unwrap().format_options();
+        let formatted = formatter::apply_final_newline_options(
+            &formatter::format_markdown(text, &options),
+            text,
+            params.options.insert_final_newline,
+            params.options.trim_final_newlines,
+            options.ensure_final_newline,
+        );
+        if formatted == *text {
+            return Ok(Some(Vec::new()));
+        }
+        // Replace the whole document in a single edit.
+        Ok(Some(vec![TextEdit {
+            range: Range {
+                start: Position::default(),
+                end: position::offset_to_position(text, text.len(), encoding),
+            },
+            new_text: formatted,
+        }]))
+    }
+
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let encoding = self.encoding();
+        let docs = self.documents.lock().unwrap();
+        let text = match docs.get(&params.text_document.uri) {
+            Some(text) => text,
+            None => return Ok(None),
+        };
+        let lines: Vec<&str> = text.lines().collect();
+        if lines.is_empty() {
+            return Ok(Some(Vec::new()));
+        }
+        // Format whole lines; a selection ending at column 0 doesn't include that line.
+        let start_line = (params.range.start.line as usize).min(lines.len() - 1);
+        let mut end_line = (params.range.end.line as usize).min(lines.len() - 1);
+        if params.range.end.character == 0 && end_line > start_line {
+            end_line -= 1;
+        }
+        let selected = lines[start_line..=end_line].join("\n");
+        let options = self.settings.lock().unwrap().format_options();
+        let formatted = formatter::format_markdown_range(text, start_line, end_line, &options);
+        if formatted == selected {
+            return Ok(Some(Vec::new()));
+        }
+        Ok(Some(vec![TextEdit {
+            range: Range {
+                start: Position {
+                    line: start_line as u32,
+                    character: 0,
+                },
+                end: position::line_end(text, end_line, encoding),
+            },
+            new_text: formatted,
+        }]))
+    }
+
+    async fn selection_range(
+        &self,
+        params: SelectionRangeParams,
+    ) -> Result<Option<Vec<SelectionRange>>> {
+        let delimiters = self.delimiters();
+        let encoding = self.encoding();
+        let docs = self.documents.lock().unwrap();
+        let text = match docs.get(&params.text_document.uri) {
+            Some(text) => text,
+            None => return Ok(None),
+        };
+        let ranges = params
+            .positions
+            .into_iter()
+            .map(|position| {
+                let offset =
+                    position::position_to_offset(text, position, encoding).unwrap_or(text.len());
+                selection::selection_range(text, offset, &delimiters, encoding)
+            })
+            .collect();
+        Ok(Some(ranges))
+    }
+
+    async fn goto_definition(
+        &self,
+        params: GotoDefinitionParams,
+    ) -> Result<Option<GotoDefinitionResponse>> {
+        let (link, _) = match self.link_at(&params.text_document_position_params) {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        Ok(self
+            .link_location(
+                &params.text_document_position_params.text_document.uri,
+                &link,
+            )?
+            .map(GotoDefinitionResponse::Scalar))
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let (link, link_range) = match self.link_at(&params.text_document_position_params) {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        let settings = self.settings.lock().unwrap().clone();
+        let to_hover = |value: String| Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(link_range),
+        };
+        // `[[#Heading]]` previews a section of the hovered document itself.
+        if link.path.is_empty() {
+            let uri = &params.text_document_position_params.text_document.uri;
+            return Ok(link.anchor.as_ref().and_then(|anchor| {
+                let text = self.document_text(uri)?;
+                hover::section_preview(&text, anchor, settings.hover_preview_lines).map(to_hover)
+            }));
+        }
+        let aliases = self.aliases.lock().unwrap().clone();
+        let index = self.link_index.clone();
+        let encoding = self.encoding();
+        let file = params
+            .text_document_position_params
+            .text_document
+            .uri
+            .to_file_path()
+            .unwrap_or_default();
+        let inner_result = tokio::task::spawn_blocking(move || {
+            let config = config::read_config().map_err(|e| e.to_string())?;
+            let vault_dir = Path::new(&config.vault_dir);
+            let pages = vault::with_aliases(vault::indexed_pages(vault_dir), &aliases);
+            let Some(target) = vault::resolve_note_link(vault_dir, &file, &link.path, &pages)
+            else {
+                return Ok(None);
+            };
+            if wikilink::is_image(&link.path) {
+                let Ok(bytes) = fs::read(&target) else {
+                    return Ok(None);
+                };
+                let name = target.file_name().unwrap_or_default().to_string_lossy();
+                return Ok(Some(hover::image_card(&name, &bytes)));
+            }
+            let Ok(content) = fs::read_to_string(&target) else {
+                return Ok(None);
+            };
+            // An embed shows the note itself, so its hover previews the embedded content.
+            if link.embed || settings.hover_full_preview {
+                return Ok(Some(hover::link_preview(
+                    &link,
+                    &content,
+                    settings.hover_preview_lines,
+                )));
+            }
+            let vpath = vault::virtual_path(vault_dir, &target).unwrap_or_default();
+            let backlinks = index.lock().unwrap().backlinks(&vpath, encoding).len();
+            let fallback_title = target
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned();
+            Ok::<_, String>(Some(hover::link_card(
+                &link,
+                &content,
+                &fallback_title,
+                backlinks,
+            )))
+        })
+        .await
+        .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let value = inner_result.map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        Ok(value.map(to_hover))
+    }
+
+    async fn document_color(&self, params: DocumentColorParams) -> Result<Vec<ColorInformation>> {
+        let encoding = self.encoding();
+        Ok(self
+            .documents
+            .lock()
+            .unwrap()
+            .get(&params.text_document.uri)
+            .map(|text| callout::callout_colors(text, encoding))
+            .unwrap_or_default())
+    }
+
+    async fn color_presentation(
+        &self,
+        params: ColorPresentationParams,
+    ) -> Result<Vec<ColorPresentation>> {
+        Ok(callout::color_presentations(params.color, params.range))
+    }
+
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        let text = match self
+            .documents
+            .lock()
+            .unwrap()
+            .get(&params.text_document.uri)
+        {
+            Some(text) => text.clone(),
+            None => return Ok(None),
+        };
+        let encoding = self.encoding();
+        let start = position::position_to_offset(&text, params.range.start, encoding).unwrap_or(0);
+        let end =
+            position::position_to_offset(&text, params.range.end, encoding).unwrap_or(text.len());
+        let config =
+            config::read_config().map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let vault_dir = Path::new(&config.vault_dir);
+        let hints = inlay_hint::title_hints(
+            &text,
+            start..end,
+            vault_dir,
+            &self.pages(vault_dir),
+            &self.delimiters(),
+            encoding,
+        );
+        Ok(Some(hints))
+    }
+
+    async fn linked_editing_range(
+        &self,
+        params: LinkedEditingRangeParams,
+    ) -> Result<Option<LinkedEditingRanges>> {
+        let position_params = params.text_document_position_params;
+        let encoding = self.encoding();
+        let docs = self.documents.lock().unwrap();
+        let Some(text) = docs.get(&position_params.text_document.uri) else {
+            return Ok(None);
+        };
+        let Some(offset) = position::position_to_offset(text, position_params.position, encoding)
+        else {
+            return Ok(None);
+        };
+        Ok(
+            markdown_link::linked_reference_labels(text, offset).map(|ranges| {
+                LinkedEditingRanges {
+                    ranges: ranges
+                        .iter()
+                        .map(|range| position::offset_range(text, range, encoding))
+                        .collect(),
+                    word_pattern: None,
+                }
+            }),
+        )
+    }
+
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Option<Vec<DocumentHighlight>>> {
+        let position_params = params.text_document_position_params;
+        let text = match self
+            .documents
+            .lock()
+            .unwrap()
+            .get(&position_params.text_document.uri)
+        {
+            Some(text) => text.clone(),
+            None => return Ok(None),
+        };
+        let encoding = self.encoding();
+        let offset = match position::position_to_offset(&text, position_params.position, encoding) {
+            Some(offset) => offset,
+            None => return Ok(None),
+        };
+        let config =
+            config::read_config().map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let vault_dir = Path::new(&config.vault_dir);
+        let highlights = highlight::link_highlights(
+            &text,
+            offset,
+            vault_dir,
+            &self.pages(vault_dir),
+            &self.delimiters(),
+            encoding,
+        );
+        Ok((!highlights.is_empty()).then_some(highlights))
+    }
+
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> Result<Option<SemanticTokensResult>> {
+        let delimiters = self.delimiters();
+        let encoding = self.encoding();
+        let docs = self.documents.lock().unwrap();
+        Ok(docs.get(&params.text_document.uri).map(|text| {
+            SemanticTokensResult::Tokens(SemanticTokens {
+                result_id: None,
+                data: semantic_tokens::semantic_tokens(text, &delimiters, encoding),
+            })
+        }))
+    }
+
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let uri = params.text_document_position.text_document.uri;
+        let index = self.link_index.clone();
+        let encoding = self.encoding();
+        let inner_result = tokio::task::spawn_blocking(move || {
+            let config = config::read_config().map_err(|e| e.to_string())?;
+            let vault_dir = Path::new(&config.vault_dir);
+            // Backlinks are found by the current note's virtual path.
+            let locations = uri
+                .to_file_path()
+                .ok()
+                .and_then(|path| vault::virtual_path(vault_dir, &path))
+                .map(|vpath| index.lock().unwrap().backlinks(&vpath, encoding))
+                .unwrap_or_default();
+            Ok::<_, String>(locations)
+        })
+        .await
+        .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let locations = inner_result.map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        Ok(Some(locations))
+    }
+
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        // Only wiki-links can be renamed; the range covers just the path.
+        let delimiters = self.delimiters();
+        let encoding = self.encoding();
+        let docs = self.documents.lock().unwrap();
+        let range = docs.get(&params.text_document.uri).and_then(|text| {
+            let offset = position::position_to_offset(text, params.position, encoding)?;
+            let link = wikilink::wikilink_at(text, offset, &delimiters)?;
+            Some(position::offset_range(text, &link.path_range, encoding))
+        });
+        Ok(range.map(PrepareRenameResponse::Range))
+    }
+
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+        let new_name = params.new_name;
+        let open_docs = self.documents.lock().unwrap().clone();
+        let delimiters = self.delimiters();
+        let encoding = self.encoding();
+        let link = open_docs.get(&uri).and_then(|text| {
+            let offset = position::position_to_offset(text, position, encoding)?;
+            wikilink::wikilink_at(text, offset, &delimiters)
+        });
+        let index = self.link_index.clone();
+        let inner_result = tokio::task::spawn_blocking(move || {
+            let config = config::read_config().map_err(|e| e.to_string())?;
+            let vault_dir = Path::new(&config.vault_dir);
+            // Rename the linked note, or the current note when not on a link.
+            let old_file = match link {
+                Some(link) => vault::resolve_link_target(vault_dir, &link.path),
+                None => uri.to_file_path().ok(),
+            };
+            Ok::<_, String>(old_file.and_then(|old_file| {
+                rename::rename_note(
+                    vault_dir,
+                    &old_file,
+                    &new_name,
+                    &index.lock().unwrap(),
+                    encoding,
+                )
+            }))
+        })
+        .await
+        .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        inner_result.map_err(|_| tower_lsp::jsonrpc::Error::internal_error())
+    }
+
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<Vec<SymbolInformation>>> {
+        let query = params.query;
+        let settings = self.settings.lock().unwrap().clone();
+        let encoding = self.encoding();
+        let mut work_done = progress::WorkDone::new(
+            self.client.clone(),
+            params.work_done_progress_params.work_done_token,
+        );
+        let inner_result = tokio::task::spawn_blocking(move || {
+            // Read configuration and get the vault directory.
+            let config = config::read_config().map_err(|e| e.to_string())?;
+            let vault_dir = Path::new(&config.vault_dir);
+            work_done.begin("Searching note symbols");
+            let all_symbols = collect_workspace_symbols(
+                vault_dir,
+                &settings.ignore_rules(vault_dir),
+                &settings,
+                encoding,
+                &mut |done, total| work_done.report(done, total),
+            );
+            work_done.end(None);
+            Ok::<_, String>(rank_workspace_symbols(
+                &query,
+                all_symbols,
+                settings.workspace_symbol_limit,
+            ))
+        })
+        .await
+        .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        let symbols = inner_result.map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+        Ok(Some(symbols))
+    }
+
+    async fn completion(
+        &self,
+        params: CompletionParams,
+    ) -> tower_lsp::jsonrpc::Result<Option<CompletionResponse>> {
+        // Retrieve document URI and cursor position.
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+        let encoding = self.encoding();
+        // A copy, so the documents stay unlocked while the vault is searched.
+        let Some(text) = self.documents.lock().unwrap().get(&uri).cloned() else {
+            return Ok(None);
+        };
+
+        // Check if the text up to the cursor ends with "[[".
+        let lines: Vec<&str> = text.lines().collect();
+        if position.line as usize >= lines.len() {
+            return Ok(None);
+        }
+        let line = lines[position.line as usize];
+        let col = match position::byte_column(line, position.character, encoding) {
+            Some(col) => col,
+            None => return Ok(None),
+        };
+        let prefix = &line[..col];
+
+        // Inside the frontmatter block, complete keys and tag values instead of links.
+        if let Some(context) =
+            completion::frontmatter_context(&text, position.line as usize, prefix)
+        {
+            let start = |column: usize| Position {
+                line: position.line,
+                character: position::column(line, column, encoding),
+            };
+            let items = match context {
+                completion::FrontmatterContext::Key { start: column } => {
+                    completion::frontmatter_key_completions(&text, start(column), position)
+                }
+                completion::FrontmatterContext::Tag { start: column } => {
+                    let config = config::read_config()
+                        .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+                    completion::tag_completions(
+                        Path::new(&config.vault_dir),
+                        &prefix[column..],
+                        start(column),
+                        position,
+                        encoding,
+                    )
+                }
+                completion::FrontmatterContext::Other => return Ok(None),
+            };
+            return Ok(Some(CompletionResponse::Array(items)));
+        }
+
+        // Links and tags typed inside code are literal text.
+        let in_code = position::position_to_offset(&text, position, encoding)
+            .is_some_and(|offset| markdown_context::is_in_code(&text, offset));
+        if in_code {
+            return Ok(None);
+        }
+
+        let delimiters = self.delimiters();
+        let link_start = match wikilink::open_link_start(prefix, &delimiters) {
+            Some(start) => start,
+            None => {
+                // Outside a link, the date trigger starts a date.
+                let (trigger, format) = {
+                    let settings = self.settings.lock().unwrap();
+                    (settings.date_trigger.clone(), settings.date_format.clone())
+                };
+                if let Some(date_start) = completion::date_query_start(prefix, &trigger) {
+                    let replace = Range {
+                        start: Position {
+                            line: position.line,
+                            character: position::column(line, date_start, encoding),
+                        },
+                        end: position,
+                    };
+                    let items =
+                        completion::date_items(date::days_today(), &format, &trigger, replace);
+                    return Ok(Some(CompletionResponse::Array(items)));
+                }
+                // Outside a link, a "#" at a word boundary starts a tag.
+                let tag_start = match tags::tag_query_start(prefix) {
+                    Some(start) => start,
+                    None => return Ok(None),
+                };
+                let config = config::read_config()
+                    .map_err(|_| tower_lsp::jsonrpc::Error::internal_error())?;
+                let start = Position {
+                    line: position.line,
+                    character: position::column(line, tag_start, encoding),
+                };
+                let items = completion::tag_completions(
+                    Path::new(&config.vault_dir),
+                    &prefix[tag_start..],
+                    start,
+                    position,
+                    encoding,
+                );
+                return Ok(Some(CompletionResponse::Array(items)));
+            }
+        };
+        let query = &prefix[link_start..];
+
+        // Get the vault directory from the config.
+        let config: Config = notemancy_core::config::read_config().map_err(|_e| {
+            tower_lsp::jsonrpc::Error::new(tower_lsp::jsonrpc::ErrorCode::InternalError)
+        })?;
+        let vault_dir = std::path::Path::new(&config.vault_dir);
+
+        // After "[[path#", offer the headings of the target note as anchors; after "[[#",
+        // those of this document.
+        if let Some(hash) = query.find('#') {
+            let target = match query[..hash].trim() {
+                "" => Some((uri.clone(), text.clone())),
+                path => {
+                    let pages = self.pages(vault_dir);
+                    let file = uri.to_file_path().unwrap_or_default();
+                    vault::resolve_note_link(vault_dir, &file, path, &pages).and_then(|target| {
+                        let target_uri = Url::from_file_path(target).ok()?;
+                        let content = self.document_text(&target_uri)?;
+                        Some((target_uri, content))
+                    })
+                }
+            };
+            let (target_uri, content) = match target {
+                Some(target) => target,
+                None => return Ok(None),
+            };
+            let anchor_start = Position {
+                line: position.line,
+                character: position::column(line, link_start + hash + 1, encoding),
+            };
+            let items = completion::heading_items(
+                &content,
+                &target_uri,
+                Range {
+                    start: anchor_start,
+                    end: position,
+                },
+            );
+            return Ok(Some(CompletionResponse::Array(items)));
+        }
+
+        // The text typed after "[[" is both the filter and the text a completion replaces.
+        let format = self.settings.lock().unwrap().wikilink_format;
+        let replace = Range {
+            start: Position {
+                line: position.line,
+                character: position::column(line, link_start, encoding),
+            },
+            end: position,
+        };
+        let note_item = |relative_vpath: String, title: String| {
+            completion::note_item(relative_vpath, title, format, &delimiters, replace)
+        };
+
+        let rules = self.settings.lock().unwrap().ignore_rules(vault_dir);
+        // Query the database for pages (notes).
+        let mut items = Vec::new();
+        let mut seen = HashSet::new();
+        let db = notemancy_core::db::crud::global();
+        if let Ok(mut stmt) = db.conn.prepare("SELECT vpath, title FROM pagetable") {
+            let rows = stmt.query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            });
+            if let Ok(rows) = rows {
+                for row in rows.flatten() {
+                    let (vpath, title) = row;
+                    // Strip the vault dir from the vpath.
+                    let vpath = vault::normalize_separators(&vpath);
+                    let relative_vpath = std::path::Path::new(&vpath)
+                        .strip_prefix(vault_dir)
+                        .map(|p| p.to_string_lossy().to_string())
+                        .unwrap_or(vpath.clone());
+                    if !seen.insert(relative_vpath.clone()) || rules.is_ignored(&relative_vpath) {
+                        continue;
+                    }
+                    items.push(note_item(relative_vpath, title));
+                }
+            }
+        }
+
+        // Notes created since the database was last indexed are only known to the
+        // watched-file index.
+        for file in self.file_index.lock().unwrap().iter() {
+            let Some(relative_vpath) = vault::virtual_path(vault_dir, file) else {
+                continue;
+            };
+            if seen.insert(relative_vpath.clone()) {
+                let title = file
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned();
+                items.push(note_item(relative_vpath, title));
+            }
+        }
+
+        // Each alias is a candidate of its own that still links to the note's path.
+        for (file, names) in self.aliases.lock().unwrap().iter() {
+            let Some(relative_vpath) = vault::virtual_path(vault_dir, file) else {
+                continue;
+            };
+            if rules.is_ignored(&relative_vpath) {
+                continue;
+            }
+            for name in names {
+                items.push(note_item(relative_vpath.clone(), name.clone()));
+            }
+        }
+
+        let limit = self.settings.lock().unwrap().completion_limit;
+        let mut list = limit_note_items(filter_note_items(items, query), query, limit);
+        let items = &mut list.items;
+        if self.settings.lock().unwrap().completion_recency {
+            let modified: Vec<_> = items
+                .iter()
+                .map(|item| {
+                    let vpath = item.detail.as_deref()?;
+                    fs::metadata(vault_dir.join(vpath)).ok()?.modified().ok()
+                })
+                .collect();
+            completion::order_by_recency(items, &modified);
+        }
+        Ok(Some(CompletionResponse::List(list)))
+    }
+}
+
+/// Parses markdown text and extracts headings as document symbols, with the symbol kind
+/// the settings map each heading level to.
+fn parse_markdown_symbols(
+    text: &str,
+    settings: &settings::Settings,
+    encoding: position::Encoding,
+) -> Vec<DocumentSymbol> {
+    let lines: Vec<&str> = text.lines().collect();
+    heading::parse_headings(text)
+        .into_iter()
+        .map(|heading| {
+            let start = Position {
+                line: heading.line as u32,
+                character: 0,
+            };
+            let end = Position {
+                line: heading.line as u32,
+                character: position::column(lines[heading.line], heading.line_len, encoding),
+            };
+            let range = Range { start, end };
+
+            #[allow(deprecated)]
+            DocumentSymbol {
+                name: heading.title,
+                detail: Some(format!("Heading level {}", heading.level)),
+                kind: settings.heading_symbol_kind(heading.level),
+                tags: None,
+                range,
+                selection_range: range,
+                children: None,
+                deprecated: None,
+            }
+        })
+        .collect()
+}
+
+/// Returns the outline of a document: its frontmatter keys grouped under a
+/// "Frontmatter" symbol, followed by its headings. Task items are nested under the
+/// heading above them, whose range grows to take them in; tasks above the first heading
+/// stay at the top level.
+fn outline_symbols(
+    text: &str,
+    settings: &settings::Settings,
+    encoding: position::Encoding,
+) -> Vec<DocumentSymbol> {
+    let mut symbols: Vec<DocumentSymbol> = frontmatter_symbol(text, encoding).into_iter().collect();
+    let mut headings = parse_markdown_symbols(text, settings, encoding);
+    let lines: Vec<&str> = text.lines().collect();
+    for task in task::find_tasks(text) {
+        let symbol = task_symbol(&task, lines[task.line], encoding);
+        let heading = headings
+            .iter_mut()
+            .rev()
+            .find(|heading| heading.range.start.line < task.line as u32);
+        match heading {
+            Some(heading) => {
+                heading.range.end = symbol.range.end;
+                heading.children.get_or_insert_with(Vec::new).push(symbol);
+            }
+            None => symbols.push(symbol),
+        }
+    }
+    symbols.extend(headings);
+    symbols
+}
+
+/// Builds the outline symbol of a task item, tagged deprecated once it is done so that
+/// clients strike it through.
+fn task_symbol(task: &task::Task, line: &str, encoding: position::Encoding) -> DocumentSymbol {
+    let range = Range {
+        start: Position {
+            line: task.line as u32,
+            character: 0,
+        },
+        end: Position {
+            line: task.line as u32,
+            character: position::column(line, task.line_len, encoding),
+        },
+    };
+    #[allow(deprecated)]
+    DocumentSymbol {
+        name: task.text.clone(),
+        detail: Some(if task.done { "Done" } else { "To do" }.to_string()),
+        kind: SymbolKind::BOOLEAN,
+        tags: task.done.then(|| vec![SymbolTag::DEPRECATED]),
+        range,
+        selection_range: range,
+        children: None,
+        deprecated: None,
+    }
+}
+
+/// Builds a "Frontmatter" symbol whose children are the top-level frontmatter keys.
+fn frontmatter_symbol(text: &str, encoding: position::Encoding) -> Option<DocumentSymbol> {
+    let block = frontmatter::frontmatter_range(text)?;
+    let block_end = text[..block.end].trim_end_matches(['\r', '\n']).len();
+    let block_range = position::offset_range(text, &(0..block_end), encoding);
+    let line_range = |line: usize| Range {
+        start: Position {
+            line: line as u32,
+            character: 0,
+        },
+        end: position::line_end(text, line, encoding),
+    };
+    #[allow(deprecated)]
+    let children = frontmatter::fields(text)
+        .into_iter()
+        .map(|field| DocumentSymbol {
+            name: field.key,
+            detail: (!field.value.is_empty()).then_some(field.value),
+            kind: SymbolKind::FIELD,
+            tags: None,
+            range: line_range(field.line),
+            selection_range: line_range(field.line),
+            children: None,
+            deprecated: None,
+        })
+        .collect();
+    #[allow(deprecated)]
+    Some(DocumentSymbol {
+        name: "Frontmatter".to_string(),
+        detail: None,
+        kind: SymbolKind::OBJECT,
+        tags: None,
+        range: block_range,
+        selection_range: line_range(0),
+        children: Some(children),
+        deprecated: None,
+    })
+}
+
+/// Finds the block or heading of `content` that `link` points at. A link naming neither
+/// points at the note's title heading, its first level-one heading, when it has one.
+fn link_target_range(
+    content: &str,
+    link: &wikilink::WikiLink,
+    encoding: position::Encoding,
+) -> Option<Range> {
+    match (&link.block, &link.anchor) {
+        (Some(id), _) => markdown_context::find_block(content, id).map(|(start, end)| Range {
+            start: Position {
+                line: start as u32,
+                character: 0,
+            },
+            end: position::line_end(content, end, encoding),
+        }),
+        (None, Some(anchor)) => find_heading_range(content, anchor, encoding),
+        (None, None) => heading::parse_headings(content)
+            .iter()
+            .find(|heading| heading.level == 1)
+            .map(|heading| heading_range(content, heading, encoding)),
+    }
+}
+
+/// Finds the heading in `text` that `anchor` points at; see `heading::resolve_anchor`.
+fn find_heading_range(text: &str, anchor: &str, encoding: position::Encoding) -> Option<Range> {
+    let headings = heading::parse_headings(text);
+    heading::resolve_anchor(&headings, anchor).map(|heading| heading_range(text, heading, encoding))
+}
+
+/// The range of a heading's whole line in `text`.
+fn heading_range(text: &str, heading: &heading::Heading, encoding: position::Encoding) -> Range {
+    Range {
+        start: Position {
+            line: heading.line as u32,
+            character: 0,
+        },
+        end: position::line_end(text, heading.line, encoding),
+    }
+}
+
+/// Reads a markdown file, extracts headings and tags, and returns them as
+/// SymbolInformation. Each tag the note uses appears once, as `#tag` with kind KEY, at
+/// its first occurrence.
+fn extract_workspace_symbols_from_file(
+    file_path: &Path,
+    settings: &settings::Settings,
+    encoding: position::Encoding,
+) -> Vec<SymbolInformation> {
+    let mut symbols = Vec::new();
+    if let Ok(content) = fs::read_to_string(file_path) {
+        let mut doc_symbols = parse_markdown_symbols(&content, settings, encoding);
+        let mut tag_names = HashSet::new();
+        for tag in tags::first_tag_occurrences(&content) {
+            let range = position::offset_range(&content, &tag.range, encoding);
+            let mut names = vec![tag.name.as_str()];
+            if settings.tag_parent_symbols {
+                names.extend(tags::tag_ancestors(&tag.name));
+            }
+            for name in names {
+                if !tag_names.insert(name.to_string()) {
+                    continue;
+                }
+                #[allow(deprecated)]
+                doc_symbols.push(DocumentSymbol {
+                    name: format!("#{}", name),
+                    detail: None,
+                    kind: SymbolKind::KEY,
+                    tags: None,
+                    deprecated: None,
+                    range,
+                    selection_range: range,
+                    children: None,
+                });
+            }
+        }
+        // Name the note each symbol belongs to by its title, or by its file name.
+        let container_name = frontmatter::title(&content).unwrap_or_else(|| {
+            file_path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        });
+        if let Ok(uri) = Url::from_file_path(file_path) {
+            // The note itself comes first, so searching for its name finds it.
+            #[allow(deprecated)]
+            symbols.push(SymbolInformation {
+                name: container_name.clone(),
+                kind: SymbolKind::FILE,
+                location: Location {
+                    uri: uri.clone(),
+                    range: Range::default(),
+                },
+                container_name: None,
+                deprecated: None,
+                tags: None,
+            });
+            for ds in doc_symbols {
+                #[allow(deprecated)]
+                let sym_info = SymbolInformation {
+                    name: ds.name,
+                    kind: ds.kind,
+                    location: Location {
+                        uri: uri.clone(),
+                        range: ds.range,
+                    },
+                    container_name: Some(container_name.clone()),
+                    deprecated: ds.deprecated,
+                    tags: ds.tags,
+                };
+                symbols.push(sym_info);
+            }
+        }
+    }
+    symbols
+}
+
+/// Collects the workspace symbols of every note in the vault not matched by `rules`.
+/// `on_progress` is called with the number of notes read so far and the total.
+fn collect_workspace_symbols(
+    vault_dir: &Path,
+    rules: &ignore::IgnoreRules,
+    settings: &settings::Settings,
+    encoding: position::Encoding,
+    on_progress: &mut dyn FnMut(usize, usize),
+) -> Vec<SymbolInformation> {
+    let files = vault::collect_notes(vault_dir, rules);
+    let mut symbols = Vec::new();
+    for (index, file) in files.iter().enumerate() {
+        symbols.extend(extract_workspace_symbols_from_file(
+            file, settings, encoding,
+        ));
+        on_progress(index + 1, files.len());
+    }
+    symbols
+}
+
+/// Filters `symbols` by `query`, best matches first, and keeps at most `limit` of them.
+/// Matches are ranked by `FuzzyMatcher` score, then by whether the name starts with the
+/// query, then by name length; an empty query keeps the first `limit` symbols as found.
+fn rank_workspace_symbols(
+    query: &str,
+    symbols: Vec<SymbolInformation>,
+    limit: usize,
+) -> Vec<SymbolInformation> {
+    let query = query.trim();
+    let lowered = query.to_lowercase();
+    let matcher = FuzzyMatcher::new(query);
+    let mut matches: Vec<((usize, bool, usize), SymbolInformation)> = symbols
+        .into_iter()
+        .filter_map(|sym| {
+            let score = matcher.score(&sym.name)?;
+            let prefix = sym.name.to_lowercase().starts_with(&lowered);
+            Some(((score, !prefix, sym.name.len()), sym))
+        })
+        .collect();
+    if !query.is_empty() {
+        matches.sort_by_key(|(rank, _)| *rank);
+    }
+    // Deduplicate symbols by using a key composed of (name, file URI, start line).
+    let mut seen = HashSet::new();
+    matches
+        .into_iter()
+        .map(|(_, sym)| sym)
+        .filter(|sym| {
+            let key = (
+                sym.name.clone(),
+                sym.location.uri.to_string(),
+                sym.location.range.start.line,
+            );
+            seen.insert(key)
+        })
+        .take(limit)
+        .collect()
+}
+
+/// Keeps the note completions whose title or path fuzzy-matches `query`, the text typed
+/// so far after the `[[`.
+fn filter_note_items(items: Vec<CompletionItem>, query: &str) -> Vec<CompletionItem> {
+    let matcher = FuzzyMatcher::new(query);
+    items
+        .into_iter()
+        .filter(|item| {
+            matcher.score(&item.label).is_some()
+                || item
+                    .detail
+                    .as_deref()
+                    .is_some_and(|vpath| matcher.score(vpath).is_some())
+        })
+        .collect()
+}
+
+/// Keeps the `limit` note completions that match `query` best. When some are cut, the list
+/// is marked incomplete so the client asks again as the query narrows.
+fn limit_note_items(mut items: Vec<CompletionItem>, query: &str, limit: usize) -> CompletionList {
+    let matcher = FuzzyMatcher::new(query);
+    items.sort_by_cached_key(|item| {
+        let label = matcher.score(&item.label);
+        let vpath = item
+            .detail
+            .as_deref()
+            .and_then(|vpath| matcher.score(vpath));
+        label.into_iter().chain(vpath).min().unwrap_or(usize::MAX)
+    });
+    let is_incomplete = items.len() > limit;
+    items.truncate(limit);
+    CompletionList {
+        is_incomplete,
+        items,
+    }
+}
+
+/// A simple fuzzy matcher that gives a “gap” score when all query characters are found in
+/// order within the candidate (ignoring case). Lower scores indicate better matches. The
+/// query is lowercased once, so a request ranking many candidates builds one matcher and
+/// reuses it for every item.
+struct FuzzyMatcher {
+    /// The lowercased query; empty when the query is blank, which matches everything.
+    query: Vec<char>,
+}
+
+impl FuzzyMatcher {
+    fn new(query: &str) -> Self {
+        let query = if query.trim().is_empty() {
+            Vec::new()
+        } else {
+            query.to_lowercase().chars().collect()
+        };
+        FuzzyMatcher { query }
+    }
+
+    /// Scores `candidate` against the query, or `None` when it doesn't match.
+    fn score(&self, candidate: &str) -> Option<usize> {
+        let mut candidate = candidate.chars().flat_map(char::to_lowercase);
+        let mut total_gap = 0;
+        for &qc in &self.query {
+            loop {
+                match candidate.next() {
+                    Some(c) if c == qc => break,
+                    Some(_) => total_gap += 1,
+                    None => return None,
+                }
+            }
+        }
+        Some(total_gap)
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|arg| arg == "--fmt") {
+        // In formatting mode: read all from stdin, format the markdown, and output to stdout.
+        let mut input = String::new();
+        io::stdin()
+            .read_to_string(&mut input)
+            .expect("Failed to read from stdin");
+        let formatted = formatter::format_markdown(&input, &formatter::FormatOptions::default());
+        println!("{}", formatted);
+        return;
+    }
+
+    let (service, socket) = LspService::build(Backend::new).finish();
+
+    let stdin = tokio::io::stdin();
+    let stdout = tokio::io::stdout();
+    Server::new(stdin, stdout, socket).serve(service).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, DuplexStream};
+    use tower_lsp::lsp_types::Url;
+
+    fn test_backend() -> Backend {
+        let mut backend_holder: Option<Backend> = None;
+        let (_service, _socket) = LspService::build(|client| {
+            let backend = Backend::new(client);
+            backend_holder = Some(backend.clone());
+            backend
+        })
+        .finish();
+        backend_holder.expect("Backend was not captured")
+    }
 
     #[test]
     fn test_parse_markdown_symbols() {
@@ -350,75 +2871,721 @@ More text
 Even more text
 Not a heading
 "#;
-        let symbols = parse_markdown_symbols(text);
+        let symbols = parse_markdown_symbols(
+            text,
+            &settings::Settings::default(),
+            position::Encoding::default(),
+        );
         assert_eq!(symbols.len(), 3);
         assert_eq!(symbols[0].name, "Heading1");
         assert_eq!(symbols[1].name, "Heading2");
         assert_eq!(symbols[2].name, "Heading3");
     }
 
+    #[test]
+    fn test_heading_symbol_kinds_apply() {
+        let settings = settings::Settings::from_initialization_options(Some(serde_json::json!({
+            "headingSymbolKinds": { "1": "file", "2": "module" },
+        })))
+        .0;
+        let kinds = |text: &str| {
+            parse_markdown_symbols(text, &settings, position::Encoding::default())
+                .into_iter()
+                .map(|sym| sym.kind)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            kinds("# A\n## B\n### C\n"),
+            vec![SymbolKind::FILE, SymbolKind::MODULE, SymbolKind::NAMESPACE]
+        );
+
+        let dir = std::env::temp_dir().join("notemancy-lsp-heading-kinds-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("note.md");
+        fs::write(&file, "# A\n## B\n").unwrap();
+        let workspace_kinds: Vec<_> =
+            extract_workspace_symbols_from_file(&file, &settings, position::Encoding::default())
+                .into_iter()
+                .map(|sym| sym.kind)
+                .collect();
+        assert_eq!(
+            workspace_kinds,
+            vec![SymbolKind::FILE, SymbolKind::FILE, SymbolKind::MODULE]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_parse_markdown_symbols_setext() {
+        let text = "Title\n=====\n\nPart  \n---\nbody";
+        let symbols = parse_markdown_symbols(
+            text,
+            &settings::Settings::default(),
+            position::Encoding::default(),
+        );
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols[0].name, "Title");
+        assert_eq!(symbols[0].detail.as_deref(), Some("Heading level 1"));
+        assert_eq!(symbols[0].range.start, Position::new(0, 0));
+        assert_eq!(symbols[0].range.end, Position::new(0, 5));
+        assert_eq!(symbols[1].name, "Part");
+        assert_eq!(symbols[1].detail.as_deref(), Some("Heading level 2"));
+        assert_eq!(symbols[1].range.start.line, 3);
+    }
+
+    #[test]
+    fn test_same_file_anchor_target() {
+        let text =
+            "# Title\nSee [[#Section Two]] and [[#^para]].\n## Section Two\n\nA para ^para\n";
+        let links = wikilink::find_wikilinks(text, &wikilink::Delimiters::default());
+        assert_eq!(links[0].path, "");
+        let range = link_target_range(text, &links[0], position::Encoding::default()).unwrap();
+        assert_eq!((range.start.line, range.end.character), (2, 14));
+        let range = link_target_range(text, &links[1], position::Encoding::default()).unwrap();
+        assert_eq!(range.start.line, 4);
+        let missing =
+            &wikilink::find_wikilinks("[[#Nowhere]]", &wikilink::Delimiters::default())[0];
+        assert_eq!(
+            link_target_range(text, missing, position::Encoding::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_plain_link_targets_title_heading() {
+        let link = &wikilink::find_wikilinks("[[note]]", &wikilink::Delimiters::default())[0];
+        let content = "---\ntitle: Note\n---\nIntro.\n## Sub\n# Note\n";
+        let range = link_target_range(content, link, position::Encoding::default()).unwrap();
+        assert_eq!(range.start, Position::new(5, 0));
+        assert_eq!(range.end, Position::new(5, 6));
+        assert_eq!(
+            link_target_range(
+                "## Only a subheading\n",
+                link,
+                position::Encoding::default()
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_find_heading_range() {
+        let text = "# Title\nintro\n## Error  Handling\nbody";
+        let range =
+            find_heading_range(text, "error handling", position::Encoding::default()).unwrap();
+        assert_eq!(range.start.line, 2);
+        assert_eq!(range.end.character, 18);
+        assert!(find_heading_range(text, "Missing", position::Encoding::default()).is_none());
+    }
+
+    #[test]
+    fn test_outline_nests_tasks_under_headings() {
+        let text = "- [ ] before\n# Today\n- [x] coffee\n- [ ] code\n## Later\n\
+                    text\n  - [X] laundry\n";
+        let symbols = outline_symbols(
+            text,
+            &settings::Settings::default(),
+            position::Encoding::default(),
+        );
+        let names: Vec<_> = symbols.iter().map(|symbol| symbol.name.as_str()).collect();
+        assert_eq!(names, vec!["before", "Today", "Later"]);
+
+        let today = symbols[1].children.as_ref().unwrap();
+        let tasks: Vec<_> = today
+            .iter()
+            .map(|task| (task.name.as_str(), task.kind, task.tags.is_some()))
+            .collect();
+        assert_eq!(
+            tasks,
+            vec![
+                ("coffee", SymbolKind::BOOLEAN, true),
+                ("code", SymbolKind::BOOLEAN, false)
+            ]
+        );
+        assert_eq!(symbols[1].range.end.line, 3);
+        assert_eq!(symbols[1].selection_range.end.line, 1);
+
+        let later = symbols[2].children.as_ref().unwrap();
+        assert_eq!(later.len(), 1);
+        assert_eq!(later[0].tags, Some(vec![SymbolTag::DEPRECATED]));
+    }
+
+    #[test]
+    fn test_outline_symbols_groups_frontmatter() {
+        let text = "---\ntitle: Note\ntags: [a]\n---\n# Heading\n";
+        let symbols = outline_symbols(
+            text,
+            &settings::Settings::default(),
+            position::Encoding::default(),
+        );
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols[0].name, "Frontmatter");
+        assert_eq!(
+            symbols[0].range.end,
+            Position {
+                line: 3,
+                character: 3
+            }
+        );
+        let children = symbols[0].children.as_ref().unwrap();
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[1].name, "tags");
+        assert_eq!(children[1].kind, SymbolKind::FIELD);
+        assert_eq!(children[1].range.start.line, 2);
+        assert_eq!(symbols[1].name, "Heading");
+
+        // An unterminated block is not frontmatter.
+        assert_eq!(
+            outline_symbols(
+                "---\ntitle: x\n",
+                &settings::Settings::default(),
+                position::Encoding::default()
+            )
+            .len(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_document_symbol() {
+        let backend = test_backend();
+
+        let uri = Url::parse("file:///test.md").unwrap();
+        let content = "# Heading1\nSome text\n## Heading2".to_string();
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem {
+                    uri: uri.clone(),
+                    language_id: "markdown".to_string(),
+                    version: 1,
+                    text: content,
+                },
+            })
+            .await;
+
+        let doc_symbols = backend
+            .document_symbol(DocumentSymbolParams {
+                text_document: TextDocumentIdentifier { uri },
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .await
+            .unwrap();
+
+        if let Some(DocumentSymbolResponse::Nested(symbols)) = doc_symbols {
+            assert_eq!(symbols.len(), 2);
+            assert_eq!(symbols[0].name, "Heading1");
+            assert_eq!(symbols[1].name, "Heading2");
+        } else {
+            panic!("Expected nested document symbols");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_document_symbol_cache_refreshes_on_change() {
+        let backend = test_backend();
+        let uri = Url::parse("file:///cached.md").unwrap();
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem {
+                    uri: uri.clone(),
+                    language_id: "markdown".to_string(),
+                    version: 1,
+                    text: "# Before".to_string(),
+                },
+            })
+            .await;
+
+        let symbol_names = |response: Option<DocumentSymbolResponse>| match response {
+            Some(DocumentSymbolResponse::Nested(symbols)) => {
+                symbols.into_iter().map(|s| s.name).collect::<Vec<_>>()
+            }
+            _ => panic!("Expected nested document symbols"),
+        };
+        let params = || DocumentSymbolParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+
+        let first = backend.document_symbol(params()).await.unwrap();
+        assert_eq!(symbol_names(first), vec!["Before"]);
+        assert!(backend.symbol_cache.lock().unwrap().contains_key(&uri));
+
+        backend
+            .did_change(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier {
+                    uri: uri.clone(),
+                    version: 2,
+                },
+                content_changes: vec![TextDocumentContentChangeEvent {
+                    range: None,
+                    range_length: None,
+                    text: "# After\n## Nested".to_string(),
+                }],
+            })
+            .await;
+        assert!(!backend.symbol_cache.lock().unwrap().contains_key(&uri));
+
+        let second = backend.document_symbol(params()).await.unwrap();
+        assert_eq!(symbol_names(second), vec!["After", "Nested"]);
+    }
+
     #[tokio::test]
-    async fn test_document_symbol() {
-        let backend = {
-            let mut backend_holder: Option<Backend> = None;
-            let (_service, _socket) = LspService::build(|client| {
-                let backend = Backend {
-                    client,
-                    documents: Arc::new(Mutex::new(HashMap::new())),
-                };
-                backend_holder = Some(backend.clone());
-                backend
+    async fn test_shutdown_with_pending_diagnostics() {
+        let backend = test_backend();
+        backend.settings.lock().unwrap().diagnostics_delay_ms = 60_000;
+        let uri = Url::parse("file:///pending.md").unwrap();
+        backend
+            .did_change(DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier {
+                    uri: uri.clone(),
+                    version: 2,
+                },
+                content_changes: vec![TextDocumentContentChangeEvent {
+                    range: None,
+                    range_length: None,
+                    text: "[[missing]]".to_string(),
+                }],
+            })
+            .await;
+
+        let shutdown = tokio::time::timeout(Duration::from_secs(1), backend.shutdown()).await;
+        assert!(matches!(shutdown, Ok(Ok(()))));
+    }
+
+    /// Writes `message` to the server as a framed JSON-RPC message.
+    async fn send_message(writer: &mut DuplexStream, message: serde_json::Value) {
+        let body = message.to_string();
+        let framed = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+        writer.write_all(framed.as_bytes()).await.unwrap();
+    }
+
+    /// Reads the server's messages until one satisfies `wanted`, giving up once a
+    /// paused-clock second passes without one.
+    async fn read_until(
+        reader: &mut BufReader<DuplexStream>,
+        wanted: impl Fn(&serde_json::Value) -> bool,
+    ) -> Option<serde_json::Value> {
+        let read = async {
+            loop {
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).await.unwrap();
+                    match line.trim_end().strip_prefix("Content-Length: ") {
+                        Some(n) => length = n.parse().unwrap(),
+                        None if line.trim_end().is_empty() => break,
+                        None => {}
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).await.unwrap();
+                let message: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                if wanted(&message) {
+                    return message;
+                }
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(1), read)
+            .await
+            .ok()
+    }
+
+    /// The version of the next diagnostics the server publishes, if it publishes any.
+    async fn next_diagnostics_version(reader: &mut BufReader<DuplexStream>) -> Option<i64> {
+        let message = read_until(reader, |message| {
+            message["method"] == "textDocument/publishDiagnostics"
+        })
+        .await?;
+        message["params"]["version"].as_i64()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rapid_changes_publish_diagnostics_once() {
+        let (service, socket) = LspService::new(Backend::new);
+        let (mut to_server, server_in) = tokio::io::duplex(1 << 16);
+        let (server_out, from_server) = tokio::io::duplex(1 << 16);
+        tokio::spawn(Server::new(server_in, server_out, socket).serve(service));
+        let mut from_server = BufReader::new(from_server);
+
+        send_message(
+            &mut to_server,
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": {
+                    "capabilities": {},
+                    "initializationOptions": { "diagnosticsDelayMs": 300 },
+                },
+            }),
+        )
+        .await;
+        assert!(read_until(&mut from_server, |message| message["id"] == 1)
+            .await
+            .is_some());
+        let uri = "untitled:Rapid-1";
+        send_message(
+            &mut to_server,
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didOpen",
+                "params": { "textDocument": {
+                    "uri": uri, "languageId": "markdown", "version": 1, "text": "# A\n",
+                } },
+            }),
+        )
+        .await;
+        assert_eq!(next_diagnostics_version(&mut from_server).await, Some(1));
+
+        let change = |version: i32| {
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didChange",
+                "params": {
+                    "textDocument": { "uri": uri, "version": version },
+                    "contentChanges": [{ "text": "# A\n# A\n" }],
+                },
             })
-            .finish();
-            backend_holder.expect("Backend was not captured")
         };
+        for version in 2..=4 {
+            send_message(&mut to_server, change(version)).await;
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(next_diagnostics_version(&mut from_server).await, Some(4));
+        assert_eq!(next_diagnostics_version(&mut from_server).await, None);
+
+        // Reopening the document drops the check still waiting for the older edit.
+        send_message(&mut to_server, change(5)).await;
+        send_message(
+            &mut to_server,
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didOpen",
+                "params": { "textDocument": {
+                    "uri": uri, "languageId": "markdown", "version": 6, "text": "# B\n",
+                } },
+            }),
+        )
+        .await;
+        assert_eq!(next_diagnostics_version(&mut from_server).await, Some(6));
+        assert_eq!(next_diagnostics_version(&mut from_server).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_did_change_watched_files_updates_index() {
+        let backend = test_backend();
+        let uri = Url::parse("file:///vault/new-note.md").unwrap();
+        let event = |typ| DidChangeWatchedFilesParams {
+            changes: vec![FileEvent {
+                uri: uri.clone(),
+                typ,
+            }],
+        };
+        let path = uri.to_file_path().unwrap();
+
+        backend
+            .did_change_watched_files(event(FileChangeType::CREATED))
+            .await;
+        assert!(backend.file_index.lock().unwrap().contains(&path));
 
-        let uri = Url::parse("file:///test.md").unwrap();
-        let content = "# Heading1\nSome text\n## Heading2".to_string();
+        backend
+            .did_change_watched_files(event(FileChangeType::DELETED))
+            .await;
+        assert!(!backend.file_index.lock().unwrap().contains(&path));
+    }
+
+    #[tokio::test]
+    async fn test_formatting() {
+        let backend = test_backend();
+
+        let uri = Url::parse("file:///format.md").unwrap();
+        let content = "#Heading\nSome text   \n\n\n\nMore".to_string();
         backend
             .did_open(DidOpenTextDocumentParams {
                 text_document: TextDocumentItem {
                     uri: uri.clone(),
                     language_id: "markdown".to_string(),
                     version: 1,
-                    text: content,
+                    text: content.clone(),
                 },
             })
             .await;
 
-        let doc_symbols = backend
-            .document_symbol(DocumentSymbolParams {
+        let edits = backend
+            .formatting(DocumentFormattingParams {
                 text_document: TextDocumentIdentifier { uri },
+                options: FormattingOptions {
+                    tab_size: 4,
+                    insert_spaces: true,
+                    ..Default::default()
+                },
                 work_done_progress_params: Default::default(),
-                partial_result_params: Default::default(),
             })
             .await
-            .unwrap();
+            .unwrap()
+            .expect("Expected formatting edits");
 
-        if let Some(DocumentSymbolResponse::Nested(symbols)) = doc_symbols {
-            assert_eq!(symbols.len(), 2);
-            assert_eq!(symbols[0].name, "Heading1");
-            assert_eq!(symbols[1].name, "Heading2");
-        } else {
-            panic!("Expected nested document symbols");
-        }
+        assert_eq!(edits.len(), 1);
+        assert_eq!(
+            edits[0].new_text,
+            formatter::format_markdown(&content, &formatter::FormatOptions::default())
+        );
+        assert_eq!(
+            edits[0].range.end,
+            Position {
+                line: 5,
+                character: 4
+            }
+        );
     }
 
     #[tokio::test]
-    async fn test_workspace_symbol() {
-        let backend = {
-            let mut backend_holder: Option<Backend> = None;
-            let (_service, _socket) = LspService::build(|client| {
-                let backend = Backend {
-                    client,
-                    documents: Arc::new(Mutex::new(HashMap::new())),
-                };
-                backend_holder = Some(backend.clone());
-                backend
+    async fn test_formatting_without_final_newline() {
+        let backend = test_backend();
+        backend.settings.lock().unwrap().ensure_final_newline = false;
+        let uri = Url::parse("file:///no-newline.md").unwrap();
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem {
+                    uri: uri.clone(),
+                    language_id: "markdown".to_string(),
+                    version: 1,
+                    text: "#Heading\nMore".to_string(),
+                },
+            })
+            .await;
+
+        let edits = backend
+            .formatting(DocumentFormattingParams {
+                text_document: TextDocumentIdentifier { uri },
+                options: FormattingOptions {
+                    tab_size: 4,
+                    insert_spaces: true,
+                    ..Default::default()
+                },
+                work_done_progress_params: Default::default(),
+            })
+            .await
+            .unwrap()
+            .expect("Expected formatting edits");
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].new_text, "# Heading\n\nMore");
+    }
+
+    #[test]
+    fn test_extract_workspace_symbols_includes_tags() {
+        let dir = std::env::temp_dir().join("notemancy-lsp-tag-symbols-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("note.md");
+        fs::write(&file, "# Plan\nA #project note.\nStill #project.\n").unwrap();
+
+        let symbols = extract_workspace_symbols_from_file(
+            &file,
+            &settings::Settings::default(),
+            position::Encoding::default(),
+        );
+        let tags: Vec<_> = symbols
+            .iter()
+            .filter(|sym| sym.kind == SymbolKind::KEY)
+            .collect();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].name, "#project");
+        assert_eq!(tags[0].location.range.start, Position::new(1, 2));
+        assert_eq!(tags[0].location.range.end, Position::new(1, 10));
+
+        fs::write(&file, "Nested #area/home/garden and #area/work\n").unwrap();
+        let names = |settings: &settings::Settings| {
+            extract_workspace_symbols_from_file(&file, settings, position::Encoding::default())
+                .into_iter()
+                .map(|sym| sym.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(&settings::Settings::default()),
+            vec![
+                "note.md",
+                "#area/home/garden",
+                "#area",
+                "#area/home",
+                "#area/work"
+            ]
+        );
+        let settings = settings::Settings {
+            tag_parent_symbols: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            names(&settings),
+            vec!["note.md", "#area/home/garden", "#area/work"]
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_workspace_symbol_container_name() {
+        let dir = std::env::temp_dir().join("notemancy-lsp-container-name-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let titled = dir.join("titled.md");
+        fs::write(&titled, "---\ntitle: 'Trip Plan'\n---\n# Packing\n").unwrap();
+        let untitled = dir.join("untitled.md");
+        fs::write(&untitled, "# Packing\n").unwrap();
+
+        let container = |file: &Path| {
+            extract_workspace_symbols_from_file(
+                file,
+                &settings::Settings::default(),
+                position::Encoding::default(),
+            )
+            .into_iter()
+            .find(|sym| sym.name == "Packing")
+            .and_then(|sym| sym.container_name)
+        };
+        assert_eq!(container(&titled).as_deref(), Some("Trip Plan"));
+        assert_eq!(container(&untitled).as_deref(), Some("untitled.md"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_workspace_symbols_include_notes() {
+        let dir = std::env::temp_dir().join("notemancy-lsp-note-symbols-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("groceries.md"), "# Monday\n").unwrap();
+        fs::write(dir.join("trip.md"), "---\ntitle: Road Trip\n---\n# Route\n").unwrap();
+
+        let symbols = collect_workspace_symbols(
+            &dir,
+            &ignore::IgnoreRules::default(),
+            &settings::Settings::default(),
+            position::Encoding::default(),
+            &mut |_, _| {},
+        );
+        let found = rank_workspace_symbols("grocer", symbols.clone(), 200);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name, "groceries.md");
+        assert_eq!(found[0].kind, SymbolKind::FILE);
+        assert_eq!(found[0].location.range, Range::default());
+        let found = rank_workspace_symbols("road", symbols, 200);
+        assert_eq!(found[0].name, "Road Trip");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_aliases_follow_open_documents() {
+        let backend = test_backend();
+        let uri = Url::parse("file:///vault/ada.md").unwrap();
+        let path = uri.to_file_path().unwrap();
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem {
+                    uri: uri.clone(),
+                    language_id: "markdown".to_string(),
+                    version: 1,
+                    text: "---\naliases: [Countess, \"AL\"]\n---\n".to_string(),
+                },
             })
-            .finish();
-            backend_holder.expect("Backend was not captured")
+            .await;
+        assert_eq!(
+            backend.aliases.lock().unwrap().get(&path),
+            Some(&vec!["Countess".to_string(), "AL".to_string()])
+        );
+
+        let change = |text: &str| DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier {
+                uri: uri.clone(),
+                version: 2,
+            },
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: text.to_string(),
+            }],
         };
+        backend
+            .did_change(change("---\naliases:\n  - Enchantress\n---\n"))
+            .await;
+        assert_eq!(
+            backend.aliases.lock().unwrap().get(&path),
+            Some(&vec!["Enchantress".to_string()])
+        );
+        backend.did_change(change("No frontmatter")).await;
+        assert!(!backend.aliases.lock().unwrap().contains_key(&path));
+    }
+
+    #[tokio::test]
+    async fn test_did_close_indexes_saved_text() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-did-close-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(&vault_dir).unwrap();
+        let path = vault_dir.join("note.md");
+        fs::write(&path, "---\naliases: [Saved]\n---\n[[saved]]\n").unwrap();
+        let uri = Url::from_file_path(&path).unwrap();
+        let backend = test_backend();
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem {
+                    uri: uri.clone(),
+                    language_id: "markdown".to_string(),
+                    version: 1,
+                    text: "---\naliases: [Unsaved]\n---\n[[unsaved]]\n".to_string(),
+                },
+            })
+            .await;
+        assert_eq!(
+            backend.aliases.lock().unwrap().get(&path),
+            Some(&vec!["Unsaved".to_string()])
+        );
+        // Saving elsewhere leaves the open document's aliases alone.
+        backend
+            .did_change_watched_files(DidChangeWatchedFilesParams {
+                changes: vec![FileEvent {
+                    uri: uri.clone(),
+                    typ: FileChangeType::CHANGED,
+                }],
+            })
+            .await;
+        assert_eq!(
+            backend.aliases.lock().unwrap().get(&path),
+            Some(&vec!["Unsaved".to_string()])
+        );
+
+        backend
+            .did_close(DidCloseTextDocumentParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+            })
+            .await;
+        assert!(backend.documents.lock().unwrap().is_empty());
+        assert_eq!(
+            backend.aliases.lock().unwrap().get(&path),
+            Some(&vec!["Saved".to_string()])
+        );
+        assert_eq!(
+            backend.document_text(&uri).as_deref(),
+            Some("---\naliases: [Saved]\n---\n[[saved]]\n")
+        );
+        let indexed: Vec<_> = backend
+            .link_index
+            .lock()
+            .unwrap()
+            .contents()
+            .map(|(_, _, text)| text.to_string())
+            .collect();
+        assert_eq!(indexed, vec!["---\naliases: [Saved]\n---\n[[saved]]\n"]);
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+
+    #[tokio::test]
+    async fn test_workspace_symbol() {
+        let backend = test_backend();
 
         let params = WorkspaceSymbolParams {
             query: "Head".to_string(),
@@ -428,30 +3595,375 @@ Not a heading
         let response = backend.symbol(params).await.unwrap();
         assert!(response.is_some());
     }
-}
 
-/// Recursively collects markdown files from `dir`, deduplicating based on their canonical path.
-fn collect_markdown_files(dir: &Path) -> Vec<PathBuf> {
-    let mut files = Vec::new();
-    let mut seen = HashSet::new();
-    if let Ok(entries) = fs::read_dir(dir) {
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.is_dir() {
-                files.extend(collect_markdown_files(&path));
-            } else if let Some(ext) = path.extension() {
-                if ext.to_string_lossy().eq_ignore_ascii_case("md") {
-                    // Canonicalize to resolve symlinks.
-                    if let Ok(canonical) = fs::canonicalize(&path) {
-                        if seen.insert(canonical) {
-                            files.push(path);
-                        }
-                    } else {
-                        files.push(path);
-                    }
-                }
+    #[test]
+    fn test_filter_note_items_by_title_substring() {
+        let range = Range::new(Position::new(0, 2), Position::new(0, 5));
+        let item = |vpath: &str, title: &str| {
+            completion::note_item(
+                vpath.to_string(),
+                title.to_string(),
+                settings::WikilinkFormat::Path,
+                &wikilink::Delimiters::default(),
+                range,
+            )
+        };
+        let items = vec![
+            item("people/ada.md", "Ada Lovelace"),
+            item("people/grace.md", "Grace Hopper"),
+            item("glove.md", "Winter gear"),
+        ];
+        let filtered = filter_note_items(items, "lov");
+        let labels: Vec<_> = filtered.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels, vec!["Ada Lovelace", "Winter gear"]);
+        assert_eq!(
+            filtered[0].filter_text.as_deref(),
+            Some("Ada Lovelace people/ada.md")
+        );
+    }
+
+    #[test]
+    #[ignore = "timing benchmark; run with --ignored"]
+    fn bench_fuzzy_matcher_reuse() {
+        let candidates: Vec<String> = (0..5000)
+            .map(|i| format!("Projects/Area {}/Meeting Notes {}.md", i % 37, i))
+            .collect();
+        let query = "area meet 12";
+        let time = |rank: &dyn Fn() -> Vec<Option<usize>>| {
+            (0..5)
+                .map(|_| {
+                    let start = std::time::Instant::now();
+                    std::hint::black_box(rank());
+                    start.elapsed()
+                })
+                .min()
+                .unwrap()
+        };
+        let per_item = || {
+            candidates
+                .iter()
+                .map(|candidate| FuzzyMatcher::new(query).score(candidate))
+                .collect()
+        };
+        let reused = || {
+            let matcher = FuzzyMatcher::new(query);
+            candidates
+                .iter()
+                .map(|candidate| matcher.score(candidate))
+                .collect()
+        };
+        assert_eq!(per_item(), reused());
+        let (per_item, reused) = (time(&per_item), time(&reused));
+        assert!(
+            reused < per_item,
+            "reused {:?}, per item {:?}",
+            reused,
+            per_item
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_matcher() {
+        let matcher = FuzzyMatcher::new("MtN");
+        assert_eq!(matcher.score("meeting notes"), Some(3));
+        assert_eq!(matcher.score("Notes"), None);
+        assert_eq!(FuzzyMatcher::new("  ").score("anything"), Some(0));
+    }
+
+    #[test]
+    fn test_limit_note_items() {
+        let range = Range::new(Position::new(0, 2), Position::new(0, 2));
+        let items: Vec<_> = ["notes/alpha.md", "notes/beta.md", "a.md"]
+            .iter()
+            .map(|vpath| {
+                completion::note_item(
+                    vpath.to_string(),
+                    vpath.trim_end_matches(".md").to_string(),
+                    settings::WikilinkFormat::Path,
+                    &wikilink::Delimiters::default(),
+                    range,
+                )
+            })
+            .collect();
+        let list = limit_note_items(items.clone(), "a", 2);
+        assert!(list.is_incomplete);
+        let labels: Vec<_> = list.items.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels, vec!["a", "notes/alpha"]);
+        assert!(!limit_note_items(items, "a", 3).is_incomplete);
+    }
+
+    #[test]
+    fn test_filter_note_items_by_partial_link() {
+        let prefix = "See [[dai";
+        let start = wikilink::open_link_start(prefix, &wikilink::Delimiters::default()).unwrap();
+        let range = Range::new(Position::new(0, start as u32), Position::new(0, 9));
+        let item = |vpath: &str, title: &str| {
+            completion::note_item(
+                vpath.to_string(),
+                title.to_string(),
+                settings::WikilinkFormat::Path,
+                &wikilink::Delimiters::default(),
+                range,
+            )
+        };
+        let items = vec![
+            item("journal/daily.md", "Daily log"),
+            item("notes/rust.md", "Rust"),
+            item("dairy/cheese.md", "Cheese"),
+            item("notes/diary.md", "Diary"),
+        ];
+        let filtered = filter_note_items(items, &prefix[start..]);
+        let labels: Vec<_> = filtered.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels, vec!["Daily log", "Cheese"]);
+        match &filtered[0].text_edit {
+            Some(CompletionTextEdit::Edit(edit)) => {
+                assert_eq!(edit.range.start, Position::new(0, 6));
+                assert_eq!(edit.range.end, Position::new(0, 9));
+            }
+            _ => panic!("expected a text edit"),
+        }
+    }
+
+    #[test]
+    fn test_rank_workspace_symbols() {
+        let uri = Url::parse("file:///vault/note.md").unwrap();
+        let symbol = |name: &str, line: u32| {
+            #[allow(deprecated)]
+            SymbolInformation {
+                name: name.to_string(),
+                kind: SymbolKind::NAMESPACE,
+                location: Location {
+                    uri: uri.clone(),
+                    range: Range {
+                        start: Position { line, character: 0 },
+                        end: Position { line, character: 0 },
+                    },
+                },
+                container_name: None,
+                deprecated: None,
+                tags: None,
             }
+        };
+        let symbols = vec![
+            symbol("Project ideas", 0),
+            symbol("Old reports", 1),
+            symbol("Reports", 2),
+            symbol("Report archive", 3),
+            symbol("Unrelated", 4),
+        ];
+        let names = |ranked: Vec<SymbolInformation>| {
+            ranked.into_iter().map(|sym| sym.name).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(rank_workspace_symbols("report", symbols.clone(), 200)),
+            vec!["Reports", "Report archive", "Old reports"]
+        );
+        assert_eq!(
+            names(rank_workspace_symbols("report", symbols.clone(), 1)),
+            vec!["Reports"]
+        );
+        assert_eq!(
+            names(rank_workspace_symbols("  ", symbols, 2)),
+            vec!["Project ideas", "Old reports"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_link_at_crlf_document() {
+        let backend = test_backend();
+        let uri = Url::parse("file:///tmp/crlf.md").unwrap();
+        backend.documents.lock().unwrap().insert(
+            uri.clone(),
+            "# Title\r\nIntro\r\nSee [[notes/target | alias]] here\r\n".to_string(),
+        );
+        let params = TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri },
+            position: Position::new(2, 8),
+        };
+        let (link, range) = backend.link_at(&params).expect("link under the cursor");
+        assert_eq!(link.path, "notes/target");
+        assert_eq!(range.start, Position::new(2, 4));
+        assert_eq!(range.end, Position::new(2, 28));
+    }
+
+    #[tokio::test]
+    async fn test_position_encoding_is_per_server() {
+        let utf8 = test_backend();
+        let result = utf8
+            .initialize(InitializeParams {
+                capabilities: ClientCapabilities {
+                    general: Some(GeneralClientCapabilities {
+                        position_encodings: Some(vec![PositionEncodingKind::UTF8]),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            result.capabilities.position_encoding,
+            Some(PositionEncodingKind::UTF8)
+        );
+        let utf16 = test_backend();
+
+        let uri = Url::parse("file:///tmp/encoding.md").unwrap();
+        let text = "Café [[note]]\n";
+        for backend in [&utf8, &utf16] {
+            backend
+                .documents
+                .lock()
+                .unwrap()
+                .insert(uri.clone(), text.to_string());
+        }
+        let link_start = |backend: &Backend, character| {
+            let params = TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+                position: Position::new(0, character),
+            };
+            backend.link_at(&params).map(|(_, range)| range.start)
+        };
+        // "é" is two UTF-8 bytes but one UTF-16 unit.
+        assert_eq!(link_start(&utf8, 8), Some(Position::new(0, 6)));
+        assert_eq!(link_start(&utf16, 8), Some(Position::new(0, 5)));
+    }
+
+    #[tokio::test]
+    async fn test_custom_delimiters_in_hover_and_definition() {
+        let backend = test_backend();
+        {
+            let mut settings = backend.settings.lock().unwrap();
+            settings.link_open_delimiter = "((".to_string();
+            settings.link_close_delimiter = "))".to_string();
+        }
+        let uri = Url::parse("untitled:Delimiters-1").unwrap();
+        let text = "# Setup\nInstall it.\n\nSee ((#Setup)) or [[#Setup]].\n";
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem {
+                    uri: uri.clone(),
+                    language_id: "markdown".to_string(),
+                    version: 1,
+                    text: text.to_string(),
+                },
+            })
+            .await;
+        let at = |character| TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            position: Position::new(3, character),
+        };
+
+        let definition = backend
+            .goto_definition(GotoDefinitionParams {
+                text_document_position_params: at(7),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+            .await
+            .unwrap();
+        let Some(GotoDefinitionResponse::Scalar(location)) = definition else {
+            panic!("expected the heading as the definition");
+        };
+        assert_eq!(location.range.start.line, 0);
+
+        let hover = |character| HoverParams {
+            text_document_position_params: at(character),
+            work_done_progress_params: Default::default(),
+        };
+        let Some(Hover {
+            contents: HoverContents::Markup(markup),
+            ..
+        }) = backend.hover(hover(7)).await.unwrap()
+        else {
+            panic!("expected a preview of the section");
+        };
+        assert!(markup.value.contains("Install it."));
+        // With other delimiters configured, `[[...]]` is plain text.
+        assert!(backend.hover(hover(22)).await.unwrap().is_none());
+
+        // Another server keeps the default delimiters.
+        let other = test_backend();
+        other
+            .documents
+            .lock()
+            .unwrap()
+            .insert(uri.clone(), text.to_string());
+        assert_eq!(
+            other.link_at(&at(22)).unwrap().0.anchor.as_deref(),
+            Some("Setup")
+        );
+        assert!(other.link_at(&at(7)).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_link_at_in_memory_and_on_disk() {
+        let backend = test_backend();
+        let untitled = Url::parse("untitled:Untitled-1").unwrap();
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem {
+                    uri: untitled.clone(),
+                    language_id: "markdown".to_string(),
+                    version: 1,
+                    text: "Draft with [[ideas]]".to_string(),
+                },
+            })
+            .await;
+        let params = |uri: &Url, position| TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            position,
+        };
+        let (link, _) = backend
+            .link_at(&params(&untitled, Position::new(0, 14)))
+            .expect("link in the unsaved buffer");
+        assert_eq!(link.path, "ideas");
+
+        let dir = std::env::temp_dir().join("notemancy-lsp-link-at-disk-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("closed.md");
+        fs::write(&file, "Saved [[notes/other]]\n").unwrap();
+        let closed = Url::from_file_path(&file).unwrap();
+        let (link, _) = backend
+            .link_at(&params(&closed, Position::new(0, 9)))
+            .expect("link in the note on disk");
+        assert_eq!(link.path, "notes/other");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_workspace_symbols_skip_ignored_notes() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-ignore-symbols-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(vault_dir.join("archive")).unwrap();
+        fs::create_dir_all(vault_dir.join("templates")).unwrap();
+        fs::write(vault_dir.join("kept.md"), "# Kept\n").unwrap();
+        fs::write(vault_dir.join("archive/old.md"), "# Old\n").unwrap();
+        fs::write(vault_dir.join("templates/daily.md"), "# Daily\n").unwrap();
+        fs::write(
+            vault_dir.join(".notemancyignore"),
+            "# comment\ntemplates/\n",
+        )
+        .unwrap();
+
+        let rules = settings::Settings {
+            ignore: vec!["archive".to_string()],
+            ..Default::default()
         }
+        .ignore_rules(&vault_dir);
+        let names: Vec<_> = collect_workspace_symbols(
+            &vault_dir,
+            &rules,
+            &settings::Settings::default(),
+            position::Encoding::default(),
+            &mut |_, _| {},
+        )
+        .into_iter()
+        .map(|sym| sym.name)
+        .collect();
+        assert_eq!(names, vec!["kept.md", "Kept"]);
+
+        let _ = fs::remove_dir_all(&vault_dir);
     }
-    files
 }
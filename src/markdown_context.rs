@@ -0,0 +1,194 @@
+use std::ops::Range;
+
+/// If `line` opens or closes a fenced code block (three or more backticks or tildes),
+/// returns the fence character and its length.
+fn fence_marker(line: &str) -> Option<(char, usize)> {
+    let trimmed = line.trim_start();
+    let fence_char = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = trimmed.chars().take_while(|c| *c == fence_char).count();
+    (len >= 3).then_some((fence_char, len))
+}
+
+/// Tracks whether a line-by-line walk of a document is inside a fenced code block.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FenceState {
+    open: Option<(char, usize)>,
+}
+
+impl FenceState {
+    /// Returns true while inside a fenced code block.
+    pub fn is_open(&self) -> bool {
+        self.open.is_some()
+    }
+
+    /// Advances past `line`, returning true if it is a fence line or lies inside a block.
+    pub fn update(&mut self, line: &str) -> bool {
+        match (self.open, fence_marker(line)) {
+            (None, Some(marker)) => {
+                self.open = Some(marker);
+                true
+            }
+            (None, None) => false,
+            (Some((open_char, open_len)), Some((fence_char, len))) => {
+                // A closing fence uses the same character, is at least as long,
+                // and carries no info string.
+                let rest = &line.trim_start()[len..];
+                if fence_char == open_char && len >= open_len && rest.trim().is_empty() {
+                    self.open = None;
+                }
+                true
+            }
+            (Some(_), None) => true,
+        }
+    }
+}
+
+/// Returns the byte ranges of all fenced code blocks in `text`, fences included.
+/// An unterminated fence runs to the end of the document.
+pub fn fenced_code_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut fence = FenceState::default();
+    let mut start = 0;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let was_open = fence.is_open();
+        fence.update(line);
+        if !was_open && fence.is_open() {
+            start = offset;
+        } else if was_open && !fence.is_open() {
+            ranges.push(start..offset + line.len());
+        }
+        offset += line.len();
+    }
+    if fence.is_open() {
+        ranges.push(start..text.len());
+    }
+    ranges
+}
+
+/// Returns the byte ranges of the inline code spans in `text`, backticks included. A span
+/// opened by a run of backticks closes at the next run of the same length within the
+/// same paragraph, before any fenced code block; a run left unclosed is literal text.
+pub fn inline_code_ranges(text: &str) -> Vec<Range<usize>> {
+    let fences = fenced_code_ranges(text);
+    let bytes = text.as_bytes();
+    let run_len = |start: usize| bytes[start..].iter().take_while(|b| **b == b'`').count();
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if let Some(fence) = fences.iter().find(|fence| fence.contains(&i)) {
+            i = fence.end;
+            continue;
+        }
+        if bytes[i] != b'`' {
+            i += 1;
+            continue;
+        }
+        let open_len = run_len(i);
+        let paragraph_end = paragraph_end(text, i);
+        let mut j = i + open_len;
+        let mut close = None;
+        while j < paragraph_end && !fences.iter().any(|fence| fence.contains(&j)) {
+            if bytes[j] == b'`' {
+                let len = run_len(j);
+                if len == open_len {
+                    close = Some(j + len);
+                    break;
+                }
+                j += len;
+            } else {
+                j += 1;
+            }
+        }
+        match close {
+            Some(end) => {
+                ranges.push(i..end);
+                i = end;
+            }
+            None => i += open_len,
+        }
+    }
+    ranges
+}
+
+/// Returns the offset where the paragraph containing `offset` ends: at the next blank
+/// line, or the end of the text.
+fn paragraph_end(text: &str, offset: usize) -> usize {
+    let mut line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    for line in text[line_start..].split_inclusive('\n') {
+        if line.trim().is_empty() && line_start > offset {
+            return line_start;
+        }
+        line_start += line.len();
+    }
+    text.len()
+}
+
+/// Returns the byte ranges of all code in `text`, fenced blocks and inline spans alike,
+/// in document order.
+pub fn code_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = fenced_code_ranges(text);
+    ranges.extend(inline_code_ranges(text));
+    ranges.sort_by_key(|range| range.start);
+    ranges
+}
+
+/// Whether `offset` lies inside a fenced code block or an inline code span of `text`,
+/// where links and tags are literal text.
+pub fn is_in_code(text: &str, offset: usize) -> bool {
+    code_ranges(text).iter().any(|code| code.contains(&offset))
+}
+
+/// Finds the paragraph marked with a trailing `^id` block identifier and returns its
+/// first and last line numbers.
+pub fn find_block(text: &str, id: &str) -> Option<(usize, usize)> {
+    let marker = format!("^{}", id);
+    let lines: Vec<&str> = text.lines().collect();
+    let end = lines.iter().position(|line| {
+        let line = line.trim_end();
+        match line.strip_suffix(&marker) {
+            Some(rest) => rest.is_empty() || rest.ends_with(char::is_whitespace),
+            None => false,
+        }
+    })?;
+    let start = lines[..end]
+        .iter()
+        .rposition(|line| line.trim().is_empty())
+        .map_or(0, |blank| blank + 1);
+    Some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fenced_code_ranges() {
+        let text = "intro\n```rust\n[[inside]]\n```\nafter\n~~~\nopen";
+        let ranges = fenced_code_ranges(text);
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(&text[ranges[0].clone()], "```rust\n[[inside]]\n```\n");
+        assert_eq!(&text[ranges[1].clone()], "~~~\nopen");
+    }
+
+    #[test]
+    fn test_inline_code_ranges() {
+        let text = "Use `[[not a link]]` or ``a ` b`` here.\nAn ` unclosed tick\n\n`split\n\nacross`\n```\n`fenced`\n```\n";
+        let spans: Vec<_> = inline_code_ranges(text)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect();
+        assert_eq!(spans, vec!["`[[not a link]]`", "``a ` b``"]);
+        assert!(is_in_code(text, text.find("[[").unwrap()));
+        assert!(!is_in_code(text, text.find("here").unwrap()));
+        assert!(is_in_code(text, text.find("fenced").unwrap()));
+    }
+
+    #[test]
+    fn test_find_block() {
+        let text = "# Title\n\nFirst line\nsecond line ^abc\n\nOther ^abcd\n";
+        assert_eq!(find_block(text, "abc"), Some((2, 3)));
+        assert_eq!(find_block(text, "abcd"), Some((5, 5)));
+        assert_eq!(find_block(text, "missing"), None);
+    }
+}
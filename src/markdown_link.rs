@@ -0,0 +1,193 @@
+use std::ops::Range;
+
+use crate::markdown_context;
+
+/// An inline `[text](target)` markdown link. Ranges are byte offsets into the document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MarkdownLink {
+    /// The whole link, from `[` to `)`.
+    pub range: Range<usize>,
+    pub text: String,
+    pub target: String,
+}
+
+/// Finds every inline markdown link in `text`. Images (`![alt](src)`), wiki-links and
+/// links written inside code are not markdown links.
+pub fn find_markdown_links(text: &str) -> Vec<MarkdownLink> {
+    let code_ranges = markdown_context::code_ranges(text);
+    let mut links = Vec::new();
+    let bytes = text.as_bytes();
+    let mut pos = 0;
+    while let Some(found) = text[pos..].find('[') {
+        let start = pos + found;
+        pos = start + 1;
+        if start > 0 && matches!(bytes[start - 1], b'!' | b'[')
+            || bytes.get(start + 1) == Some(&b'[')
+        {
+            continue;
+        }
+        let Some(text_len) = text[start + 1..].find([']', '[', '\n']) else {
+            break;
+        };
+        let text_end = start + 1 + text_len;
+        if bytes[text_end] != b']' || bytes.get(text_end + 1) != Some(&b'(') {
+            continue;
+        }
+        let target_start = text_end + 2;
+        let Some(target_len) = text[target_start..].find([')', '\n']) else {
+            break;
+        };
+        let target_end = target_start + target_len;
+        if bytes[target_end] != b')' {
+            continue;
+        }
+        if !code_ranges.iter().any(|code| code.contains(&start)) {
+            links.push(MarkdownLink {
+                range: start..target_end + 1,
+                text: text[start + 1..text_end].to_string(),
+                target: text[target_start..target_end].trim().to_string(),
+            });
+        }
+        pos = target_end + 1;
+    }
+    links
+}
+
+/// Returns the markdown link containing the byte `offset`, if any.
+pub fn markdown_link_at(text: &str, offset: usize) -> Option<MarkdownLink> {
+    find_markdown_links(text)
+        .into_iter()
+        .find(|link| link.range.contains(&offset))
+}
+
+/// Returns the label of a `[label]: url` reference definition at the start of `line`,
+/// as a byte range into the line.
+fn definition_label(line: &str) -> Option<Range<usize>> {
+    let content = line.trim_start_matches(' ');
+    let indent = line.len() - content.len();
+    if indent > 3 || !content.starts_with('[') {
+        return None;
+    }
+    let len = content[1..].find([']', '['])?;
+    let rest = &content[1 + len..];
+    (len > 0 && rest.starts_with("]:")).then(|| indent + 1..indent + 1 + len)
+}
+
+/// Finds the labels of reference-style links in `text`: the `label` of every
+/// `[label]: url` definition, and of every `[text][label]`, `[label][]` or `[label]`
+/// using one of those definitions. Ranges cover the label alone; code is skipped.
+pub fn reference_labels(text: &str) -> Vec<Range<usize>> {
+    let code_ranges = markdown_context::code_ranges(text);
+    let outside_code =
+        |range: &Range<usize>| !code_ranges.iter().any(|code| code.contains(&range.start));
+    let mut definitions = Vec::new();
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if let Some(label) = definition_label(line) {
+            definitions.push(offset + label.start..offset + label.end);
+        }
+        offset += line.len();
+    }
+    definitions.retain(|range| outside_code(range));
+    let defined: Vec<String> = definitions
+        .iter()
+        .map(|range| text[range.clone()].to_lowercase())
+        .collect();
+
+    let bytes = text.as_bytes();
+    let mut labels = Vec::new();
+    let mut pos = 0;
+    while let Some(found) = text[pos..].find('[') {
+        let start = pos + found;
+        pos = start + 1;
+        if start > 0 && bytes[start - 1] == b'[' || bytes.get(start + 1) == Some(&b'[') {
+            continue;
+        }
+        let Some(len) = text[start + 1..].find([']', '[', '\n']) else {
+            break;
+        };
+        let close = start + 1 + len;
+        if bytes[close] != b']' || definitions.iter().any(|def| def.start == start + 1) {
+            continue;
+        }
+        pos = close + 1;
+        let label = match bytes.get(close + 1) {
+            Some(b'(') | Some(b':') => continue,
+            Some(b'[') => {
+                let Some(len) = text[close + 2..].find([']', '[', '\n']) else {
+                    break;
+                };
+                let end = close + 2 + len;
+                if bytes[end] != b']' {
+                    continue;
+                }
+                pos = end + 1;
+                if len == 0 {
+                    start + 1..close
+                } else {
+                    close + 2..end
+                }
+            }
+            _ => start + 1..close,
+        };
+        if outside_code(&label) && defined.contains(&text[label.clone()].to_lowercase()) {
+            labels.push(label);
+        }
+    }
+    labels.extend(definitions);
+    labels.sort_by_key(|range| range.start);
+    labels
+}
+
+/// Returns the reference labels to edit together with the one at `offset`: every
+/// occurrence of the same label, written identically, across its uses and definition.
+/// Returns `None` when the cursor isn't on a label that occurs more than once.
+pub fn linked_reference_labels(text: &str, offset: usize) -> Option<Vec<Range<usize>>> {
+    let labels = reference_labels(text);
+    let current = labels
+        .iter()
+        .find(|range| range.start <= offset && offset <= range.end)?;
+    let name = &text[current.clone()];
+    let linked: Vec<Range<usize>> = labels
+        .iter()
+        .filter(|range| &text[(*range).clone()] == name)
+        .cloned()
+        .collect();
+    (linked.len() > 1).then_some(linked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_markdown_links() {
+        let text = "See [the guide](docs/guide.md), ![img](a.png) and [[wiki]]. `[x](y)`";
+        let links = find_markdown_links(text);
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].text, "the guide");
+        assert_eq!(links[0].target, "docs/guide.md");
+        assert_eq!(&text[links[0].range.clone()], "[the guide](docs/guide.md)");
+        assert!(markdown_link_at(text, 6).is_some());
+        assert!(markdown_link_at(text, 40).is_none());
+    }
+
+    #[test]
+    fn test_reference_labels() {
+        let text = "See [the docs][docs], [docs][] and [docs].\n\
+                    Not [inline](x.md) or [unknown].\n`[docs]`\n\n[docs]: https://example.com\n";
+        let labels: Vec<_> = reference_labels(text)
+            .into_iter()
+            .map(|range| (range.start, &text[range]))
+            .collect();
+        assert_eq!(
+            labels,
+            vec![(15, "docs"), (23, "docs"), (36, "docs"), (87, "docs")]
+        );
+
+        let linked = linked_reference_labels(text, 89).unwrap();
+        assert_eq!(linked.len(), 4);
+        assert_eq!(linked_reference_labels(text, 5), None);
+        assert_eq!(linked_reference_labels("[a]\n\n[a]: b\n[b]: c\n", 13), None);
+    }
+}
@@ -0,0 +1,97 @@
+use std::ops::Range;
+use std::path::Path;
+
+use tower_lsp::lsp_types::Location;
+
+use crate::frontmatter;
+use crate::link_index::LinkIndex;
+use crate::markdown_context;
+use crate::markdown_link;
+use crate::position::{self, Encoding};
+use crate::vault;
+use crate::wikilink::{self, Delimiters};
+
+/// Command listing where a note's title appears as plain text in other notes.
+pub const FIND_UNLINKED_MENTIONS_COMMAND: &str = "notemancy.findUnlinkedMentions";
+
+/// Finds the places where `title` appears in `text` as a whole word or phrase, ignoring
+/// ASCII case. Mentions inside the frontmatter, wiki-links, markdown links and code are
+/// left out, since they are either links already or not prose.
+pub fn unlinked_mentions(text: &str, title: &str, delimiters: &Delimiters) -> Vec<Range<usize>> {
+    let title = title.trim();
+    if title.is_empty() {
+        return Vec::new();
+    }
+    let mut excluded = markdown_context::code_ranges(text);
+    excluded.extend(frontmatter::frontmatter_range(text));
+    excluded.extend(
+        wikilink::find_wikilinks(text, delimiters)
+            .into_iter()
+            .map(|link| link.range),
+    );
+    excluded.extend(
+        markdown_link::find_markdown_links(text)
+            .into_iter()
+            .map(|link| link.range),
+    );
+
+    let haystack = text.to_ascii_lowercase();
+    let needle = title.to_ascii_lowercase();
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let mut mentions = Vec::new();
+    let mut pos = 0;
+    while let Some(found) = haystack[pos..].find(&needle) {
+        let start = pos + found;
+        let end = start + needle.len();
+        pos = start + needle.chars().next().map_or(1, char::len_utf8);
+        let bounded = !text[..start].chars().next_back().is_some_and(is_word)
+            && !text[end..].chars().next().is_some_and(is_word);
+        if bounded && !excluded.iter().any(|range| range.contains(&start)) {
+            mentions.push(start..end);
+            pos = end;
+        }
+    }
+    mentions
+}
+
+/// Finds the unlinked mentions of `title` across the notes of the link `index`, skipping
+/// the note `exclude` (normally the one with that title).
+pub fn vault_unlinked_mentions(
+    index: &LinkIndex,
+    title: &str,
+    exclude: Option<&Path>,
+    encoding: Encoding,
+) -> Vec<Location> {
+    let exclude = exclude.map(vault::normalize_path);
+    let mut locations = Vec::new();
+    for (file, uri, content) in index.contents() {
+        if exclude.as_ref() == Some(&vault::normalize_path(file)) {
+            continue;
+        }
+        for range in unlinked_mentions(content, title, index.delimiters()) {
+            locations.push(Location {
+                uri: uri.clone(),
+                range: position::offset_range(content, &range, encoding),
+            });
+        }
+    }
+    locations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unlinked_mentions() {
+        let text = "---\ntitle: Rust Notes\n---\nMy rust notes and RUST NOTES.\n\
+                    Not rust notesy, see [[Rust Notes]] or [rust notes](x.md).\n\
+                    `rust notes` stays code; (Rust Notes) counts.\n";
+        let found: Vec<_> = unlinked_mentions(text, "Rust Notes", &Delimiters::default())
+            .into_iter()
+            .map(|range| &text[range])
+            .collect();
+        assert_eq!(found, vec!["rust notes", "RUST NOTES", "Rust Notes"]);
+        assert!(unlinked_mentions(text, "  ", &Delimiters::default()).is_empty());
+    }
+}
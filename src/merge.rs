@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use tower_lsp::lsp_types::{
+    DeleteFile, DocumentChangeOperation, DocumentChanges, OneOf,
+    OptionalVersionedTextDocumentIdentifier, ResourceOp, TextDocumentEdit, TextEdit, Url,
+    WorkspaceEdit,
+};
+
+use crate::frontmatter;
+use crate::link_index::LinkIndex;
+use crate::position::{self, Encoding};
+use crate::vault;
+use crate::wikilink::{self, WikiLink};
+
+/// The command that merges one note into another. Its arguments are the source and target
+/// virtual paths; it returns `{"linksRewritten": n}`.
+pub const MERGE_NOTE_COMMAND: &str = "notemancy.mergeNote";
+
+/// A note taking part in a merge: its file and current text.
+pub struct MergeNote<'a> {
+    pub file: &'a Path,
+    pub text: &'a str,
+}
+
+/// The path a link to the merged note is rewritten to: the target, written with or
+/// without `.md` as the link was, and with `anchor` added when the link had none.
+fn rewritten_path(link: &WikiLink, target_vpath: &str, anchor: Option<&str>) -> String {
+    let mut path = if link.path.ends_with(".md") {
+        target_vpath.to_string()
+    } else {
+        target_vpath
+            .strip_suffix(".md")
+            .unwrap_or(target_vpath)
+            .to_string()
+    };
+    if let Some(anchor) = anchor.filter(|_| link.anchor.is_none() && link.block.is_none()) {
+        path.push('#');
+        path.push_str(anchor);
+    }
+    path
+}
+
+/// Builds the edit that merges `source` into `target`: the source's body is appended to
+/// the target under a `## heading` section, every link to the source is pointed at the
+/// target instead (at the new section when `link_to_section` is set), and the source file
+/// is deleted last. Links inside the moved body are rewritten too. Returns the edit and
+/// the number of links rewritten.
+pub fn merge_edit(
+    vault_dir: &Path,
+    source: MergeNote,
+    target: MergeNote,
+    heading: &str,
+    link_to_section: bool,
+    index: &LinkIndex,
+    encoding: Encoding,
+) -> Option<(WorkspaceEdit, usize)> {
+    let source_vpath = vault::virtual_path(vault_dir, source.file)?;
+    let target_vpath = vault::virtual_path(vault_dir, target.file)?;
+    if source_vpath == target_vpath {
+        return None;
+    }
+    let source_uri = Url::from_file_path(source.file).ok()?;
+    let target_uri = Url::from_file_path(target.file).ok()?;
+    let anchor = link_to_section.then_some(heading.trim());
+    let mut rewritten = 0;
+
+    // The moved body, with its own links to the source rewritten.
+    let body = frontmatter::strip_frontmatter(source.text);
+    let mut moved = String::new();
+    let mut copied = 0;
+    for link in wikilink::find_wikilinks(body, index.delimiters()) {
+        let path = vault::vault_link_path(vault_dir, source.file, &link.path);
+        if vault::link_matches(&path, &source_vpath) {
+            moved.push_str(&body[copied..link.path_range.start]);
+            moved.push_str(&rewritten_path(&link, &target_vpath, anchor));
+            copied = link.path_range.end;
+            rewritten += 1;
+        }
+    }
+    moved.push_str(&body[copied..]);
+
+    let mut edits: BTreeMap<Url, Vec<TextEdit>> = BTreeMap::new();
+    for note in index.note_links(&source_vpath) {
+        if note.uri == source_uri {
+            continue;
+        }
+        // The target's text may be newer than the index's copy when it's open.
+        let content = if note.uri == target_uri {
+            target.text.to_string()
+        } else {
+            note.content
+        };
+        let links = if note.uri == target_uri {
+            wikilink::find_wikilinks(&content, index.delimiters())
+                .into_iter()
+                .filter(|link| {
+                    let path = vault::vault_link_path(vault_dir, target.file, &link.path);
+                    vault::link_matches(&path, &source_vpath)
+                })
+                .collect()
+        } else {
+            note.links
+        };
+        for link in links {
+            edits.entry(note.uri.clone()).or_default().push(TextEdit {
+                range: position::offset_range(&content, &link.path_range, encoding),
+                new_text: rewritten_path(&link, &target_vpath, anchor),
+            });
+            rewritten += 1;
+        }
+    }
+    let separator = match target.text {
+        "" => "",
+        text if text.ends_with("\n\n") => "",
+        text if text.ends_with('\n') => "\n",
+        _ => "\n\n",
+    };
+    let end = target.text.len();
+    edits.entry(target_uri).or_default().push(TextEdit {
+        range: position::offset_range(target.text, &(end..end), encoding),
+        new_text: format!(
+            "{}## {}\n\n{}\n",
+            separator,
+            heading.trim(),
+            moved.trim_matches('\n')
+        ),
+    });
+
+    let mut operations: Vec<DocumentChangeOperation> = edits
+        .into_iter()
+        .map(|(uri, edits)| {
+            DocumentChangeOperation::Edit(TextDocumentEdit {
+                text_document: OptionalVersionedTextDocumentIdentifier { uri, version: None },
+                edits: edits.into_iter().map(OneOf::Left).collect(),
+            })
+        })
+        .collect();
+    operations.push(DocumentChangeOperation::Op(ResourceOp::Delete(
+        DeleteFile {
+            uri: source_uri,
+            options: None,
+        },
+    )));
+    Some((
+        WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(operations)),
+            ..Default::default()
+        },
+        rewritten,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    use crate::wikilink::Delimiters;
+
+    #[test]
+    fn test_merge_edit() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-merge-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(&vault_dir).unwrap();
+        let source = "---\ntitle: Draft\n---\nDraft text, see [[draft#Part]].\n";
+        let target = "# Final\nAlso [[draft]].";
+        fs::write(vault_dir.join("draft.md"), source).unwrap();
+        fs::write(vault_dir.join("final.md"), target).unwrap();
+        fs::write(vault_dir.join("other.md"), "[[draft.md | d]] [[final]]\n").unwrap();
+
+        let (edit, rewritten) = merge_edit(
+            &vault_dir,
+            MergeNote {
+                file: &vault_dir.join("draft.md"),
+                text: source,
+            },
+            MergeNote {
+                file: &vault_dir.join("final.md"),
+                text: target,
+            },
+            "Draft",
+            true,
+            &LinkIndex::build(&vault_dir, &Delimiters::default()),
+            Encoding::default(),
+        )
+        .unwrap();
+        assert_eq!(rewritten, 3);
+        let Some(DocumentChanges::Operations(operations)) = edit.document_changes else {
+            panic!("expected document change operations");
+        };
+        let texts = |index: usize| match &operations[index] {
+            DocumentChangeOperation::Edit(edit) => edit
+                .edits
+                .iter()
+                .map(|edit| match edit {
+                    OneOf::Left(edit) => edit.new_text.clone(),
+                    OneOf::Right(edit) => edit.text_edit.new_text.clone(),
+                })
+                .collect::<Vec<_>>(),
+            _ => panic!("expected a text edit"),
+        };
+        assert_eq!(
+            texts(0),
+            vec![
+                "final#Draft".to_string(),
+                "\n\n## Draft\n\nDraft text, see [[final#Part]].\n".to_string()
+            ]
+        );
+        assert_eq!(texts(1), vec!["final.md#Draft".to_string()]);
+        assert!(matches!(
+            operations.last(),
+            Some(DocumentChangeOperation::Op(ResourceOp::Delete(_)))
+        ));
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+}
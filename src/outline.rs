@@ -0,0 +1,121 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::frontmatter;
+use crate::heading;
+use crate::vault;
+
+/// The command listing the headings of every note, for a vault-wide table of contents.
+/// Its optional argument is a query the notes' paths or titles must fuzzy match; it
+/// returns `{vpath, title, headings}` entries.
+pub const GLOBAL_OUTLINE_COMMAND: &str = "notemancy.globalOutline";
+
+/// A heading in the global outline.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct OutlineHeading {
+    pub name: String,
+    pub level: usize,
+    /// Zero-based line number of the heading.
+    pub line: usize,
+}
+
+/// A note and its headings, in document order.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct NoteOutline {
+    pub vpath: String,
+    pub title: String,
+    pub headings: Vec<OutlineHeading>,
+}
+
+/// The outline of the note at `vpath` with `content`. Notes without a frontmatter title
+/// are named after their file.
+pub fn note_outline(vpath: String, content: &str) -> NoteOutline {
+    let title = frontmatter::title(content).unwrap_or_else(|| {
+        let name = vpath.rsplit('/').next().unwrap_or(&vpath);
+        name.strip_suffix(".md").unwrap_or(name).to_string()
+    });
+    let headings = heading::parse_headings(content)
+        .into_iter()
+        .map(|heading| OutlineHeading {
+            name: heading.title,
+            level: heading.level,
+            line: heading.line,
+        })
+        .collect();
+    NoteOutline {
+        vpath,
+        title,
+        headings,
+    }
+}
+
+/// The outlines of the notes among `files`, by virtual path. With a `query`, only notes
+/// whose path or title fuzzy matches it are kept.
+pub fn vault_outline(vault_dir: &Path, files: &[PathBuf], query: Option<&str>) -> Vec<NoteOutline> {
+    let matcher = query
+        .map(str::trim)
+        .filter(|query| !query.is_empty())
+        .map(crate::FuzzyMatcher::new);
+    let mut outlines: Vec<NoteOutline> = files
+        .iter()
+        .filter_map(|file| {
+            let vpath = vault::virtual_path(vault_dir, file)?;
+            let content = fs::read_to_string(file).ok()?;
+            Some(note_outline(vpath, &content))
+        })
+        .filter(|outline| {
+            matcher.as_ref().is_none_or(|matcher| {
+                matcher.score(&outline.vpath).is_some() || matcher.score(&outline.title).is_some()
+            })
+        })
+        .collect();
+    outlines.sort_by(|a, b| a.vpath.cmp(&b.vpath));
+    outlines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vault_outline() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-global-outline-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(vault_dir.join("notes")).unwrap();
+        fs::write(
+            vault_dir.join("notes/rust.md"),
+            "---\ntitle: Rust Notes\n---\n# Rust\n## Traits\n```\n# not a heading\n```\n",
+        )
+        .unwrap();
+        fs::write(vault_dir.join("cooking.md"), "Intro\n\nSoup\n====\n").unwrap();
+        let files = vault::collect_markdown_files(&vault_dir);
+
+        let outlines = vault_outline(&vault_dir, &files, None);
+        assert_eq!(outlines.len(), 2);
+        assert_eq!(outlines[0].vpath, "cooking.md");
+        assert_eq!(outlines[0].title, "cooking");
+        assert_eq!(
+            outlines[0].headings,
+            vec![OutlineHeading {
+                name: "Soup".to_string(),
+                level: 1,
+                line: 2,
+            }]
+        );
+        let names: Vec<_> = outlines[1]
+            .headings
+            .iter()
+            .map(|heading| (heading.name.as_str(), heading.level, heading.line))
+            .collect();
+        assert_eq!(names, vec![("Rust", 1, 3), ("Traits", 2, 4)]);
+
+        let found = vault_outline(&vault_dir, &files, Some("rstnts"));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].title, "Rust Notes");
+        assert_eq!(vault_outline(&vault_dir, &files, Some(" ")).len(), 2);
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+}
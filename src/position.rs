@@ -0,0 +1,304 @@
+use tower_lsp::lsp_types::{
+    ClientCapabilities, Position, PositionEncodingKind, Range, TextDocumentContentChangeEvent,
+};
+
+/// What a position's `character` counts, as negotiated with the client: UTF-16 code units
+/// unless the client offered something else.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    #[default]
+    Utf16,
+    Utf32,
+}
+
+impl Encoding {
+    /// The kind to announce in the server capabilities.
+    pub fn kind(self) -> PositionEncodingKind {
+        match self {
+            Encoding::Utf8 => PositionEncodingKind::UTF8,
+            Encoding::Utf16 => PositionEncodingKind::UTF16,
+            Encoding::Utf32 => PositionEncodingKind::UTF32,
+        }
+    }
+
+    /// Length of `c` in this encoding's units.
+    fn width(self, c: char) -> usize {
+        match self {
+            Encoding::Utf8 => c.len_utf8(),
+            Encoding::Utf16 => c.len_utf16(),
+            Encoding::Utf32 => 1,
+        }
+    }
+}
+
+/// Picks the position encoding from the ones the client supports, in the client's order
+/// of preference. Without a list, the LSP default of UTF-16 applies.
+pub fn negotiate_encoding(capabilities: &ClientCapabilities) -> Encoding {
+    let offered = capabilities
+        .general
+        .as_ref()
+        .and_then(|general| general.position_encodings.as_deref())
+        .unwrap_or_default();
+    offered
+        .iter()
+        .find_map(|kind| match kind.as_str() {
+            "utf-8" => Some(Encoding::Utf8),
+            "utf-16" => Some(Encoding::Utf16),
+            "utf-32" => Some(Encoding::Utf32),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// The column of byte index `byte` of `line`, in `encoding`'s units.
+pub fn column(line: &str, byte: usize, encoding: Encoding) -> u32 {
+    line[..byte]
+        .chars()
+        .map(|c| encoding.width(c))
+        .sum::<usize>() as u32
+}
+
+/// The byte index of column `character` of `line`, or `None` past the end of the line. A
+/// column inside a character lands on the character's start.
+pub fn byte_column(line: &str, character: u32, encoding: Encoding) -> Option<usize> {
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        units += encoding.width(c);
+        if units > character as usize {
+            return Some(i);
+        }
+    }
+    (units == character as usize).then_some(line.len())
+}
+
+/// The position at the end of line `line` of `text`, before its terminator.
+pub fn line_end(text: &str, line: usize, encoding: Encoding) -> Position {
+    let content = text.split('\n').nth(line).unwrap_or_default();
+    let content = content.strip_suffix('\r').unwrap_or(content);
+    Position {
+        line: line as u32,
+        character: column(content, content.len(), encoding),
+    }
+}
+
+/// Converts an LSP position into a byte offset into `text`. Lines may end in `\n` or
+/// `\r\n`; a column can reach the end of the line but not into its terminator.
+pub fn position_to_offset(text: &str, position: Position, encoding: Encoding) -> Option<usize> {
+    let mut offset = 0;
+    for (line_num, line) in text.split('\n').enumerate() {
+        if line_num == position.line as usize {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            return byte_column(line, position.character, encoding).map(|col| offset + col);
+        }
+        offset += line.len() + 1;
+    }
+    None
+}
+
+/// Converts a byte offset into `text` into an LSP position.
+pub fn offset_to_position(text: &str, offset: usize, encoding: Encoding) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: column(&text[line_start..], offset - line_start, encoding),
+    }
+}
+
+/// Converts a byte range into `text` into an LSP range.
+pub fn offset_range(text: &str, range: &std::ops::Range<usize>, encoding: Encoding) -> Range {
+    Range {
+        start: offset_to_position(text, range.start, encoding),
+        end: offset_to_position(text, range.end, encoding),
+    }
+}
+
+/// Applies an incremental content change to `text`. A change without a range replaces
+/// the whole document.
+pub fn apply_change(text: &mut String, change: TextDocumentContentChangeEvent, encoding: Encoding) {
+    match change.range {
+        Some(range) => {
+            let start = clamped_offset(text, range.start, encoding);
+            let end = clamped_offset(text, range.end, encoding).max(start);
+            text.replace_range(start..end, &change.text);
+        }
+        None => *text = change.text,
+    }
+}
+
+/// Like `position_to_offset`, but clamps positions past the end of a line or of the
+/// document instead of rejecting them.
+fn clamped_offset(text: &str, position: Position, encoding: Encoding) -> usize {
+    let mut offset = 0;
+    for (line_num, line) in text.split('\n').enumerate() {
+        if line_num == position.line as usize {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            return offset + byte_column(line, position.character, encoding).unwrap_or(line.len());
+        }
+        offset += line.len() + 1;
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_round_trip() {
+        let text = "first\nsecond [[link]]\n";
+        let position = Position {
+            line: 1,
+            character: 7,
+        };
+        let offset = position_to_offset(text, position, Encoding::Utf16).unwrap();
+        assert_eq!(&text[offset..offset + 2], "[[");
+        assert_eq!(offset_to_position(text, offset, Encoding::Utf16), position);
+        assert_eq!(
+            position_to_offset(
+                text,
+                Position {
+                    line: 0,
+                    character: 9
+                },
+                Encoding::Utf16
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_apply_change() {
+        let mut text = "# Title\nold line\n".to_string();
+        let range_change = |sl, sc, el, ec, new: &str| TextDocumentContentChangeEvent {
+            range: Some(Range {
+                start: Position {
+                    line: sl,
+                    character: sc,
+                },
+                end: Position {
+                    line: el,
+                    character: ec,
+                },
+            }),
+            range_length: None,
+            text: new.to_string(),
+        };
+        apply_change(&mut text, range_change(1, 0, 1, 3, "new"), Encoding::Utf16);
+        assert_eq!(text, "# Title\nnew line\n");
+        apply_change(
+            &mut text,
+            range_change(2, 0, 2, 0, "appended"),
+            Encoding::Utf16,
+        );
+        assert_eq!(text, "# Title\nnew line\nappended");
+        apply_change(&mut text, range_change(0, 7, 1, 99, ""), Encoding::Utf16);
+        assert_eq!(text, "# Title\nappended");
+
+        apply_change(
+            &mut text,
+            TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: "replaced".to_string(),
+            },
+            Encoding::Utf16,
+        );
+        assert_eq!(text, "replaced");
+    }
+
+    #[test]
+    fn test_crlf_offsets() {
+        let text = "first\r\nsecond [[link]]\r\nthird";
+        let offset = position_to_offset(text, Position::new(1, 7), Encoding::Utf16).unwrap();
+        assert_eq!(&text[offset..offset + 8], "[[link]]");
+        assert_eq!(
+            offset_to_position(text, offset, Encoding::Utf16),
+            Position::new(1, 7)
+        );
+        assert_eq!(
+            position_to_offset(text, Position::new(2, 0), Encoding::Utf16),
+            Some(text.find("third").unwrap())
+        );
+        // The end of a line is addressable, its "\r" isn't.
+        assert_eq!(
+            position_to_offset(text, Position::new(0, 5), Encoding::Utf16),
+            Some(5)
+        );
+        assert_eq!(
+            position_to_offset(text, Position::new(0, 6), Encoding::Utf16),
+            None
+        );
+
+        let mut text = text.to_string();
+        apply_change(
+            &mut text,
+            TextDocumentContentChangeEvent {
+                range: Some(Range::new(Position::new(0, 0), Position::new(0, 99))),
+                range_length: None,
+                text: "1st".to_string(),
+            },
+            Encoding::Utf16,
+        );
+        assert!(text.starts_with("1st\r\nsecond"));
+    }
+
+    #[test]
+    fn test_utf16_columns() {
+        // "🦀" is four bytes and two UTF-16 code units, "é" two bytes and one unit.
+        let text = "# Café\n🦀 see [[crab]]\n";
+        let link = text.find("[[").unwrap();
+        let position = Position::new(1, 7);
+        assert_eq!(
+            position_to_offset(text, position, Encoding::Utf16),
+            Some(link)
+        );
+        assert_eq!(offset_to_position(text, link, Encoding::Utf16), position);
+        assert_eq!(line_end(text, 0, Encoding::Utf16), Position::new(0, 6));
+        assert_eq!(column("🦀 see", "🦀 see".len(), Encoding::Utf16), 6);
+        // A column between the two halves of the emoji lands on its start.
+        assert_eq!(
+            position_to_offset(text, Position::new(1, 1), Encoding::Utf16),
+            Some(text.find('🦀').unwrap())
+        );
+        assert_eq!(
+            position_to_offset(text, Position::new(0, 7), Encoding::Utf16),
+            None
+        );
+    }
+
+    #[test]
+    fn test_negotiate_encoding() {
+        let offering = |kinds: Vec<PositionEncodingKind>| ClientCapabilities {
+            general: Some(tower_lsp::lsp_types::GeneralClientCapabilities {
+                position_encodings: Some(kinds),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let utf8 = negotiate_encoding(&offering(vec![
+            PositionEncodingKind::new("utf-7"),
+            PositionEncodingKind::UTF8,
+            PositionEncodingKind::UTF16,
+        ]));
+        assert_eq!(utf8, Encoding::Utf8);
+        assert_eq!(utf8.kind(), PositionEncodingKind::UTF8);
+        let utf32 = negotiate_encoding(&offering(vec![PositionEncodingKind::UTF32]));
+        assert_eq!(utf32, Encoding::Utf32);
+        assert_eq!(
+            negotiate_encoding(&ClientCapabilities::default()),
+            Encoding::Utf16
+        );
+
+        // "🦀" is four UTF-8 bytes, two UTF-16 units and one UTF-32 unit.
+        let text = "🦀 [[crab]]";
+        let link = text.find("[[").unwrap();
+        let character = |encoding| offset_to_position(text, link, encoding).character;
+        assert_eq!(character(Encoding::Utf8), 5);
+        assert_eq!(character(Encoding::Utf16), 3);
+        assert_eq!(character(Encoding::Utf32), 2);
+        let offset = position_to_offset(text, Position::new(0, 5), Encoding::Utf8);
+        assert_eq!(offset, Some(link));
+    }
+}
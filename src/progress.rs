@@ -0,0 +1,96 @@
+use tokio::runtime::Handle;
+use tower_lsp::lsp_types::notification::Progress;
+use tower_lsp::lsp_types::{
+    ProgressParams, ProgressParamsValue, ProgressToken, WorkDoneProgress, WorkDoneProgressBegin,
+    WorkDoneProgressEnd, WorkDoneProgressReport,
+};
+use tower_lsp::Client;
+
+/// Reports the progress of a long-running scan through the work-done token the client sent
+/// with its request. Meant to be driven from inside `spawn_blocking`; every method does
+/// nothing when the client didn't send a token.
+pub struct WorkDone {
+    client: Client,
+    token: Option<ProgressToken>,
+    runtime: Handle,
+    /// The last percentage sent, so unchanged values aren't reported again.
+    percentage: Option<u32>,
+}
+
+/// Share of `total` that `done` items make up, as a whole percentage.
+fn percentage(done: usize, total: usize) -> u32 {
+    if total == 0 {
+        return 100;
+    }
+    (done.min(total) * 100 / total) as u32
+}
+
+impl WorkDone {
+    /// Prepares to report on `token`. Must be called from async code, whose runtime later
+    /// carries the notifications.
+    pub fn new(client: Client, token: Option<ProgressToken>) -> Self {
+        WorkDone {
+            client,
+            token,
+            runtime: Handle::current(),
+            percentage: None,
+        }
+    }
+
+    /// Shows the progress with `title`, at nothing done yet.
+    pub fn begin(&mut self, title: &str) {
+        self.percentage = Some(0);
+        self.send(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+            title: title.to_string(),
+            cancellable: Some(false),
+            message: None,
+            percentage: Some(0),
+        }));
+    }
+
+    /// Moves the progress to `done` of `total` items, skipping the notification when the
+    /// percentage hasn't changed.
+    pub fn report(&mut self, done: usize, total: usize) {
+        let percentage = percentage(done, total);
+        if self.percentage == Some(percentage) {
+            return;
+        }
+        self.percentage = Some(percentage);
+        self.send(WorkDoneProgress::Report(WorkDoneProgressReport {
+            cancellable: Some(false),
+            message: Some(format!("{}/{}", done, total)),
+            percentage: Some(percentage),
+        }));
+    }
+
+    /// Closes the progress, with an optional final `message`.
+    pub fn end(&mut self, message: Option<String>) {
+        self.send(WorkDoneProgress::End(WorkDoneProgressEnd { message }));
+    }
+
+    fn send(&self, value: WorkDoneProgress) {
+        let Some(token) = self.token.clone() else {
+            return;
+        };
+        let params = ProgressParams {
+            token,
+            value: ProgressParamsValue::WorkDone(value),
+        };
+        self.runtime
+            .block_on(self.client.send_notification::<Progress>(params));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentage() {
+        assert_eq!(percentage(0, 8), 0);
+        assert_eq!(percentage(3, 8), 37);
+        assert_eq!(percentage(8, 8), 100);
+        assert_eq!(percentage(9, 8), 100);
+        assert_eq!(percentage(0, 0), 100);
+    }
+}
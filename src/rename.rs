@@ -0,0 +1,170 @@
+use std::path::Path;
+
+use tower_lsp::lsp_types::{
+    DocumentChangeOperation, DocumentChanges, OneOf, OptionalVersionedTextDocumentIdentifier,
+    RenameFile, ResourceOp, TextDocumentEdit, TextEdit, Url, WorkspaceEdit,
+};
+
+use crate::link_index::LinkIndex;
+use crate::position::{self, Encoding};
+use crate::vault;
+
+/// Builds the edit that renames the note at `old_file` to the virtual path `new_name`.
+/// Every inbound link, including self-links inside the note, has its path rewritten while
+/// keeping any `#anchor` and `| alias`; the file itself is renamed last.
+pub fn rename_note(
+    vault_dir: &Path,
+    old_file: &Path,
+    new_name: &str,
+    index: &LinkIndex,
+    encoding: Encoding,
+) -> Option<WorkspaceEdit> {
+    let old_vpath = vault::virtual_path(vault_dir, old_file)?;
+    let new_name = new_name.trim();
+    if new_name.is_empty() {
+        return None;
+    }
+    let new_vpath = if new_name.ends_with(".md") {
+        new_name.to_string()
+    } else {
+        format!("{}.md", new_name)
+    };
+    if new_vpath == old_vpath {
+        return None;
+    }
+
+    let mut operations = Vec::new();
+    for note in index.note_links(&old_vpath) {
+        let edits = note
+            .links
+            .iter()
+            .map(|link| {
+                // Keep the link's own style of including or omitting the extension.
+                let new_text = if link.path.ends_with(".md") {
+                    new_vpath.clone()
+                } else {
+                    new_vpath
+                        .strip_suffix(".md")
+                        .unwrap_or(&new_vpath)
+                        .to_string()
+                };
+                OneOf::Left(TextEdit {
+                    range: position::offset_range(&note.content, &link.path_range, encoding),
+                    new_text,
+                })
+            })
+            .collect();
+        operations.push(DocumentChangeOperation::Edit(TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier {
+                uri: note.uri,
+                version: None,
+            },
+            edits,
+        }));
+    }
+    operations.push(DocumentChangeOperation::Op(ResourceOp::Rename(
+        RenameFile {
+            old_uri: Url::from_file_path(old_file).ok()?,
+            new_uri: Url::from_file_path(vault_dir.join(&new_vpath)).ok()?,
+            options: None,
+            annotation_id: None,
+        },
+    )));
+
+    Some(WorkspaceEdit {
+        document_changes: Some(DocumentChanges::Operations(operations)),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    use crate::wikilink::Delimiters;
+
+    #[test]
+    fn test_rename_note_rewrites_inbound_links() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-rename-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(&vault_dir).unwrap();
+        fs::write(vault_dir.join("old.md"), "# Old\nSee [[old#Old]].\n").unwrap();
+        fs::write(
+            vault_dir.join("other.md"),
+            "Link to [[old.md | the old one]].\n",
+        )
+        .unwrap();
+
+        let edit = rename_note(
+            &vault_dir,
+            &vault_dir.join("old.md"),
+            "new",
+            &LinkIndex::build(&vault_dir, &Delimiters::default()),
+            Encoding::default(),
+        )
+        .expect("rename should produce an edit");
+        let Some(DocumentChanges::Operations(operations)) = edit.document_changes else {
+            panic!("expected document change operations");
+        };
+
+        let mut new_texts = Vec::new();
+        for operation in &operations {
+            if let DocumentChangeOperation::Edit(edit) = operation {
+                for text_edit in &edit.edits {
+                    if let OneOf::Left(text_edit) = text_edit {
+                        new_texts.push(text_edit.new_text.clone());
+                    }
+                }
+            }
+        }
+        new_texts.sort();
+        assert_eq!(new_texts, vec!["new".to_string(), "new.md".to_string()]);
+        assert!(matches!(
+            operations.last(),
+            Some(DocumentChangeOperation::Op(ResourceOp::Rename(_)))
+        ));
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+
+    #[test]
+    fn test_rename_note_rewrites_relative_links() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-rename-relative-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(vault_dir.join("projects")).unwrap();
+        fs::write(vault_dir.join("old.md"), "# Old\n").unwrap();
+        fs::write(
+            vault_dir.join("projects/plan.md"),
+            "Up to [[../old#Old]] and [[./old]].\n",
+        )
+        .unwrap();
+
+        let edit = rename_note(
+            &vault_dir,
+            &vault_dir.join("old.md"),
+            "archive/new",
+            &LinkIndex::build(&vault_dir, &Delimiters::default()),
+            Encoding::default(),
+        )
+        .expect("rename should produce an edit");
+        let Some(DocumentChanges::Operations(operations)) = edit.document_changes else {
+            panic!("expected document change operations");
+        };
+        let DocumentChangeOperation::Edit(edit) = &operations[0] else {
+            panic!("expected the linking note to be edited");
+        };
+        assert!(edit.text_document.uri.path().ends_with("/projects/plan.md"));
+        let new_texts: Vec<_> = edit
+            .edits
+            .iter()
+            .map(|edit| match edit {
+                OneOf::Left(edit) => (edit.range.start.character, edit.new_text.as_str()),
+                OneOf::Right(edit) => (edit.text_edit.range.start.character, ""),
+            })
+            .collect();
+        assert_eq!(new_texts, vec![(8, "archive/new")]);
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+}
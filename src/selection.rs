@@ -0,0 +1,139 @@
+use std::ops::Range;
+
+use tower_lsp::lsp_types::SelectionRange;
+
+use crate::heading;
+use crate::position::{self, Encoding};
+use crate::wikilink::{self, Delimiters};
+
+/// Builds the expand-selection chain at `offset`: word, wiki-link, paragraph, each
+/// enclosing heading section from innermost out, and finally the whole document.
+pub fn selection_range(
+    text: &str,
+    offset: usize,
+    delimiters: &Delimiters,
+    encoding: Encoding,
+) -> SelectionRange {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line_end = |line: usize| {
+        let end = line_starts
+            .get(line + 1)
+            .map_or(text.len(), |next| next - 1);
+        // Leave the "\r" of a CRLF terminator out of the line.
+        if text[..end].ends_with('\r') {
+            end - 1
+        } else {
+            end
+        }
+    };
+    let current_line = line_starts.partition_point(|start| *start <= offset) - 1;
+
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    if let Some(word) = word_at(text, offset) {
+        ranges.push(word);
+    }
+    if let Some(link) = wikilink::wikilink_at(text, offset, delimiters) {
+        ranges.push(link.range);
+    }
+
+    // Paragraphs end at blank lines and at headings.
+    let headings = heading::parse_headings(text);
+    let is_heading = |line: usize| headings.iter().any(|heading| heading.line == line);
+    let is_blank = |line: usize| text[line_starts[line]..line_end(line)].trim().is_empty();
+    if !is_blank(current_line) && !is_heading(current_line) {
+        let is_break = |line: usize| is_blank(line) || is_heading(line);
+        let first = (0..current_line)
+            .rev()
+            .find(|line| is_break(*line))
+            .map_or(0, |brk| brk + 1);
+        let last = (current_line + 1..line_starts.len())
+            .find(|line| is_break(*line))
+            .map_or(line_starts.len() - 1, |brk| brk - 1);
+        ranges.push(line_starts[first]..line_end(last));
+    }
+
+    let last_line = line_starts.len() - 1;
+    for index in (0..headings.len()).rev() {
+        let start_line = headings[index].line;
+        let end_line = heading::section_end_line(&headings, index, last_line);
+        if start_line <= current_line && current_line <= end_line {
+            ranges.push(line_starts[start_line]..line_end(end_line));
+        }
+    }
+    ranges.push(0..text.len());
+
+    // Each level must strictly grow and contain the one before it.
+    let mut chain: Vec<Range<usize>> = Vec::new();
+    for range in ranges {
+        let grows = chain.last().is_none_or(|inner| {
+            range.start <= inner.start && inner.end <= range.end && range != *inner
+        });
+        if grows {
+            chain.push(range);
+        }
+    }
+
+    let mut selection: Option<SelectionRange> = None;
+    for range in chain.iter().rev() {
+        selection = Some(SelectionRange {
+            range: position::offset_range(text, range, encoding),
+            parent: selection.map(Box::new),
+        });
+    }
+    selection.expect("the document range is always present")
+}
+
+fn word_at(text: &str, offset: usize) -> Option<Range<usize>> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    let start = text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_word(*c))
+        .last()
+        .map_or(offset, |(i, _)| i);
+    let end = text[offset..]
+        .char_indices()
+        .find(|(_, c)| !is_word(*c))
+        .map_or(text.len(), |(i, _)| offset + i);
+    (start < end).then_some(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selection_range_chain() {
+        let text = "# Title\n\n## Part\nSee [[some note]] here\nmore\n\nnext\n# Other";
+        let offset = text.find("some").unwrap() + 1;
+        let mut chain = Vec::new();
+        let mut current = Some(selection_range(
+            text,
+            offset,
+            &Delimiters::default(),
+            Encoding::default(),
+        ));
+        while let Some(selection) = current {
+            let start =
+                position::position_to_offset(text, selection.range.start, Encoding::default())
+                    .unwrap();
+            let end = position::position_to_offset(text, selection.range.end, Encoding::default())
+                .unwrap();
+            chain.push(&text[start..end]);
+            current = selection.parent.map(|parent| *parent);
+        }
+        assert_eq!(
+            chain,
+            vec![
+                "some",
+                "[[some note]]",
+                "See [[some note]] here\nmore",
+                "## Part\nSee [[some note]] here\nmore\n\nnext",
+                "# Title\n\n## Part\nSee [[some note]] here\nmore\n\nnext",
+                text,
+            ]
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use std::ops::Range;
+
+use tower_lsp::lsp_types::{SemanticToken, SemanticTokenType, SemanticTokensLegend};
+
+use crate::frontmatter;
+use crate::heading;
+use crate::position::{self, Encoding};
+use crate::tags;
+use crate::wikilink::{self, Delimiters};
+
+// Indices into the legend's token types.
+const HEADING: u32 = 0;
+const WIKI_LINK: u32 = 1;
+const WIKI_LINK_ALIAS: u32 = 2;
+const TAG: u32 = 3;
+const FRONTMATTER: u32 = 4;
+
+/// The token types reported by `semantic_tokens`, in index order.
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: vec![
+            SemanticTokenType::new("heading"),
+            SemanticTokenType::new("wikiLink"),
+            SemanticTokenType::new("wikiLinkAlias"),
+            SemanticTokenType::new("tag"),
+            SemanticTokenType::new("frontmatter"),
+        ],
+        token_modifiers: Vec::new(),
+    }
+}
+
+/// Tokenizes `text` into headings, wiki-link paths and aliases, tags and frontmatter
+/// lines, encoded relative to one another as the protocol requires. Tokens never span
+/// lines; where two would overlap, such as a tag inside a heading, the one starting first
+/// wins.
+pub fn semantic_tokens(
+    text: &str,
+    delimiters: &Delimiters,
+    encoding: Encoding,
+) -> Vec<SemanticToken> {
+    // (line, start column, length, token type), columns and lengths in `encoding`'s
+    // units.
+    let mut spans: Vec<(u32, u32, u32, u32)> = Vec::new();
+    let mut push_range = |range: &Range<usize>, token_type: u32| {
+        let start = position::offset_to_position(text, range.start, encoding);
+        let end = position::offset_to_position(text, range.end, encoding);
+        if start.line == end.line && end.character > start.character {
+            spans.push((
+                start.line,
+                start.character,
+                end.character - start.character,
+                token_type,
+            ));
+        }
+    };
+
+    if let Some(range) = frontmatter::frontmatter_range(text) {
+        let mut line_start = 0;
+        for line in text[range].split_inclusive('\n') {
+            let content = line.trim_end_matches(['\r', '\n']);
+            push_range(&(line_start..line_start + content.len()), FRONTMATTER);
+            line_start += line.len();
+        }
+    }
+    for link in wikilink::find_wikilinks(text, delimiters) {
+        push_range(&link.path_range, WIKI_LINK);
+        if let Some(alias_range) = &link.alias_range {
+            push_range(alias_range, WIKI_LINK_ALIAS);
+        }
+    }
+    for tag in tags::inline_tags(text) {
+        push_range(&tag.range, TAG);
+    }
+    for heading in heading::parse_headings(text) {
+        let line_start = position::position_to_offset(
+            text,
+            tower_lsp::lsp_types::Position::new(heading.line as u32, 0),
+            encoding,
+        )
+        .unwrap_or_default();
+        push_range(&(line_start..line_start + heading.line_len), HEADING);
+    }
+
+    spans.sort();
+    let mut tokens = Vec::new();
+    let (mut prev_line, mut prev_start, mut prev_end) = (0, 0, 0);
+    for (line, start, length, token_type) in spans {
+        if !tokens.is_empty() && line == prev_line && start < prev_end {
+            continue;
+        }
+        let delta_line = line - prev_line;
+        let delta_start = if delta_line == 0 {
+            start - prev_start
+        } else {
+            start
+        };
+        tokens.push(SemanticToken {
+            delta_line,
+            delta_start,
+            length,
+            token_type,
+            token_modifiers_bitset: 0,
+        });
+        (prev_line, prev_start, prev_end) = (line, start, start + length);
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Turns the relative encoding back into absolute `(line, column, length, type)`.
+    fn decode(tokens: &[SemanticToken]) -> Vec<(u32, u32, u32, u32)> {
+        let (mut line, mut start) = (0, 0);
+        tokens
+            .iter()
+            .map(|token| {
+                if token.delta_line > 0 {
+                    line += token.delta_line;
+                    start = token.delta_start;
+                } else {
+                    start += token.delta_start;
+                }
+                (line, start, token.length, token.token_type)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_semantic_tokens() {
+        let text = "---\ntags: [a]\n---\n# Title #x\nSee [[notes/a | Alias]] and #tag.\n```\n[[code]]\n```\n";
+        assert_eq!(
+            decode(&semantic_tokens(
+                text,
+                &Delimiters::default(),
+                Encoding::default()
+            )),
+            vec![
+                (0, 0, 3, FRONTMATTER),
+                (1, 0, 9, FRONTMATTER),
+                (2, 0, 3, FRONTMATTER),
+                (3, 0, 10, HEADING),
+                (4, 6, 7, WIKI_LINK),
+                (4, 16, 5, WIKI_LINK_ALIAS),
+                (4, 28, 4, TAG),
+            ]
+        );
+    }
+}
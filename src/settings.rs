@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_json::Value;
+use tower_lsp::lsp_types::SymbolKind;
+
+use crate::formatter::FormatOptions;
+use crate::ignore::IgnoreRules;
+use crate::wikilink::{Delimiters, LinkOrder};
+
+/// What a note completion inserts between the `[[` and `]]`.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WikilinkFormat {
+    /// `[[notes/rust.md]]`
+    Path,
+    /// `[[notes/rust.md | Rust]]`
+    #[default]
+    PathAlias,
+    /// `[[Rust]]`, resolved by title.
+    Title,
+}
+
+/// How the frontmatter normalizer writes the `tags` and `aliases` lists.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ListStyle {
+    /// `tags: [rust, lsp]`
+    #[default]
+    Inline,
+    /// One `  - item` line per value under the key.
+    Block,
+}
+
+/// Editor-facing options for the language server. The notemancy config owns the vault
+/// itself; these are passed by the client as `initializationOptions` and fall back to
+/// their defaults when absent.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Settings {
+    /// Maximum number of note lines shown in a hover preview.
+    pub hover_preview_lines: usize,
+    /// Show the raw note preview on hover instead of the metadata card.
+    pub hover_full_preview: bool,
+    /// Maximum number of results returned by a workspace symbol search.
+    pub workspace_symbol_limit: usize,
+    /// Folder holding note templates, relative to the vault unless absolute.
+    pub templates_dir: String,
+    /// How completed note links are written.
+    pub wikilink_format: WikilinkFormat,
+    /// Glob patterns for notes left out of completion and workspace symbols, on top of
+    /// the vault's `.gitignore` and `.notemancyignore`.
+    pub ignore: Vec<String>,
+    /// Rank note completions by how recently the note was modified rather than leaving
+    /// the client to sort them alphabetically.
+    pub completion_recency: bool,
+    /// Maximum number of note completions returned at once; the client asks for more as
+    /// the typed text narrows the list.
+    pub completion_limit: usize,
+    /// Symbol kind names shown for headings in the outline and workspace symbols, keyed by
+    /// heading level (`{"1": "file", "2": "module"}`). Levels left out use `namespace`.
+    pub heading_symbol_kinds: HashMap<String, String>,
+    /// Marker the formatter writes for every bullet list item: `-`, `*` or `+`.
+    pub bullet_marker: char,
+    /// Longest run of blank lines the formatter keeps.
+    pub max_blank_lines: usize,
+    /// Have the formatter put exactly one blank line after every heading.
+    pub blank_line_after_heading: bool,
+    /// Have the formatter end documents with a newline.
+    pub ensure_final_newline: bool,
+    /// Where daily notes live in the vault; `{{year}}`, `{{month}}`, `{{day}}` and
+    /// `{{date}}` are filled in with the note's date.
+    pub daily_note_path: String,
+    /// Template new daily notes are created from, by name in the templates folder.
+    pub daily_note_template: String,
+    /// Reading pace used for the reading-time estimate of the document stats.
+    pub reading_words_per_minute: usize,
+    /// List the parents of nested tags as workspace symbols of their own, so that
+    /// `#project/alpha` can also be found as `#project`.
+    pub tag_parent_symbols: bool,
+    /// Keys the frontmatter normalizer puts first, in this order; other keys follow
+    /// alphabetically.
+    pub frontmatter_key_order: Vec<String>,
+    /// Style the frontmatter normalizer rewrites `tags` and `aliases` in.
+    pub frontmatter_list_style: ListStyle,
+    /// Report wiki-links that point at the note they are written in.
+    pub self_link_diagnostics: bool,
+    /// How long after the last edit a changed document's diagnostics are recomputed, in
+    /// milliseconds.
+    pub diagnostics_delay_ms: u64,
+    /// Folder HTML exports are written to, relative to the vault unless absolute. Empty
+    /// writes each export next to its note.
+    pub export_dir: String,
+    /// Heading of the section a merged note's body goes under; `{{title}}` is filled in
+    /// with the merged note's title.
+    pub merge_heading: String,
+    /// Point links to a merged note at its new section rather than the top of the target.
+    pub merge_link_to_section: bool,
+    /// Typed before a date to complete it, such as `@today`; empty turns date completion
+    /// off.
+    pub date_trigger: String,
+    /// How completed dates are written, with the placeholders of `daily_note_path`.
+    pub date_format: String,
+    /// The string a wiki-link opens with.
+    pub link_open_delimiter: String,
+    /// The string a wiki-link closes with.
+    pub link_close_delimiter: String,
+    /// Which side of a wiki-link's `|` is the path: `path_first` or `alias_first`.
+    pub link_order: LinkOrder,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            hover_preview_lines: 20,
+            hover_full_preview: false,
+            workspace_symbol_limit: 200,
+            templates_dir: "templates".to_string(),
+            wikilink_format: WikilinkFormat::default(),
+            ignore: Vec::new(),
+            completion_recency: true,
+            completion_limit: 100,
+            heading_symbol_kinds: HashMap::new(),
+            bullet_marker: '-',
+            max_blank_lines: 1,
+            blank_line_after_heading: true,
+            ensure_final_newline: true,
+            daily_note_path: "journal/{{year}}/{{date}}.md".to_string(),
+            daily_note_template: "daily".to_string(),
+            reading_words_per_minute: 200,
+            tag_parent_symbols: true,
+            frontmatter_key_order: ["title", "date", "tags", "aliases"]
+                .map(String::from)
+                .to_vec(),
+            frontmatter_list_style: ListStyle::default(),
+            self_link_diagnostics: true,
+            diagnostics_delay_ms: 300,
+            export_dir: String::new(),
+            merge_heading: "{{title}}".to_string(),
+            merge_link_to_section: true,
+            date_trigger: "@".to_string(),
+            date_format: "{{date}}".to_string(),
+            link_open_delimiter: "[[".to_string(),
+            link_close_delimiter: "]]".to_string(),
+            link_order: LinkOrder::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Reads settings from the client's initialization options, ignoring unknown keys.
+    /// A malformed option falls back to its default without resetting the others; the
+    /// returned messages name each option rejected and why.
+    pub fn from_initialization_options(options: Option<Value>) -> (Self, Vec<String>) {
+        let mut rejected = Vec::new();
+        let options = match options {
+            None | Some(Value::Null) => return (Settings::default(), rejected),
+            Some(Value::Object(options)) => options,
+            Some(other) => {
+                rejected.push(format!(
+                    "initialization options must be an object, not {}",
+                    other
+                ));
+                return (Settings::default(), rejected);
+            }
+        };
+        // Each option is checked on its own, so one bad value can't take the rest down.
+        let valid: serde_json::Map<String, Value> = options
+            .into_iter()
+            .filter(|(key, value)| {
+                let single = Value::Object([(key.clone(), value.clone())].into_iter().collect());
+                match serde_json::from_value::<Settings>(single) {
+                    Ok(_) => true,
+                    Err(err) => {
+                        rejected.push(format!("ignoring setting {}: {}", key, err));
+                        false
+                    }
+                }
+            })
+            .collect();
+        let settings = serde_json::from_value(Value::Object(valid)).unwrap_or_default();
+        (settings, rejected)
+    }
+
+    /// Loads the ignore rules for `vault_dir` from these settings and the vault's
+    /// ignore files.
+    pub fn ignore_rules(&self, vault_dir: &Path) -> IgnoreRules {
+        IgnoreRules::load(vault_dir, &self.ignore)
+    }
+
+    /// Returns the symbol kind for a heading of `level`, falling back to `NAMESPACE` when
+    /// the level isn't mapped or names an unknown kind.
+    pub fn heading_symbol_kind(&self, level: usize) -> SymbolKind {
+        self.heading_symbol_kinds
+            .get(&level.to_string())
+            .and_then(|name| symbol_kind_named(name))
+            .unwrap_or(SymbolKind::NAMESPACE)
+    }
+
+    /// Resolves the templates folder against the vault.
+    pub fn templates_path(&self, vault_dir: &Path) -> PathBuf {
+        vault_dir.join(&self.templates_dir)
+    }
+
+    /// The formatter rules these settings ask for.
+    pub fn format_options(&self) -> FormatOptions {
+        FormatOptions {
+            bullet: self.bullet_marker,
+            max_blank_lines: self.max_blank_lines,
+            blank_line_after_heading: self.blank_line_after_heading,
+            ensure_final_newline: self.ensure_final_newline,
+        }
+    }
+
+    /// The wiki-link delimiters and order to find and write links with.
+    pub fn delimiters(&self) -> Delimiters {
+        Delimiters {
+            order: self.link_order,
+            ..Delimiters::new(&self.link_open_delimiter, &self.link_close_delimiter)
+        }
+    }
+
+    /// Resolves the export folder against the vault, or `None` to export next to notes.
+    pub fn export_path(&self, vault_dir: &Path) -> Option<PathBuf> {
+        (!self.export_dir.trim().is_empty()).then(|| vault_dir.join(self.export_dir.trim()))
+    }
+}
+
+/// Parses a symbol kind name as written in the LSP specification, ignoring case and
+/// underscores, so `enumMember`, `enum_member` and `EnumMember` are all accepted.
+fn symbol_kind_named(name: &str) -> Option<SymbolKind> {
+    let kind = match name.replace('_', "").to_lowercase().as_str() {
+        "file" => SymbolKind::FILE,
+        "module" => SymbolKind::MODULE,
+        "namespace" => SymbolKind::NAMESPACE,
+        "package" => SymbolKind::PACKAGE,
+        "class" => SymbolKind::CLASS,
+        "method" => SymbolKind::METHOD,
+        "property" => SymbolKind::PROPERTY,
+        "field" => SymbolKind::FIELD,
+        "constructor" => SymbolKind::CONSTRUCTOR,
+        "enum" => SymbolKind::ENUM,
+        "interface" => SymbolKind::INTERFACE,
+        "function" => SymbolKind::FUNCTION,
+        "variable" => SymbolKind::VARIABLE,
+        "constant" => SymbolKind::CONSTANT,
+        "string" => SymbolKind::STRING,
+        "number" => SymbolKind::NUMBER,
+        "boolean" => SymbolKind::BOOLEAN,
+        "array" => SymbolKind::ARRAY,
+        "object" => SymbolKind::OBJECT,
+        "key" => SymbolKind::KEY,
+        "null" => SymbolKind::NULL,
+        "enummember" => SymbolKind::ENUM_MEMBER,
+        "struct" => SymbolKind::STRUCT,
+        "event" => SymbolKind::EVENT,
+        "operator" => SymbolKind::OPERATOR,
+        "typeparameter" => SymbolKind::TYPE_PARAMETER,
+        _ => return None,
+    };
+    Some(kind)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_initialization_options() {
+        let (settings, rejected) = Settings::from_initialization_options(Some(serde_json::json!({
+            "hoverPreviewLines": 5,
+            "wikilinkFormat": "title",
+            "linkOrder": "alias_first",
+            "unknown": true,
+        })));
+        assert_eq!(settings.hover_preview_lines, 5);
+        assert_eq!(settings.wikilink_format, WikilinkFormat::Title);
+        assert_eq!(settings.delimiters().order, LinkOrder::AliasFirst);
+        assert!(rejected.is_empty());
+        assert_eq!(
+            Settings::from_initialization_options(None)
+                .0
+                .hover_preview_lines,
+            20
+        );
+    }
+
+    #[test]
+    fn test_malformed_option_keeps_the_others() {
+        let (settings, rejected) = Settings::from_initialization_options(Some(serde_json::json!({
+            "bulletMarker": "--",
+            "linkOpenDelimiter": "((",
+            "linkCloseDelimiter": "))",
+            "ignore": ["drafts/**"],
+        })));
+        assert_eq!(settings.bullet_marker, '-');
+        assert_eq!(settings.delimiters().open, "((");
+        assert_eq!(settings.ignore, vec!["drafts/**"]);
+        assert_eq!(rejected.len(), 1);
+        assert!(rejected[0].contains("bulletMarker"));
+
+        let (settings, rejected) =
+            Settings::from_initialization_options(Some(serde_json::json!("[[")));
+        assert_eq!(settings.completion_limit, 100);
+        assert_eq!(rejected.len(), 1);
+    }
+
+    #[test]
+    fn test_heading_symbol_kind() {
+        let settings = Settings::from_initialization_options(Some(serde_json::json!({
+            "headingSymbolKinds": { "1": "File", "2": "enum_member", "3": "bogus" },
+        })))
+        .0;
+        assert_eq!(settings.heading_symbol_kind(1), SymbolKind::FILE);
+        assert_eq!(settings.heading_symbol_kind(2), SymbolKind::ENUM_MEMBER);
+        assert_eq!(settings.heading_symbol_kind(3), SymbolKind::NAMESPACE);
+        assert_eq!(settings.heading_symbol_kind(4), SymbolKind::NAMESPACE);
+    }
+}
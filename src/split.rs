@@ -0,0 +1,254 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::Path;
+
+use tower_lsp::lsp_types::{
+    CreateFile, CreateFileOptions, DocumentChangeOperation, DocumentChanges, OneOf,
+    OptionalVersionedTextDocumentIdentifier, Position, ResourceOp, TextDocumentEdit, TextEdit, Url,
+    WorkspaceEdit,
+};
+
+use crate::extract;
+use crate::heading::{self, Heading};
+use crate::link_index::LinkIndex;
+use crate::markdown_context;
+use crate::position::{self, Encoding};
+use crate::vault;
+use crate::wikilink::{self, WikiLink};
+
+/// The command that moves a heading and everything nested below it into a new note. Its
+/// arguments are the note's URI and a position on the heading; it returns
+/// `{"linksRewritten": n}`.
+pub const SPLIT_AT_HEADING_COMMAND: &str = "notemancy.splitAtHeading";
+
+/// The byte range of lines `first..=last` of `text`, the last line's break included.
+fn line_span(text: &str, first: usize, last: usize) -> Range<usize> {
+    let mut offset = 0;
+    let mut start = text.len();
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        if index == first {
+            start = offset;
+        }
+        offset += line.len();
+        if index == last {
+            return start..offset;
+        }
+    }
+    start..text.len()
+}
+
+/// Whether the anchor or block `link` points at, in the note with `text` and `headings`,
+/// lies on one of the `moved` lines.
+fn targets_moved(link: &WikiLink, text: &str, headings: &[Heading], moved: &Range<usize>) -> bool {
+    match (&link.block, &link.anchor) {
+        (Some(id), _) => {
+            markdown_context::find_block(text, id).is_some_and(|(start, _)| moved.contains(&start))
+        }
+        (None, Some(anchor)) => {
+            heading::resolve_anchor(headings, anchor).is_some_and(|h| moved.contains(&h.line))
+        }
+        (None, None) => false,
+    }
+}
+
+/// Builds the edit that splits the section of `text` started by the heading on `line`
+/// into a new note next to `file`, named after the heading. The section runs to the next
+/// heading of the same or a higher level and is replaced by a link to the new note.
+/// Links into the moved part, from this note or, through `index`, from others, are
+/// pointed at the new note; links moved along that point back into this note get its
+/// path. Returns the edit and the number of links rewritten.
+pub fn split_edit(
+    vault_dir: &Path,
+    file: &Path,
+    text: &str,
+    line: usize,
+    index: &LinkIndex,
+    encoding: Encoding,
+) -> Option<(WorkspaceEdit, usize)> {
+    let headings = heading::parse_headings(text);
+    let heading_index = headings.iter().position(|heading| heading.line == line)?;
+    let heading = &headings[heading_index];
+    let last_line = text.lines().count().saturating_sub(1);
+    let end_line = heading::section_end_line(&headings, heading_index, last_line);
+    let moved_lines = heading.line..end_line + 1;
+    let span = line_span(text, heading.line, end_line);
+    let section = span.start..span.start + text[span.clone()].trim_end().len();
+
+    let vpath = vault::virtual_path(vault_dir, file)?;
+    let uri = Url::from_file_path(file).ok()?;
+    let name = extract::suggested_name(&heading.title);
+    let new_file = extract::available_path(file.parent()?, &name);
+    let new_vpath = vault::virtual_path(vault_dir, &new_file)?;
+    let new_uri = Url::from_file_path(&new_file).ok()?;
+    let new_path = |link: &WikiLink| {
+        if link.path.ends_with(".md") {
+            new_vpath.clone()
+        } else {
+            new_vpath
+                .strip_suffix(".md")
+                .unwrap_or(&new_vpath)
+                .to_string()
+        }
+    };
+    let mut rewritten = 0;
+
+    // The moved text, with links to headings it leaves behind given this note's path.
+    let body = &text[section.clone()];
+    let mut moved = String::new();
+    let mut copied = 0;
+    for link in wikilink::find_wikilinks(body, index.delimiters()) {
+        let has_target = link.anchor.is_some() || link.block.is_some();
+        if link.path.is_empty()
+            && has_target
+            && !targets_moved(&link, text, &headings, &moved_lines)
+        {
+            moved.push_str(&body[copied..link.path_range.start]);
+            moved.push_str(vpath.strip_suffix(".md").unwrap_or(&vpath));
+            copied = link.path_range.end;
+            rewritten += 1;
+        }
+    }
+    moved.push_str(&body[copied..]);
+    moved.push('\n');
+
+    let mut edits: BTreeMap<Url, Vec<TextEdit>> = BTreeMap::new();
+    for link in wikilink::find_wikilinks(text, index.delimiters()) {
+        let points_here = link.path.is_empty()
+            || vault::link_matches(&vault::vault_link_path(vault_dir, file, &link.path), &vpath);
+        if section.contains(&link.range.start)
+            || !points_here
+            || !targets_moved(&link, text, &headings, &moved_lines)
+        {
+            continue;
+        }
+        edits.entry(uri.clone()).or_default().push(TextEdit {
+            range: position::offset_range(text, &link.path_range, encoding),
+            new_text: new_path(&link),
+        });
+        rewritten += 1;
+    }
+    for note in index.note_links(&vpath) {
+        if note.uri == uri {
+            continue;
+        }
+        for link in note.links {
+            if targets_moved(&link, text, &headings, &moved_lines) {
+                edits.entry(note.uri.clone()).or_default().push(TextEdit {
+                    range: position::offset_range(&note.content, &link.path_range, encoding),
+                    new_text: new_path(&link),
+                });
+                rewritten += 1;
+            }
+        }
+    }
+    let link = index.delimiters().link(
+        new_vpath.strip_suffix(".md").unwrap_or(&new_vpath),
+        Some(&heading.title),
+    );
+    edits.entry(uri).or_default().push(TextEdit {
+        range: position::offset_range(text, &section, encoding),
+        new_text: link,
+    });
+
+    let mut operations = vec![
+        DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+            uri: new_uri.clone(),
+            options: Some(CreateFileOptions {
+                overwrite: Some(false),
+                ignore_if_exists: Some(false),
+            }),
+            annotation_id: None,
+        })),
+        DocumentChangeOperation::Edit(TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier {
+                uri: new_uri,
+                version: None,
+            },
+            edits: vec![OneOf::Left(TextEdit {
+                range: tower_lsp::lsp_types::Range::new(Position::default(), Position::default()),
+                new_text: moved,
+            })],
+        }),
+    ];
+    operations.extend(edits.into_iter().map(|(uri, edits)| {
+        DocumentChangeOperation::Edit(TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier { uri, version: None },
+            edits: edits.into_iter().map(OneOf::Left).collect(),
+        })
+    }));
+    Some((
+        WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(operations)),
+            ..Default::default()
+        },
+        rewritten,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    use crate::wikilink::Delimiters;
+
+    #[test]
+    fn test_line_span() {
+        let text = "a\nbb\nccc";
+        assert_eq!(line_span(text, 1, 1), 2..5);
+        assert_eq!(line_span(text, 1, 2), 2..8);
+        assert_eq!(line_span(text, 0, 9), 0..8);
+    }
+
+    #[test]
+    fn test_split_edit() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-split-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(&vault_dir).unwrap();
+        let text = "# Guide\nSee [[#Setup]].\n\n## Setup\nSteps, back to [[#Guide]].\n\
+                    ### Linux\nApt.\n\n## Usage\nRun it.\n";
+        fs::write(vault_dir.join("guide.md"), text).unwrap();
+        fs::write(
+            vault_dir.join("other.md"),
+            "[[guide#Linux]] [[guide.md#Setup | setup]] [[guide#Usage]]\n",
+        )
+        .unwrap();
+
+        let (edit, rewritten) = split_edit(
+            &vault_dir,
+            &vault_dir.join("guide.md"),
+            text,
+            3,
+            &LinkIndex::build(&vault_dir, &Delimiters::default()),
+            Encoding::default(),
+        )
+        .unwrap();
+        assert_eq!(rewritten, 4);
+        let Some(DocumentChanges::Operations(operations)) = edit.document_changes else {
+            panic!("expected document change operations");
+        };
+        let DocumentChangeOperation::Op(ResourceOp::Create(create)) = &operations[0] else {
+            panic!("expected the new note to be created first");
+        };
+        assert!(create.uri.path().ends_with("/Setup.md"));
+        let texts = |index: usize| match &operations[index] {
+            DocumentChangeOperation::Edit(edit) => edit
+                .edits
+                .iter()
+                .map(|edit| match edit {
+                    OneOf::Left(edit) => edit.new_text.clone(),
+                    OneOf::Right(edit) => edit.text_edit.new_text.clone(),
+                })
+                .collect::<Vec<_>>(),
+            _ => panic!("expected a text edit"),
+        };
+        assert_eq!(
+            texts(1),
+            vec!["## Setup\nSteps, back to [[guide#Guide]].\n### Linux\nApt.\n"]
+        );
+        assert_eq!(texts(2), vec!["Setup", "[[Setup | Setup]]"]);
+        assert_eq!(texts(3), vec!["Setup", "Setup.md"]);
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+}
@@ -0,0 +1,84 @@
+use serde::Serialize;
+
+use crate::frontmatter;
+use crate::heading;
+use crate::markdown_context;
+use crate::markdown_link;
+use crate::wikilink::{self, Delimiters};
+
+/// The command that counts the words, headings and links of a note. Its argument is the
+/// note's URI; it returns a `DocumentStats` object.
+pub const DOCUMENT_STATS_COMMAND: &str = "notemancy.documentStats";
+
+/// Size and reading-time figures for a note.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentStats {
+    /// Words in the prose, leaving out the frontmatter and fenced code.
+    pub words: usize,
+    /// Characters in that same prose, whitespace included.
+    pub characters: usize,
+    pub headings: usize,
+    /// Wiki-links and markdown links.
+    pub links: usize,
+    /// Minutes needed to read the prose at the configured pace, rounded up.
+    pub reading_minutes: usize,
+}
+
+/// Computes the stats of `text`, whose links use `delimiters`, estimating the reading time at `words_per_minute`.
+pub fn document_stats(
+    text: &str,
+    words_per_minute: usize,
+    delimiters: &Delimiters,
+) -> DocumentStats {
+    let body_start = frontmatter::frontmatter_range(text).map_or(0, |range| range.end);
+    let mut prose = String::new();
+    let mut pos = body_start;
+    for code in markdown_context::fenced_code_ranges(text) {
+        if code.start >= pos {
+            prose.push_str(&text[pos..code.start]);
+            pos = code.end;
+        }
+    }
+    prose.push_str(&text[pos..]);
+
+    // Heading markers, bullets and other lone punctuation aren't words.
+    let words = prose
+        .split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .count();
+    DocumentStats {
+        words,
+        characters: prose.chars().count(),
+        headings: heading::parse_headings(text).len(),
+        links: wikilink::find_wikilinks(text, delimiters).len()
+            + markdown_link::find_markdown_links(text).len(),
+        reading_minutes: words.div_ceil(words_per_minute.max(1)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_stats() {
+        let text = "---\ntitle: Not counted\n---\n# Title\nFour words [[here]] now.\n\
+                    ```\nskipped code words\n```\nSee [docs](d.md).\n";
+        let stats = document_stats(text, 3, &Delimiters::default());
+        assert_eq!(
+            stats,
+            DocumentStats {
+                words: 7,
+                characters: 51,
+                headings: 1,
+                links: 2,
+                reading_minutes: 3,
+            }
+        );
+        assert_eq!(
+            document_stats("", 200, &Delimiters::default()).reading_minutes,
+            0
+        );
+    }
+}
@@ -0,0 +1,765 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tower_lsp::lsp_types::{Location, Url};
+
+use crate::frontmatter;
+use crate::link_index;
+use crate::markdown_context;
+use crate::position::{self, Encoding};
+use crate::vault;
+
+/// An inline `#tag` in a document. The range covers the `#` and the name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InlineTag {
+    pub name: String,
+    pub range: Range<usize>,
+}
+
+/// The command that adds tags to a note's frontmatter, merging them with the tags it
+/// already has. Its arguments are the note's URI and the list of tags; it returns the
+/// number of tags that were new as `{"added": n}`.
+pub const ADD_TAGS_COMMAND: &str = "notemancy.addTags";
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '/')
+}
+
+/// Finds the inline `#tags` in the body of `text`. A tag must follow whitespace, so
+/// headings, `[[note#anchor]]` links and a `#` at the start of a line are not tags.
+/// Frontmatter, fenced code and inline code are skipped.
+pub fn inline_tags(text: &str) -> Vec<InlineTag> {
+    let code_ranges = markdown_context::code_ranges(text);
+    let body_start = frontmatter::frontmatter_range(text).map_or(0, |range| range.end);
+    let mut tags = Vec::new();
+    let mut line_start = 0;
+    for line in text.split_inclusive('\n') {
+        if line_start >= body_start {
+            for (i, c) in line.char_indices() {
+                if c != '#' || !line[..i].ends_with(char::is_whitespace) {
+                    continue;
+                }
+                let name: String = line[i + 1..]
+                    .chars()
+                    .take_while(|c| is_tag_char(*c))
+                    .collect();
+                let name = name.trim_end_matches('/');
+                // Purely numeric names such as issue numbers ("#42") aren't tags.
+                if name.is_empty() || name.chars().all(|c| c.is_ascii_digit()) {
+                    continue;
+                }
+                let start = line_start + i;
+                if code_ranges.iter().any(|code| code.contains(&start)) {
+                    continue;
+                }
+                tags.push(InlineTag {
+                    name: name.to_string(),
+                    range: start..start + 1 + name.len(),
+                });
+            }
+        }
+        line_start += line.len();
+    }
+    tags
+}
+
+/// Returns every tag a note uses, from its frontmatter `tags` and its inline `#tags`,
+/// without duplicates and in order of first appearance.
+pub fn note_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    let frontmatter_tags = frontmatter::list_field(text, "tags")
+        .into_iter()
+        .map(|tag| tag.trim_start_matches('#').to_string());
+    let inline = inline_tags(text).into_iter().map(|tag| tag.name);
+    for tag in frontmatter_tags.chain(inline) {
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// Finds every tag occurrence in `text`: frontmatter tags at their value in the `tags`
+/// field, then inline tags at the `#tag` itself.
+pub fn tag_occurrences(text: &str) -> Vec<InlineTag> {
+    let mut occurrences: Vec<InlineTag> = Vec::new();
+    if let Some(range) = frontmatter::frontmatter_range(text) {
+        let block = &text[range];
+        // Values are searched for after the `tags:` key so that a tag matching another
+        // field's value isn't picked up.
+        let mut search_from = block
+            .find("\ntags:")
+            .map_or(block.len(), |i| i + "\ntags:".len());
+        for name in frontmatter::list_field(text, "tags") {
+            let name = name.trim_start_matches('#').to_string();
+            let Some(found) = block[search_from..].find(name.as_str()) else {
+                continue;
+            };
+            let start = search_from + found;
+            search_from = start + name.len();
+            if !name.is_empty() {
+                occurrences.push(InlineTag {
+                    range: start..start + name.len(),
+                    name,
+                });
+            }
+        }
+    }
+    occurrences.extend(inline_tags(text));
+    occurrences
+}
+
+/// Returns one entry per tag a note uses, located at its first occurrence.
+pub fn first_tag_occurrences(text: &str) -> Vec<InlineTag> {
+    let mut occurrences: Vec<InlineTag> = Vec::new();
+    for tag in tag_occurrences(text) {
+        if occurrences.iter().all(|seen| seen.name != tag.name) {
+            occurrences.push(tag);
+        }
+    }
+    occurrences
+}
+
+/// Whether the tag `name` is `query` or nested below it, as `project/alpha` is below
+/// `project`.
+pub fn tag_matches(query: &str, name: &str) -> bool {
+    let query = query.trim().trim_start_matches('#').trim_end_matches('/');
+    !query.is_empty()
+        && name
+            .strip_prefix(query)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Finds every use of the tag `query`, or of a tag nested below it, across the vault.
+pub fn vault_tag_usages(vault_dir: &Path, query: &str, encoding: Encoding) -> Vec<Location> {
+    let mut locations = Vec::new();
+    for file in vault::collect_markdown_files(vault_dir) {
+        let (Ok(content), Ok(uri)) = (fs::read_to_string(&file), Url::from_file_path(&file)) else {
+            continue;
+        };
+        for tag in tag_occurrences(&content) {
+            if tag_matches(query, &tag.name) {
+                locations.push(Location {
+                    uri: uri.clone(),
+                    range: position::offset_range(&content, &tag.range, encoding),
+                });
+            }
+        }
+    }
+    locations
+}
+
+/// Builds the text change adding `new_tags` to the frontmatter `tags` of `text`, keeping
+/// the tags already there and the style they are written in: an inline `[a, b]` or
+/// `a, b` list is rewritten with the union, a block list gets new `- tag` lines, and a
+/// note without `tags` (or without frontmatter) gets an inline list. Returns the byte
+/// range to replace, its replacement and how many tags were added, or `None` when every
+/// tag is already present.
+pub fn add_tags_change(text: &str, new_tags: &[String]) -> Option<(Range<usize>, String, usize)> {
+    let existing = frontmatter::list_field(text, "tags");
+    let existing: Vec<&str> = existing
+        .iter()
+        .map(|tag| tag.trim_start_matches('#'))
+        .collect();
+    let mut added: Vec<&str> = Vec::new();
+    for tag in new_tags {
+        let tag = tag.trim().trim_start_matches('#');
+        if !tag.is_empty() && !existing.contains(&tag) && !added.contains(&tag) {
+            added.push(tag);
+        }
+    }
+    if added.is_empty() {
+        return None;
+    }
+    let count = added.len();
+
+    let Some(block) = frontmatter::frontmatter_range(text) else {
+        return Some((
+            0..0,
+            format!("---\ntags: [{}]\n---\n", added.join(", ")),
+            count,
+        ));
+    };
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for line in text[block.clone()].split_inclusive('\n') {
+        lines.push((offset, line));
+        offset += line.len();
+    }
+    let Some(index) = lines[1..lines.len() - 1]
+        .iter()
+        .position(|(_, line)| line.starts_with("tags:"))
+        .map(|i| i + 1)
+    else {
+        let (closing, _) = lines[lines.len() - 1];
+        let insert = format!("tags: [{}]\n", added.join(", "));
+        return Some((closing..closing, insert, count));
+    };
+
+    let (start, line) = lines[index];
+    let content = line.trim_end_matches(['\r', '\n']);
+    let value = content["tags:".len()..].trim();
+    if !value.is_empty() {
+        let all: Vec<&str> = existing.iter().copied().chain(added).collect();
+        let rewritten = if value.starts_with('[') {
+            format!("tags: [{}]", all.join(", "))
+        } else {
+            format!("tags: {}", all.join(", "))
+        };
+        return Some((start..start + content.len(), rewritten, count));
+    }
+    // A block list: append after its last item, copying the first item's indentation.
+    let items: Vec<&(usize, &str)> = lines[index + 1..lines.len() - 1]
+        .iter()
+        .take_while(|(_, item)| item.starts_with(char::is_whitespace) || item.starts_with('-'))
+        .collect();
+    let indent = items.first().map_or("  ", |(_, item)| {
+        &item[..item.len() - item.trim_start().len()]
+    });
+    let insert_at = items
+        .last()
+        .map_or(start + line.len(), |(item_start, item)| {
+            item_start + item.len()
+        });
+    let newline = if line.ends_with("\r\n") { "\r\n" } else { "\n" };
+    let insert: String = added
+        .iter()
+        .map(|tag| format!("{}- {}{}", indent, tag, newline))
+        .collect();
+    Some((insert_at..insert_at, insert, count))
+}
+
+/// The command that moves a note's tags between its body and its frontmatter. Its
+/// arguments are the note's URI, then optionally the direction, `toFrontmatter` (the
+/// default) or `toInline`, and whether to remove the tags from where they were. It
+/// returns the edit applied.
+pub const CONSOLIDATE_TAGS_COMMAND: &str = "notemancy.consolidateTags";
+
+/// Which way the consolidate-tags command moves a note's tags.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TagDirection {
+    /// Inline `#tags` are merged into the frontmatter `tags`.
+    ToFrontmatter,
+    /// Frontmatter `tags` are written out as a line of inline `#tags`.
+    ToInline,
+}
+
+impl TagDirection {
+    /// Reads a direction from its command argument name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "toFrontmatter" => Some(TagDirection::ToFrontmatter),
+            "toInline" => Some(TagDirection::ToInline),
+            _ => None,
+        }
+    }
+}
+
+/// The byte range of the frontmatter `tags` field of `text`, its block list items and
+/// line break included.
+fn tags_field_range(text: &str) -> Option<Range<usize>> {
+    let block = frontmatter::frontmatter_range(text)?;
+    let mut lines = Vec::new();
+    let mut offset = block.start;
+    for line in text[block.clone()].split_inclusive('\n') {
+        lines.push((offset, line));
+        offset += line.len();
+    }
+    // The delimiter lines are never fields.
+    let fields = &lines[1..lines.len() - 1];
+    let index = fields
+        .iter()
+        .position(|(_, line)| line.starts_with("tags:"))?;
+    let end = fields[index + 1..]
+        .iter()
+        .find(|(_, item)| !item.starts_with(char::is_whitespace) && !item.starts_with('-'))
+        .map_or(lines[lines.len() - 1].0, |(start, _)| *start);
+    Some(fields[index].0..end)
+}
+
+/// Builds the text changes consolidating the tags of `text` in `direction`. Towards the
+/// frontmatter, the inline tags are merged into `tags` as `add_tags_change` does; towards
+/// the body, the frontmatter tags not already used inline are written on a `Tags:` line
+/// after the frontmatter. With `remove`, the tags are also taken out of where they were:
+/// each inline `#tag` with the space before it, or the whole `tags` field. Returns the
+/// byte ranges to replace with their replacements.
+pub fn consolidate_tags_changes(
+    text: &str,
+    direction: TagDirection,
+    remove: bool,
+) -> Vec<(Range<usize>, String)> {
+    let inline = inline_tags(text);
+    let mut changes = Vec::new();
+    let mut moved: Vec<String> = Vec::new();
+    match direction {
+        TagDirection::ToFrontmatter => {
+            for tag in &inline {
+                if !moved.contains(&tag.name) {
+                    moved.push(tag.name.clone());
+                }
+            }
+            if let Some((range, new_text, _)) = add_tags_change(text, &moved) {
+                changes.push((range, new_text));
+            }
+            if remove {
+                for tag in &inline {
+                    let before = &text[..tag.range.start];
+                    let start = match before.strip_suffix([' ', '\t']) {
+                        Some(rest) => rest.len(),
+                        None => tag.range.start,
+                    };
+                    changes.push((start..tag.range.end, String::new()));
+                }
+            }
+        }
+        TagDirection::ToInline => {
+            for tag in frontmatter::list_field(text, "tags") {
+                let tag = tag.trim_start_matches('#').to_string();
+                if !tag.is_empty() && !moved.contains(&tag) {
+                    moved.push(tag);
+                }
+            }
+            let line: Vec<String> = moved
+                .iter()
+                .filter(|tag| inline.iter().all(|used| used.name != **tag))
+                .map(|tag| format!("#{}", tag))
+                .collect();
+            if !line.is_empty() {
+                let body_start = frontmatter::frontmatter_range(text).map_or(0, |range| range.end);
+                changes.push((
+                    body_start..body_start,
+                    format!("Tags: {}\n", line.join(" ")),
+                ));
+            }
+            if remove {
+                changes.extend(tags_field_range(text).map(|range| (range, String::new())));
+            }
+        }
+    }
+    changes
+}
+
+/// The command that lists every use of a tag across the vault. Its argument is the tag,
+/// with or without `#`; it returns the `Location`s of the uses, nested tags included.
+pub const FIND_TAG_USAGES_COMMAND: &str = "notemancy.findTagUsages";
+
+/// Counts how many notes in the vault use each tag, most used first.
+pub fn vault_tag_counts(vault_dir: &Path) -> Vec<(String, usize)> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for file in vault::collect_markdown_files(vault_dir) {
+        if let Ok(content) = fs::read_to_string(&file) {
+            for tag in note_tags(&content) {
+                *counts.entry(tag).or_default() += 1;
+            }
+        }
+    }
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+/// The command that returns the vault's tag tree for a tag browser, as nested
+/// `{name, tag, notes, children}` nodes.
+pub const TAG_HIERARCHY_COMMAND: &str = "notemancy.tagHierarchy";
+
+/// A node of the tag tree: one segment of a nested tag.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TagNode {
+    /// The segment itself, such as `alpha` in `project/alpha`.
+    pub name: String,
+    /// The full tag, without `#`.
+    pub tag: String,
+    /// Number of notes using the tag or one nested below it.
+    pub notes: usize,
+    pub children: Vec<TagNode>,
+}
+
+/// Builds the tag tree from the tags of each note, `note_tags` giving one list per note.
+/// Nodes are ordered by note count, most used first, then by name.
+pub fn tag_tree(note_tags: impl IntoIterator<Item = Vec<String>>) -> Vec<TagNode> {
+    let mut notes: HashMap<String, usize> = HashMap::new();
+    for tags in note_tags {
+        // Each note counts once per node, however many of its tags sit below it.
+        let mut nodes = BTreeSet::new();
+        for tag in &tags {
+            nodes.extend(tag_ancestors(tag).map(str::to_string));
+            nodes.insert(tag.clone());
+        }
+        for node in nodes {
+            *notes.entry(node).or_default() += 1;
+        }
+    }
+    tag_subtree(&notes, None)
+}
+
+fn tag_subtree(notes: &HashMap<String, usize>, parent: Option<&str>) -> Vec<TagNode> {
+    let mut children: Vec<TagNode> = notes
+        .iter()
+        .filter_map(|(tag, count)| {
+            let name = match parent {
+                Some(parent) => tag.strip_prefix(parent)?.strip_prefix('/')?,
+                None => tag.as_str(),
+            };
+            (!name.contains('/')).then(|| TagNode {
+                name: name.to_string(),
+                tag: tag.clone(),
+                notes: *count,
+                children: tag_subtree(notes, Some(tag)),
+            })
+        })
+        .collect();
+    children.sort_by(|a, b| b.notes.cmp(&a.notes).then_with(|| a.name.cmp(&b.name)));
+    children
+}
+
+/// Builds the tag tree of every note in the vault.
+pub fn vault_tag_tree(vault_dir: &Path) -> Vec<TagNode> {
+    tag_tree(
+        vault::collect_markdown_files(vault_dir)
+            .into_iter()
+            .filter_map(|file| fs::read_to_string(file).ok())
+            .map(|content| note_tags(&content)),
+    )
+}
+
+/// The command listing the notes that share the most tags with a note. Its argument is
+/// the note's URI; it returns `{vpath, title, sharedTags, score}` entries, best first.
+pub const RELATED_NOTES_COMMAND: &str = "notemancy.relatedNotes";
+
+/// How many notes the related-notes command returns.
+pub const RELATED_NOTES_LIMIT: usize = 20;
+
+/// A note sharing tags with another.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelatedNote {
+    pub vpath: String,
+    pub title: String,
+    /// The tags both notes use, in the order this note first uses them.
+    pub shared_tags: Vec<String>,
+    /// Number of shared tags.
+    pub score: usize,
+}
+
+/// Finds the notes among `files` sharing tags with the note `file` whose text is `text`,
+/// the note itself left out. Notes sharing more tags come first, then by virtual path;
+/// at most `limit` are returned.
+pub fn related_notes(
+    vault_dir: &Path,
+    files: &[PathBuf],
+    file: &Path,
+    text: &str,
+    limit: usize,
+) -> Vec<RelatedNote> {
+    let tags: BTreeSet<String> = note_tags(text).into_iter().collect();
+    if tags.is_empty() {
+        return Vec::new();
+    }
+    let mut related: Vec<RelatedNote> = files
+        .iter()
+        .filter(|other| other.as_path() != file)
+        .filter_map(|other| {
+            let content = fs::read_to_string(other).ok()?;
+            let shared_tags: Vec<String> = note_tags(&content)
+                .into_iter()
+                .filter(|tag| tags.contains(tag))
+                .collect();
+            if shared_tags.is_empty() {
+                return None;
+            }
+            Some(RelatedNote {
+                vpath: vault::virtual_path(vault_dir, other)?,
+                title: link_index::note_title(other, &content),
+                score: shared_tags.len(),
+                shared_tags,
+            })
+        })
+        .collect();
+    related.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.vpath.cmp(&b.vpath)));
+    related.truncate(limit);
+    related
+}
+
+/// Returns the parents of a nested tag, outermost first: `a/b/c` has `a` and `a/b`.
+pub fn tag_ancestors(name: &str) -> impl Iterator<Item = &str> {
+    name.match_indices('/').map(move |(i, _)| &name[..i])
+}
+
+/// Lists the segments directly below the tag `parent` in the tag tree built from
+/// `counts`, each with the number of uses of it and everything nested below it, most
+/// used first. `project` has the children `alpha` and `beta` when `project/alpha/design`
+/// and `project/beta` are in use.
+pub fn tag_children(counts: &[(String, usize)], parent: &str) -> Vec<(String, usize)> {
+    let parent = parent.trim_start_matches('#').trim_end_matches('/');
+    let mut children: HashMap<&str, usize> = HashMap::new();
+    for (tag, count) in counts {
+        let Some(rest) = tag
+            .strip_prefix(parent)
+            .and_then(|rest| rest.strip_prefix('/'))
+        else {
+            continue;
+        };
+        let segment = rest.split('/').next().unwrap_or(rest);
+        if !segment.is_empty() {
+            *children.entry(segment).or_default() += count;
+        }
+    }
+    let mut children: Vec<_> = children
+        .into_iter()
+        .map(|(segment, count)| (segment.to_string(), count))
+        .collect();
+    children.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    children
+}
+
+/// If `prefix` (the text of a line up to the cursor) ends with a partial `#tag` at a
+/// word boundary, returns the byte index just after the `#`. A `#` at the start of the
+/// line begins a heading and is ignored.
+pub fn tag_query_start(prefix: &str) -> Option<usize> {
+    let hash = prefix.rfind('#')?;
+    let partial = &prefix[hash + 1..];
+    (prefix[..hash].ends_with(char::is_whitespace) && partial.chars().all(is_tag_char))
+        .then_some(hash + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_tree_note_counts() {
+        let tree = tag_tree([
+            vec![
+                "project/alpha/design".to_string(),
+                "project/beta".to_string(),
+            ],
+            vec!["project/beta".to_string(), "rust".to_string()],
+            vec!["rust".to_string()],
+            vec!["rust".to_string()],
+        ]);
+        let summary = |nodes: &[TagNode]| {
+            nodes
+                .iter()
+                .map(|node| (node.tag.clone(), node.notes))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            summary(&tree),
+            vec![("rust".to_string(), 3), ("project".to_string(), 2)]
+        );
+        let project = &tree[1];
+        assert_eq!(
+            summary(&project.children),
+            vec![
+                ("project/beta".to_string(), 2),
+                ("project/alpha".to_string(), 1)
+            ]
+        );
+        let alpha = &project.children[1];
+        assert_eq!(alpha.name, "alpha");
+        assert_eq!(alpha.children[0].tag, "project/alpha/design");
+        assert!(alpha.children[0].children.is_empty());
+    }
+
+    #[test]
+    fn test_inline_tags() {
+        let text = "---\ntags: [meta]\n---\n# Heading #1\nSome #rust and #lsp/tower text [[a#b]] `#code`\n```\n#not-a-tag\n```\n#start";
+        let names: Vec<_> = inline_tags(text).into_iter().map(|tag| tag.name).collect();
+        assert_eq!(names, vec!["rust", "lsp/tower"]);
+        assert_eq!(note_tags(text), vec!["meta", "rust", "lsp/tower"]);
+    }
+
+    #[test]
+    fn test_tag_query_start() {
+        assert_eq!(tag_query_start("text #ru"), Some(6));
+        assert_eq!(tag_query_start("text #"), Some(6));
+        assert_eq!(tag_query_start("#heading"), None);
+        assert_eq!(tag_query_start("[[note#"), None);
+        assert_eq!(tag_query_start("text #a b"), None);
+    }
+
+    #[test]
+    fn test_tag_tree() {
+        let counts = vec![
+            ("project/alpha/design".to_string(), 3),
+            ("project/alpha".to_string(), 1),
+            ("project/beta".to_string(), 2),
+            ("projects".to_string(), 5),
+        ];
+        assert_eq!(
+            tag_children(&counts, "project/"),
+            vec![("alpha".to_string(), 4), ("beta".to_string(), 2)]
+        );
+        assert_eq!(
+            tag_children(&counts, "#project/alpha"),
+            vec![("design".to_string(), 3)]
+        );
+        let ancestors: Vec<_> = tag_ancestors("a/b/c").collect();
+        assert_eq!(ancestors, vec!["a", "a/b"]);
+    }
+
+    #[test]
+    fn test_first_tag_occurrences() {
+        let text = "---\ntitle: rust\ntags: [rust, lsp]\n---\nA #lsp and #new note, #new again\n";
+        let tags = first_tag_occurrences(text);
+        let found: Vec<_> = tags
+            .iter()
+            .map(|tag| (tag.name.as_str(), &text[tag.range.clone()]))
+            .collect();
+        assert_eq!(
+            found,
+            vec![("rust", "rust"), ("lsp", "lsp"), ("new", "#new")]
+        );
+        assert_eq!(tags[0].range.start, text.find("[rust").unwrap() + 1);
+        assert_eq!(tags[2].range.start, text.find("#new").unwrap());
+    }
+
+    #[test]
+    fn test_consolidate_tags_changes() {
+        let apply = |text: &str, direction, remove| {
+            let mut changes = consolidate_tags_changes(text, direction, remove);
+            changes.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+            let mut text = text.to_string();
+            for (range, new_text) in changes {
+                text.replace_range(range, &new_text);
+            }
+            text
+        };
+        let text = "---\ntags: [rust]\n---\nA #lsp note about #rust and #lsp.\n";
+        assert_eq!(
+            apply(text, TagDirection::ToFrontmatter, false),
+            "---\ntags: [rust, lsp]\n---\nA #lsp note about #rust and #lsp.\n"
+        );
+        assert_eq!(
+            apply(text, TagDirection::ToFrontmatter, true),
+            "---\ntags: [rust, lsp]\n---\nA note about and.\n"
+        );
+
+        let text = "---\ntitle: T\ntags:\n  - rust\n  - lsp\ndate: x\n---\nSee #rust.\n";
+        assert_eq!(
+            apply(text, TagDirection::ToInline, false),
+            format!(
+                "{}Tags: #lsp\nSee #rust.\n",
+                &text[..text.find("See").unwrap()]
+            )
+        );
+        assert_eq!(
+            apply(text, TagDirection::ToInline, true),
+            "---\ntitle: T\ndate: x\n---\nTags: #lsp\nSee #rust.\n"
+        );
+        assert_eq!(
+            apply("No tags.\n", TagDirection::ToInline, true),
+            "No tags.\n"
+        );
+        assert_eq!(
+            TagDirection::from_name("toInline"),
+            Some(TagDirection::ToInline)
+        );
+    }
+
+    #[test]
+    fn test_add_tags_change() {
+        let apply = |text: &str, tags: &[&str]| {
+            let tags: Vec<String> = tags.iter().map(|tag| tag.to_string()).collect();
+            add_tags_change(text, &tags).map(|(range, new_text, count)| {
+                let mut text = text.to_string();
+                text.replace_range(range, &new_text);
+                (text, count)
+            })
+        };
+        assert_eq!(
+            apply("---\ntags: [rust, lsp]\n---\n", &["lsp", "#notes"]),
+            Some(("---\ntags: [rust, lsp, notes]\n---\n".to_string(), 1))
+        );
+        assert_eq!(
+            apply("---\ntags: rust\n---\n", &["a"]),
+            Some(("---\ntags: rust, a\n---\n".to_string(), 1))
+        );
+        assert_eq!(
+            apply("---\ntags:\n    - rust\ndate: x\n---\n", &["a", "b"]),
+            Some((
+                "---\ntags:\n    - rust\n    - a\n    - b\ndate: x\n---\n".to_string(),
+                2
+            ))
+        );
+        assert_eq!(
+            apply("---\ntitle: x\n---\nBody", &["a"]),
+            Some(("---\ntitle: x\ntags: [a]\n---\nBody".to_string(), 1))
+        );
+        assert_eq!(
+            apply("Body", &["a"]),
+            Some(("---\ntags: [a]\n---\nBody".to_string(), 1))
+        );
+        assert_eq!(apply("---\ntags: [a]\n---\n", &["a"]), None);
+    }
+
+    #[test]
+    fn test_vault_tag_usages() {
+        assert!(tag_matches("#project", "project/alpha"));
+        assert!(tag_matches("project", "project"));
+        assert!(!tag_matches("project", "projects"));
+        assert!(!tag_matches("", "project"));
+
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-tag-usages-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(&vault_dir).unwrap();
+        fs::write(
+            vault_dir.join("a.md"),
+            "---\ntags: [project]\n---\nMore #project and #project/alpha, not #projects\n",
+        )
+        .unwrap();
+        fs::write(vault_dir.join("b.md"), "Nothing here\n").unwrap();
+
+        let mut found: Vec<_> = vault_tag_usages(&vault_dir, "project", Encoding::default())
+            .into_iter()
+            .map(|location| (location.range.start.line, location.range.start.character))
+            .collect();
+        found.sort();
+        assert_eq!(found, vec![(1, 7), (3, 5), (3, 18)]);
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+
+    #[test]
+    fn test_related_notes() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-related-notes-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(&vault_dir).unwrap();
+        let text = "---\ntags: [rust, lsp]\n---\nAlso #editor\n";
+        fs::write(vault_dir.join("self.md"), text).unwrap();
+        fs::write(vault_dir.join("one.md"), "Only #rust\n").unwrap();
+        fs::write(
+            vault_dir.join("both.md"),
+            "---\ntitle: Both\ntags: [editor]\n---\nAlso #rust #cooking\n",
+        )
+        .unwrap();
+        fs::write(vault_dir.join("none.md"), "Soup #cooking\n").unwrap();
+        fs::write(vault_dir.join("also.md"), "About #lsp\n").unwrap();
+        let files = vault::collect_markdown_files(&vault_dir);
+
+        let related = related_notes(&vault_dir, &files, &vault_dir.join("self.md"), text, 10);
+        let found: Vec<_> = related
+            .iter()
+            .map(|note| (note.vpath.as_str(), note.title.as_str(), note.score))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("both.md", "Both", 2),
+                ("also.md", "also", 1),
+                ("one.md", "one", 1)
+            ]
+        );
+        assert_eq!(related[0].shared_tags, vec!["editor", "rust"]);
+        assert_eq!(
+            related_notes(&vault_dir, &files, &vault_dir.join("self.md"), text, 1).len(),
+            1
+        );
+        assert!(related_notes(&vault_dir, &files, &vault_dir.join("self.md"), "", 10).is_empty());
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+}
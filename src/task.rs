@@ -0,0 +1,83 @@
+use crate::frontmatter;
+use crate::markdown_context::FenceState;
+
+/// A checkbox list item such as `- [ ] write tests` or `1. [x] ship it`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Task {
+    /// The text after the checkbox, trimmed.
+    pub text: String,
+    /// Whether the box is checked (`[x]` or `[X]`).
+    pub done: bool,
+    /// Zero-based line number of the item.
+    pub line: usize,
+    /// Length of the line in bytes, without its line break.
+    pub line_len: usize,
+}
+
+/// Returns the checkbox held by a list item line, and the text after it.
+fn checkbox(line: &str) -> Option<(bool, &str)> {
+    let item = line.trim_start();
+    let rest = match item.strip_prefix(['-', '*', '+']) {
+        Some(rest) => rest,
+        None => {
+            let digits = item.len() - item.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            if digits == 0 {
+                return None;
+            }
+            item[digits..].strip_prefix(['.', ')'])?
+        }
+    };
+    let rest = rest.strip_prefix([' ', '\t'])?.trim_start();
+    let done = match rest.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    let text = &rest[3..];
+    (text.is_empty() || text.starts_with([' ', '\t'])).then_some((done, text.trim()))
+}
+
+/// Finds the task items of `text`, skipping the frontmatter and fenced code. Items
+/// without any text after the checkbox are left out.
+pub fn find_tasks(text: &str) -> Vec<Task> {
+    let body_start = frontmatter::frontmatter_range(text).map_or(0, |range| range.end);
+    let skipped_lines = text[..body_start].lines().count();
+    let mut fence = FenceState::default();
+    text[body_start..]
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !fence.update(line))
+        .filter_map(|(index, line)| {
+            let (done, task) = checkbox(line)?;
+            (!task.is_empty()).then(|| Task {
+                text: task.to_string(),
+                done,
+                line: skipped_lines + index,
+                line_len: line.len(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_tasks() {
+        let text = "---\ntitle: Todo\n---\n- [ ] write tests\n  * [x] nested done\n\
+                    3. [X] numbered\n- [] not a task\n- [ ]\n[x] no marker\n```\n- [ ] code\n```\n";
+        let tasks: Vec<_> = find_tasks(text)
+            .into_iter()
+            .map(|task| (task.line, task.text, task.done))
+            .collect();
+        assert_eq!(
+            tasks,
+            vec![
+                (3, "write tests".to_string(), false),
+                (4, "nested done".to_string(), true),
+                (5, "numbered".to_string(), true),
+            ]
+        );
+    }
+}
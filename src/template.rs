@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Command, Position, TextEdit, Url,
+    WorkspaceEdit,
+};
+
+use crate::frontmatter;
+use crate::position::{self, Encoding};
+
+/// The command that inserts a template into a note. Its arguments are the note's URI
+/// and the template name.
+pub const INSERT_TEMPLATE_COMMAND: &str = "notemancy.insertTemplate";
+
+/// Lists the templates in `templates_dir` by name, that is by file stem, sorted.
+pub fn list_templates(templates_dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(templates_dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "md"))
+        .filter_map(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Reads the template called `name` from `templates_dir`.
+pub fn read_template(templates_dir: &Path, name: &str) -> Option<String> {
+    fs::read_to_string(templates_dir.join(format!("{}.md", name))).ok()
+}
+
+/// Fills in the `{{title}}` and `{{date}}` placeholders of a template.
+pub fn expand(template: &str, title: &str, date: &str) -> String {
+    template
+        .replace("{{title}}", title)
+        .replace("{{date}}", date)
+}
+
+/// Builds the edit inserting `expanded` at the top of the note, below its frontmatter
+/// when it has one.
+pub fn insert_edit(uri: &Url, text: &str, expanded: &str, encoding: Encoding) -> WorkspaceEdit {
+    let position = frontmatter::frontmatter_range(text).map_or(Position::default(), |range| {
+        position::offset_to_position(text, range.end, encoding)
+    });
+    let mut new_text = expanded.to_string();
+    if !new_text.ends_with('\n') {
+        new_text.push('\n');
+    }
+    let mut changes = HashMap::new();
+    changes.insert(
+        uri.clone(),
+        vec![TextEdit {
+            range: tower_lsp::lsp_types::Range {
+                start: position,
+                end: position,
+            },
+            new_text,
+        }],
+    );
+    WorkspaceEdit {
+        changes: Some(changes),
+        ..Default::default()
+    }
+}
+
+/// Offers one source action per template, each running the insert-template command.
+pub fn template_actions(uri: &Url, templates: &[String]) -> Vec<CodeActionOrCommand> {
+    templates
+        .iter()
+        .map(|name| {
+            let title = format!("Insert template: {}", name);
+            CodeActionOrCommand::CodeAction(CodeAction {
+                title: title.clone(),
+                kind: Some(CodeActionKind::SOURCE),
+                command: Some(Command {
+                    title,
+                    command: INSERT_TEMPLATE_COMMAND.to_string(),
+                    arguments: Some(vec![
+                        serde_json::Value::String(uri.to_string()),
+                        serde_json::Value::String(name.clone()),
+                    ]),
+                }),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_and_insert_below_frontmatter() {
+        let expanded = expand("## {{title}}\nCreated {{date}}", "Plans", "2024-05-01");
+        assert_eq!(expanded, "## Plans\nCreated 2024-05-01");
+
+        let uri = Url::parse("file:///vault/plans.md").unwrap();
+        let edit = insert_edit(
+            &uri,
+            "---\ntitle: Plans\n---\nBody\n",
+            &expanded,
+            Encoding::default(),
+        );
+        let edits = &edit.changes.unwrap()[&uri];
+        assert_eq!(edits[0].range.start, Position::new(3, 0));
+        assert_eq!(edits[0].new_text, "## Plans\nCreated 2024-05-01\n");
+
+        let edit = insert_edit(&uri, "Body\n", "Top", Encoding::default());
+        assert_eq!(
+            edit.changes.unwrap()[&uri][0].range.start,
+            Position::new(0, 0)
+        );
+    }
+
+    #[test]
+    fn test_list_templates() {
+        let dir = std::env::temp_dir().join("notemancy-lsp-templates-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("weekly.md"), "").unwrap();
+        fs::write(dir.join("daily.md"), "").unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        assert_eq!(list_templates(&dir), vec!["daily", "weekly"]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::{TextEdit, Url, WorkspaceEdit};
+
+use crate::heading;
+use crate::position::{self, Encoding};
+use crate::wikilink::{self, Delimiters};
+
+/// The command that inserts or refreshes a note's table of contents. Its arguments are
+/// the note's URI and the cursor position.
+pub const INSERT_TOC_COMMAND: &str = "notemancy.insertTableOfContents";
+
+const TOC_START: &str = "<!-- toc -->";
+const TOC_END: &str = "<!-- /toc -->";
+
+/// Builds the table of contents of `text` between its markers: a bullet list of
+/// `[[#Heading]]` links, indented by heading level. Repeated heading names link to their
+/// later occurrences as `[[#Heading#2]]` and so on.
+pub fn toc_text(text: &str, delimiters: &Delimiters) -> String {
+    let headings = heading::parse_headings(text);
+    let min_level = headings.iter().map(|h| h.level).min().unwrap_or(1);
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut toc = format!("{}\n", TOC_START);
+    for heading in &headings {
+        let occurrence = seen
+            .entry(wikilink::normalize_heading(&heading.title))
+            .or_default();
+        *occurrence += 1;
+        let anchor = match *occurrence {
+            1 => heading.title.clone(),
+            n => format!("{}#{}", heading.title, n),
+        };
+        toc.push_str(&"  ".repeat(heading.level - min_level));
+        toc.push_str(&format!("- {}\n", delimiters.wrap(&format!("#{}", anchor))));
+    }
+    toc.push_str(TOC_END);
+    toc
+}
+
+/// Returns the byte range of an existing table of contents, markers included.
+fn existing_toc(text: &str) -> Option<std::ops::Range<usize>> {
+    let start = text.find(TOC_START)?;
+    let end = start + text[start..].find(TOC_END)? + TOC_END.len();
+    Some(start..end)
+}
+
+/// Builds the edit that replaces the note's existing table of contents with a fresh one
+/// or, when it has none, inserts one at `cursor`.
+pub fn toc_edit(
+    uri: &Url,
+    text: &str,
+    cursor: usize,
+    delimiters: &Delimiters,
+    encoding: Encoding,
+) -> WorkspaceEdit {
+    let (range, new_text) = match existing_toc(text) {
+        Some(range) => (range, toc_text(text, delimiters)),
+        None => (cursor..cursor, format!("{}\n", toc_text(text, delimiters))),
+    };
+    let mut changes = HashMap::new();
+    changes.insert(
+        uri.clone(),
+        vec![TextEdit {
+            range: position::offset_range(text, &range, encoding),
+            new_text,
+        }],
+    );
+    WorkspaceEdit {
+        changes: Some(changes),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower_lsp::lsp_types::Position;
+
+    #[test]
+    fn test_toc_text() {
+        let text = "## Intro\n### Details\n## Notes\n## Notes\n";
+        assert_eq!(
+            toc_text(text, &Delimiters::default()),
+            "<!-- toc -->\n- [[#Intro]]\n  - [[#Details]]\n- [[#Notes]]\n- [[#Notes#2]]\n<!-- /toc -->"
+        );
+    }
+
+    #[test]
+    fn test_toc_edit_updates_in_place() {
+        let uri = Url::parse("file:///vault/note.md").unwrap();
+        let text = "# Title\n<!-- toc -->\n- [[#Old]]\n<!-- /toc -->\n## New\n";
+        let edit = toc_edit(&uri, text, 0, &Delimiters::default(), Encoding::default());
+        let edits = &edit.changes.unwrap()[&uri];
+        assert_eq!(edits[0].range.start, Position::new(1, 0));
+        assert_eq!(edits[0].range.end, Position::new(3, 13));
+        assert_eq!(
+            edits[0].new_text,
+            "<!-- toc -->\n- [[#Title]]\n  - [[#New]]\n<!-- /toc -->"
+        );
+
+        let edit = toc_edit(
+            &uri,
+            "# Title\n\n",
+            8,
+            &Delimiters::default(),
+            Encoding::default(),
+        );
+        let edits = &edit.changes.unwrap()[&uri];
+        assert_eq!(edits[0].range.start, Position::new(1, 0));
+        assert!(edits[0].new_text.ends_with("<!-- /toc -->\n"));
+    }
+}
@@ -0,0 +1,533 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use rusqlite::Connection;
+use tower_lsp::lsp_types::Url;
+
+use crate::ignore::IgnoreRules;
+use crate::wikilink::WikiLink;
+
+/// The wiki-links in one note that point at a particular target.
+pub struct NoteLinks {
+    pub uri: Url,
+    /// The note text the link ranges refer to.
+    pub content: String,
+    pub links: Vec<WikiLink>,
+}
+
+/// Recursively collects markdown files from `dir`, deduplicating based on their canonical path.
+pub fn collect_markdown_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut seen = HashSet::new();
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                files.extend(collect_markdown_files(&path));
+            } else if let Some(ext) = path.extension() {
+                if ext.to_string_lossy().eq_ignore_ascii_case("md") {
+                    // Canonicalize to resolve symlinks.
+                    if let Ok(canonical) = fs::canonicalize(&path) {
+                        if seen.insert(canonical) {
+                            files.push(path);
+                        }
+                    } else {
+                        files.push(path);
+                    }
+                }
+            }
+        }
+    }
+    files
+}
+
+/// Collects the vault's markdown files, leaving out those matched by `rules`.
+pub fn collect_notes(vault_dir: &Path, rules: &IgnoreRules) -> Vec<PathBuf> {
+    collect_markdown_files(vault_dir)
+        .into_iter()
+        .filter(|file| virtual_path(vault_dir, file).is_none_or(|vpath| !rules.is_ignored(&vpath)))
+        .collect()
+}
+
+/// Writes the path separators of a link or stored virtual path as `/`, so notes authored
+/// on Windows compare equal to the same notes written elsewhere.
+pub fn normalize_separators(path: &str) -> String {
+    path.replace('\\', "/")
+}
+
+/// Resolves a wiki-link path against the vault directory. Links may omit the `.md` extension.
+/// Absolute paths and paths climbing out of the vault resolve to nothing.
+pub fn resolve_link_target(vault_dir: &Path, link_path: &str) -> Option<PathBuf> {
+    if link_path.is_empty() {
+        return None;
+    }
+    let candidate = normalize_path(&vault_dir.join(normalize_separators(link_path)));
+    if !candidate.starts_with(normalize_path(vault_dir)) {
+        return None;
+    }
+    if candidate.is_file() {
+        return Some(candidate);
+    }
+    let mut with_ext = candidate.into_os_string();
+    with_ext.push(".md");
+    let with_ext = PathBuf::from(with_ext);
+    with_ext.is_file().then_some(with_ext)
+}
+
+/// A note indexed in the notemancy database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Page {
+    pub file: PathBuf,
+    pub title: String,
+    /// Other names declared in the note's frontmatter `aliases`.
+    pub aliases: Vec<String>,
+}
+
+/// The file a `vpath` stored in the notemancy database stands for. Stored vpaths start
+/// with the vault dir; joining keeps them as they are.
+fn stored_file(vault_dir: &Path, vpath: &str) -> PathBuf {
+    let vpath = normalize_separators(vpath);
+    let relative = Path::new(&vpath)
+        .strip_prefix(vault_dir)
+        .unwrap_or(Path::new(&vpath));
+    vault_dir.join(relative)
+}
+
+/// Reads every indexed note and its title from the notemancy database.
+pub fn indexed_pages(vault_dir: &Path) -> Vec<Page> {
+    stored_pages(&notemancy_core::db::crud::global().conn, vault_dir)
+}
+
+/// Reads every note and its title from the `pagetable` of `conn`.
+fn stored_pages(conn: &Connection, vault_dir: &Path) -> Vec<Page> {
+    let Ok(mut stmt) = conn.prepare("SELECT vpath, title FROM pagetable") else {
+        return Vec::new();
+    };
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    });
+    let Ok(rows) = rows else {
+        return Vec::new();
+    };
+    rows.flatten()
+        .map(|(vpath, title)| Page {
+            file: stored_file(vault_dir, &vpath),
+            title,
+            aliases: Vec::new(),
+        })
+        .collect()
+}
+
+/// Brings the notemancy database behind `conn` in line with `notes`, the vault's files
+/// and their titles: rows for files of `vault_dir` that are gone are dropped, retitled notes are
+/// updated and new ones inserted. It all happens in one transaction, so on an error the
+/// database is left as it was. Returns how many rows changed.
+pub fn reindex_pages(
+    conn: &Connection,
+    vault_dir: &Path,
+    notes: &[(PathBuf, String)],
+) -> Result<usize, String> {
+    let transaction = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let stored: HashMap<PathBuf, (String, String)> = {
+        let mut stmt = transaction
+            .prepare("SELECT vpath, title FROM pagetable")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(|e| e.to_string())?;
+        let mut stored = HashMap::new();
+        for row in rows {
+            let (vpath, title) = row.map_err(|e| e.to_string())?;
+            stored.insert(stored_file(vault_dir, &vpath), (vpath, title));
+        }
+        stored
+    };
+    let current: HashSet<&Path> = notes.iter().map(|(file, _)| file.as_path()).collect();
+    let mut changed = 0;
+    for (file, (vpath, _)) in &stored {
+        if file.starts_with(vault_dir) && !current.contains(file.as_path()) {
+            changed += transaction
+                .execute("DELETE FROM pagetable WHERE vpath = ?1", [vpath])
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    for (file, title) in notes {
+        changed += match stored.get(file) {
+            Some((_, stored_title)) if stored_title == title => 0,
+            Some((vpath, _)) => transaction
+                .execute(
+                    "UPDATE pagetable SET title = ?2 WHERE vpath = ?1",
+                    [vpath.as_str(), title.as_str()],
+                )
+                .map_err(|e| e.to_string())?,
+            None => transaction
+                .execute(
+                    "INSERT INTO pagetable (vpath, title) VALUES (?1, ?2)",
+                    [file.to_string_lossy().as_ref(), title.as_str()],
+                )
+                .map_err(|e| e.to_string())?,
+        };
+    }
+    transaction.commit().map_err(|e| e.to_string())?;
+    Ok(changed)
+}
+
+/// Attaches the frontmatter `aliases` known for each file to `pages`. Files with aliases
+/// that the database hasn't indexed yet are added, titled by their file name.
+pub fn with_aliases(mut pages: Vec<Page>, aliases: &HashMap<PathBuf, Vec<String>>) -> Vec<Page> {
+    for page in &mut pages {
+        if let Some(names) = aliases.get(&page.file) {
+            page.aliases = names.clone();
+        }
+    }
+    for (file, names) in aliases {
+        if !names.is_empty() && pages.iter().all(|page| page.file != *file) {
+            pages.push(Page {
+                file: file.clone(),
+                title: file
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+                aliases: names.clone(),
+            });
+        }
+    }
+    pages
+}
+
+/// Returns the pages called `name`, by title or by one of their aliases, ignoring case
+/// and surrounding whitespace.
+pub fn pages_named<'a>(pages: &'a [Page], name: &str) -> Vec<&'a Page> {
+    let name = name.trim().to_lowercase();
+    let matches = |candidate: &String| candidate.trim().to_lowercase() == name;
+    pages
+        .iter()
+        .filter(|page| matches(&page.title) || page.aliases.iter().any(matches))
+        .collect()
+}
+
+/// Resolves a wiki-link path like `resolve_link_target`, falling back to the note whose
+/// title or alias is `link_path` when no file matches. A name shared by several notes
+/// resolves to nothing.
+pub fn resolve_link(vault_dir: &Path, link_path: &str, pages: &[Page]) -> Option<PathBuf> {
+    resolve_link_target(vault_dir, link_path).or_else(|| {
+        match pages_named(pages, link_path).as_slice() {
+            [page] => Some(page.file.clone()),
+            _ => None,
+        }
+    })
+}
+
+/// Rewrites a link path written relative to the note `from_file`'s folder, such as
+/// `./sibling` or `../other/note`, as a path from the vault root. Other paths, and relative
+/// ones that climb out of the vault, are returned unchanged.
+pub fn vault_link_path(vault_dir: &Path, from_file: &Path, link_path: &str) -> String {
+    let path = normalize_separators(link_path.trim());
+    if !path.starts_with("./") && !path.starts_with("../") {
+        return link_path.to_string();
+    }
+    let from_dir = from_file
+        .parent()
+        .and_then(|dir| dir.strip_prefix(vault_dir).ok())
+        .unwrap_or(Path::new(""));
+    // Walk the segments to check the path never climbs above the vault root.
+    let mut depth = from_dir.components().count();
+    for part in path.split('/') {
+        match part {
+            ".." => match depth.checked_sub(1) {
+                Some(parent) => depth = parent,
+                None => return link_path.to_string(),
+            },
+            "." | "" => {}
+            _ => depth += 1,
+        }
+    }
+    normalize_separators(&normalize_path(&from_dir.join(&path)).to_string_lossy())
+}
+
+/// Resolves a wiki-link written in `from_file` like `resolve_link`, first taking `./` and
+/// `../` paths relative to that note's folder.
+pub fn resolve_note_link(
+    vault_dir: &Path,
+    from_file: &Path,
+    link_path: &str,
+    pages: &[Page],
+) -> Option<PathBuf> {
+    resolve_link(
+        vault_dir,
+        &vault_link_path(vault_dir, from_file, link_path),
+        pages,
+    )
+}
+
+/// Returns the virtual path of `file`: its path relative to the vault, with forward
+/// slashes, as it is written inside wiki-links.
+pub fn virtual_path(vault_dir: &Path, file: &Path) -> Option<String> {
+    let relative = file.strip_prefix(vault_dir).ok()?;
+    Some(normalize_separators(&relative.to_string_lossy()))
+}
+
+/// Resolves `.` and `..` components without touching the filesystem.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Builds the relative path, with forward slashes, that leads from the directory
+/// `from_dir` to `to`, as used by markdown links.
+pub fn relative_path(from_dir: &Path, to: &Path) -> String {
+    let from: Vec<_> = from_dir.components().collect();
+    let to: Vec<_> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut parts: Vec<String> = vec!["..".to_string(); from.len() - common];
+    parts.extend(
+        to[common..]
+            .iter()
+            .map(|part| part.as_os_str().to_string_lossy().into_owned()),
+    );
+    parts.join("/")
+}
+
+/// Returns true if a wiki-link written as `link_path` points at the note `vpath`.
+pub fn link_matches(link_path: &str, vpath: &str) -> bool {
+    let link_path = normalize_separators(link_path);
+    let vpath = normalize_separators(vpath);
+    !link_path.is_empty()
+        && (link_path == vpath || vpath.strip_suffix(".md") == Some(link_path.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reindex_pages() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE pagetable (vpath TEXT, title TEXT)", [])
+            .unwrap();
+        let vault_dir = Path::new("/vault");
+        let other_dir = Path::new("/other");
+        let titles = |dir: &Path| {
+            let mut titles: Vec<_> = stored_pages(&conn, dir)
+                .into_iter()
+                .filter(|page| page.file.starts_with(dir))
+                .map(|page| (page.file, page.title))
+                .collect();
+            titles.sort();
+            titles
+        };
+        let note = |name: &str, title: &str| (vault_dir.join(name), title.to_string());
+
+        let other = vec![(other_dir.join("kept.md"), "Kept".to_string())];
+        assert_eq!(reindex_pages(&conn, other_dir, &other), Ok(1));
+        let first = vec![note("a.md", "A"), note("b.md", "B")];
+        assert_eq!(reindex_pages(&conn, vault_dir, &first), Ok(2));
+        assert_eq!(reindex_pages(&conn, vault_dir, &first), Ok(0));
+
+        // One retitled, one gone and one new.
+        let second = vec![note("a.md", "Renamed"), note("c.md", "C")];
+        assert_eq!(reindex_pages(&conn, vault_dir, &second), Ok(3));
+        assert_eq!(titles(vault_dir), second);
+        assert_eq!(titles(other_dir), other);
+
+        assert_eq!(reindex_pages(&conn, vault_dir, &[]), Ok(2));
+        assert_eq!(reindex_pages(&conn, other_dir, &[]), Ok(1));
+        assert!(titles(vault_dir).is_empty());
+    }
+
+    #[test]
+    fn test_link_matches() {
+        assert!(link_matches("notes/rust.md", "notes/rust.md"));
+        assert!(link_matches("notes/rust", "notes/rust.md"));
+        assert!(!link_matches("rust", "notes/rust.md"));
+        assert!(!link_matches("", "notes/rust.md"));
+    }
+
+    #[test]
+    fn test_mixed_separators() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-separators-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(vault_dir.join("folder/sub")).unwrap();
+        fs::write(vault_dir.join("folder/sub/note.md"), "").unwrap();
+
+        let expected = Some(vault_dir.join("folder/sub/note.md"));
+        assert_eq!(
+            resolve_link_target(&vault_dir, "folder\\sub\\note"),
+            expected
+        );
+        assert_eq!(
+            resolve_link_target(&vault_dir, "folder/sub\\note.md"),
+            expected
+        );
+        assert!(link_matches("folder\\sub/note", "folder/sub/note.md"));
+        assert!(link_matches("folder/sub/note", "folder\\sub\\note.md"));
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+
+    #[test]
+    fn test_links_leaving_the_vault() {
+        let root = std::env::temp_dir().join("notemancy-lsp-vault-escape-test");
+        let _ = fs::remove_dir_all(&root);
+        let vault_dir = root.join("vault");
+        fs::create_dir_all(&vault_dir).unwrap();
+        fs::write(root.join("secret.md"), "outside").unwrap();
+        fs::write(vault_dir.join("inside.md"), "").unwrap();
+        let from = vault_dir.join("note.md");
+
+        assert_eq!(resolve_link_target(&vault_dir, "../secret"), None);
+        assert_eq!(resolve_note_link(&vault_dir, &from, "../secret", &[]), None);
+        let absolute = root.join("secret.md");
+        assert_eq!(
+            resolve_link_target(&vault_dir, &absolute.to_string_lossy()),
+            None
+        );
+        assert_eq!(
+            resolve_link_target(&vault_dir, "sub/../inside"),
+            Some(vault_dir.join("inside.md"))
+        );
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn test_relative_links() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-relative-links-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(vault_dir.join("projects/alpha")).unwrap();
+        fs::create_dir_all(vault_dir.join("areas")).unwrap();
+        fs::write(vault_dir.join("projects/alpha/sibling.md"), "").unwrap();
+        fs::write(vault_dir.join("areas/health.md"), "").unwrap();
+        let from = vault_dir.join("projects/alpha/plan.md");
+
+        assert_eq!(
+            vault_link_path(&vault_dir, &from, "./sibling"),
+            "projects/alpha/sibling"
+        );
+        assert_eq!(
+            vault_link_path(&vault_dir, &from, "../../areas/./health.md"),
+            "areas/health.md"
+        );
+        assert_eq!(
+            vault_link_path(&vault_dir, &from, "areas/health"),
+            "areas/health"
+        );
+        assert_eq!(
+            vault_link_path(&vault_dir, &from, "../../../outside"),
+            "../../../outside"
+        );
+        assert_eq!(
+            resolve_note_link(&vault_dir, &from, "./sibling", &[]),
+            Some(vault_dir.join("projects/alpha/sibling.md"))
+        );
+        assert_eq!(
+            resolve_note_link(&vault_dir, &from, "..\\..\\areas\\health", &[]),
+            Some(vault_dir.join("areas/health.md"))
+        );
+        assert_eq!(
+            resolve_note_link(&vault_dir, &from, "../sibling", &[]),
+            None
+        );
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+
+    #[test]
+    fn test_virtual_path() {
+        let vault = Path::new("/vault");
+        assert_eq!(
+            virtual_path(vault, Path::new("/vault/notes/rust.md")).as_deref(),
+            Some("notes/rust.md")
+        );
+        assert_eq!(virtual_path(vault, Path::new("/elsewhere/a.md")), None);
+    }
+
+    #[test]
+    fn test_relative_path() {
+        let from = Path::new("/vault/notes/rust");
+        assert_eq!(
+            relative_path(from, Path::new("/vault/notes/rust/a.md")),
+            "a.md"
+        );
+        assert_eq!(
+            relative_path(from, Path::new("/vault/ideas/b.md")),
+            "../../ideas/b.md"
+        );
+        assert_eq!(
+            normalize_path(Path::new("/vault/notes/./rust/../b.md")),
+            Path::new("/vault/notes/b.md")
+        );
+    }
+
+    #[test]
+    fn test_resolve_link_by_title() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-title-test");
+        let _ = fs::remove_dir_all(&vault_dir);
+        fs::create_dir_all(&vault_dir).unwrap();
+        fs::write(vault_dir.join("a.md"), "").unwrap();
+        let page = |file: &str, title: &str| Page {
+            file: vault_dir.join(file),
+            title: title.to_string(),
+            aliases: Vec::new(),
+        };
+        let pages = vec![
+            page("notes/one.md", "My Note Title"),
+            page("notes/two.md", "Shared"),
+            page("notes/three.md", "shared"),
+        ];
+
+        assert_eq!(
+            resolve_link(&vault_dir, "a", &pages),
+            Some(vault_dir.join("a.md"))
+        );
+        assert_eq!(
+            resolve_link(&vault_dir, "my note title", &pages),
+            Some(vault_dir.join("notes/one.md"))
+        );
+        assert_eq!(resolve_link(&vault_dir, "Shared", &pages), None);
+        assert_eq!(pages_named(&pages, " SHARED ").len(), 2);
+
+        let _ = fs::remove_dir_all(&vault_dir);
+    }
+
+    #[test]
+    fn test_resolve_link_by_alias() {
+        let vault_dir = std::env::temp_dir().join("notemancy-lsp-alias-test");
+        let mut aliases = HashMap::new();
+        aliases.insert(
+            vault_dir.join("people/ada.md"),
+            vec!["AKA".to_string(), "Countess".to_string()],
+        );
+        aliases.insert(vault_dir.join("new.md"), vec!["Fresh".to_string()]);
+        let pages = with_aliases(
+            vec![Page {
+                file: vault_dir.join("people/ada.md"),
+                title: "Ada Lovelace".to_string(),
+                aliases: Vec::new(),
+            }],
+            &aliases,
+        );
+        assert_eq!(pages.len(), 2);
+        assert_eq!(
+            resolve_link(&vault_dir, "countess", &pages),
+            Some(vault_dir.join("people/ada.md"))
+        );
+        assert_eq!(
+            resolve_link(&vault_dir, "Fresh", &pages),
+            Some(vault_dir.join("new.md"))
+        );
+    }
+}
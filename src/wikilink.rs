@@ -0,0 +1,438 @@
+use std::ops::Range;
+
+use serde::Deserialize;
+
+use crate::markdown_context;
+
+/// A `[[path#anchor | alias]]` or `[[path^blockid]]` link found in a document.
+/// All ranges are byte offsets into the document text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WikiLink {
+    /// The whole link, including the `[[` and `]]` delimiters.
+    pub range: Range<usize>,
+    /// The link target with surrounding whitespace removed.
+    pub path: String,
+    /// Where `path` sits in the document.
+    pub path_range: Range<usize>,
+    /// The heading anchor after `#`, if present.
+    pub anchor: Option<String>,
+    /// The block identifier after `^`, if present.
+    pub block: Option<String>,
+    /// The display text, on the other side of `|` from the path, if present.
+    pub alias: Option<String>,
+    /// Where `alias` sits in the document.
+    pub alias_range: Option<Range<usize>>,
+    /// Whether the link is an embed, written `![[path]]`. The `!` stays outside `range`.
+    pub embed: bool,
+}
+
+/// File extensions of the images a wiki-link can point at.
+const IMAGE_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "gif", "svg", "webp"];
+
+/// Returns true if a link path names an image file rather than a note.
+pub fn is_image(path: &str) -> bool {
+    std::path::Path::new(path).extension().is_some_and(|ext| {
+        IMAGE_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str())
+    })
+}
+
+/// Which side of a link's `|` holds the path and which the display text.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkOrder {
+    /// `[[notes/rust | Rust]]`
+    #[default]
+    PathFirst,
+    /// `[[Rust | notes/rust]]`
+    AliasFirst,
+}
+
+/// The strings that open and close a wiki-link, `[[` and `]]` unless configured
+/// otherwise, and the order of the path and display text between them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Delimiters {
+    pub open: String,
+    pub close: String,
+    pub order: LinkOrder,
+}
+
+impl Default for Delimiters {
+    fn default() -> Self {
+        Delimiters {
+            open: "[[".to_string(),
+            close: "]]".to_string(),
+            order: LinkOrder::default(),
+        }
+    }
+}
+
+impl Delimiters {
+    /// Builds a pair from the settings, falling back to the default for an empty side.
+    pub fn new(open: &str, close: &str) -> Self {
+        let default = Delimiters::default();
+        Delimiters {
+            open: if open.is_empty() {
+                default.open
+            } else {
+                open.to_string()
+            },
+            close: if close.is_empty() {
+                default.close
+            } else {
+                close.to_string()
+            },
+            order: default.order,
+        }
+    }
+
+    /// Writes `inner` as a link, between the delimiters.
+    pub fn wrap(&self, inner: &str) -> String {
+        format!("{}{}{}", self.open, inner, self.close)
+    }
+
+    /// What goes between the delimiters of a link to `path` showing `alias`, the two in
+    /// the configured order.
+    pub fn inner(&self, path: &str, alias: Option<&str>) -> String {
+        match (alias, self.order) {
+            (None, _) => path.to_string(),
+            (Some(alias), LinkOrder::PathFirst) => format!("{} | {}", path, alias),
+            (Some(alias), LinkOrder::AliasFirst) => format!("{} | {}", alias, path),
+        }
+    }
+
+    /// Writes a link to `path` showing `alias`.
+    pub fn link(&self, path: &str, alias: Option<&str>) -> String {
+        self.wrap(&self.inner(path, alias))
+    }
+}
+
+/// Finds every wiki-link in `text` written with `delimiters`. A link must open and close
+/// on the same line, and brackets inside fenced code or inline code are literal text
+/// rather than links.
+pub fn find_wikilinks(text: &str, delimiters: &Delimiters) -> Vec<WikiLink> {
+    let code_ranges = markdown_context::code_ranges(text);
+    let mut links = Vec::new();
+    let mut pos = 0;
+    while let Some(found) = text[pos..].find(&delimiters.open) {
+        let start = pos + found;
+        let inner_start = start + delimiters.open.len();
+        let line_end = text[inner_start..]
+            .find('\n')
+            .map_or(text.len(), |i| inner_start + i);
+        let inner_end = match find_close(&text[inner_start..line_end], delimiters) {
+            Close::At(close) => inner_start + close,
+            // A second opening delimiter before the closing one means the first one was
+            // never closed.
+            Close::Reopened(reopen) => {
+                pos = inner_start + reopen;
+                continue;
+            }
+            Close::Missing => {
+                pos = inner_start;
+                continue;
+            }
+        };
+        if !code_ranges.iter().any(|code| code.contains(&start)) {
+            links.push(parse_link(text, start, inner_start, inner_end, delimiters));
+        }
+        pos = inner_end + delimiters.close.len();
+    }
+    links
+}
+
+/// Where the delimiter closing a wiki-link is, relative to the text after its opening.
+enum Close {
+    At(usize),
+    Reopened(usize),
+    Missing,
+}
+
+/// Scans for the delimiter that closes a wiki-link. Single brackets inside the link, such
+/// as a footnote-style `[1]` in an alias, are balanced first, and backslash-escaped
+/// brackets are skipped, so `[[note | See [1]]]` closes on its last two brackets. For
+/// other delimiters the brackets balanced are their first and last characters, unless
+/// those are the same character. Symmetric delimiters such as `%%` close on their next
+/// occurrence, since a second opening can't be told apart from the close.
+fn find_close(rest: &str, delimiters: &Delimiters) -> Close {
+    let symmetric = delimiters.open == delimiters.close;
+    let mut open = delimiters.open.chars().next();
+    let mut close = delimiters.close.chars().last();
+    if open == close {
+        (open, close) = (None, None);
+    }
+    let mut depth = 0;
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if !symmetric && depth == 0 && rest[i..].starts_with(&delimiters.open) {
+            return Close::Reopened(i);
+        } else if Some(c) == open {
+            depth += 1;
+        } else if Some(c) == close && depth > 0 {
+            depth -= 1;
+        } else if rest[i..].starts_with(&delimiters.close) {
+            return Close::At(i);
+        }
+    }
+    Close::Missing
+}
+
+/// Returns the wiki-link whose brackets contain the byte `offset`, if any.
+pub fn wikilink_at(text: &str, offset: usize, delimiters: &Delimiters) -> Option<WikiLink> {
+    find_wikilinks(text, delimiters)
+        .into_iter()
+        .find(|link| link.range.contains(&offset))
+}
+
+/// If `prefix` (the text of a line up to the cursor) ends inside an unclosed link,
+/// returns the byte index just after its opening delimiter.
+pub fn open_link_start(prefix: &str, delimiters: &Delimiters) -> Option<usize> {
+    if delimiters.open == delimiters.close {
+        // The last delimiter could be a close, so pair them up from the start of the line.
+        let mut pos = 0;
+        while let Some(found) = prefix[pos..].find(&delimiters.open) {
+            let start = pos + found + delimiters.open.len();
+            match find_close(&prefix[start..], delimiters) {
+                Close::At(close) => pos = start + close + delimiters.close.len(),
+                _ => return Some(start),
+            }
+        }
+        return None;
+    }
+    let start = prefix.rfind(&delimiters.open)? + delimiters.open.len();
+    match find_close(&prefix[start..], delimiters) {
+        Close::At(_) => None,
+        _ => Some(start),
+    }
+}
+
+/// Normalizes heading text for anchor comparison: lowercased, with runs of
+/// whitespace collapsed to a single space.
+pub fn normalize_heading(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn parse_link(
+    text: &str,
+    start: usize,
+    inner_start: usize,
+    inner_end: usize,
+    delimiters: &Delimiters,
+) -> WikiLink {
+    let inner = &text[inner_start..inner_end];
+    // The target and where it starts, and the display text and where it starts.
+    let (target, target_start, display) = match inner.find('|') {
+        Some(i) => {
+            let (before, after) = (&inner[..i], &inner[i + 1..]);
+            match delimiters.order {
+                LinkOrder::PathFirst => (before, inner_start, Some((after, inner_start + i + 1))),
+                LinkOrder::AliasFirst => (after, inner_start + i + 1, Some((before, inner_start))),
+            }
+        }
+        None => (inner, inner_start, None),
+    };
+    let (alias, alias_range) = match display {
+        Some((raw, raw_start)) => {
+            let alias = raw.trim();
+            let alias_start = raw_start + (raw.len() - raw.trim_start().len());
+            (
+                Some(alias.to_string()),
+                Some(alias_start..alias_start + alias.len()),
+            )
+        }
+        None => (None, None),
+    };
+    let (target, block) = match target.rfind('^') {
+        Some(i) => (&target[..i], Some(target[i + 1..].trim().to_string())),
+        None => (target, None),
+    };
+    let (path_part, anchor) = match target.find('#') {
+        Some(i) => (&target[..i], Some(target[i + 1..].trim().to_string())),
+        None => (target, None),
+    };
+    let path = path_part.trim();
+    let path_start = target_start + (path_part.len() - path_part.trim_start().len());
+    WikiLink {
+        range: start..inner_end + delimiters.close.len(),
+        path: path.to_string(),
+        path_range: path_start..path_start + path.len(),
+        anchor,
+        block,
+        alias,
+        alias_range,
+        embed: text[..start].ends_with('!'),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_wikilinks() {
+        let text = "See [[notes/rust.md#Error Handling | errors]] and [[todo]].\n[[broken\n]]";
+        let links = find_wikilinks(text, &Delimiters::default());
+        assert_eq!(links.len(), 2);
+
+        assert_eq!(links[0].path, "notes/rust.md");
+        assert_eq!(&text[links[0].path_range.clone()], "notes/rust.md");
+        assert_eq!(links[0].anchor.as_deref(), Some("Error Handling"));
+        assert_eq!(links[0].alias.as_deref(), Some("errors"));
+        assert_eq!(&text[links[0].alias_range.clone().unwrap()], "errors");
+
+        assert_eq!(&text[links[1].range.clone()], "[[todo]]");
+        assert_eq!(links[1].anchor, None);
+        assert_eq!(links[1].alias, None);
+    }
+
+    #[test]
+    fn test_custom_delimiters() {
+        let braces = Delimiters::new("{{", "}}");
+        let text = "See {{notes/a#Part | A {1}}} and {{b}}, not [[c]]. {{open";
+        let links = find_wikilinks(text, &braces);
+        let paths: Vec<_> = links.iter().map(|link| link.path.as_str()).collect();
+        assert_eq!(paths, vec!["notes/a", "b"]);
+        assert_eq!(&text[links[0].range.clone()], "{{notes/a#Part | A {1}}}");
+        assert_eq!(links[0].alias.as_deref(), Some("A {1}"));
+        assert_eq!(&text[links[1].range.clone()], "{{b}}");
+
+        assert_eq!(open_link_start("x {{gui", &braces), Some(4));
+        assert_eq!(open_link_start("x {{b}} y", &braces), None);
+        assert_eq!(open_link_start("x [[gui", &braces), None);
+        assert_eq!(braces.wrap("b"), "{{b}}");
+        assert_eq!(Delimiters::new("", ""), Delimiters::default());
+    }
+
+    #[test]
+    fn test_symmetric_delimiters() {
+        let percent = Delimiters::new("%%", "%%");
+        let text = "See %%notes/a | A%% and %%b%%, not [[c]]. %%open";
+        let links = find_wikilinks(text, &percent);
+        let paths: Vec<_> = links.iter().map(|link| link.path.as_str()).collect();
+        assert_eq!(paths, vec!["notes/a", "b"]);
+        assert_eq!(&text[links[0].range.clone()], "%%notes/a | A%%");
+        assert_eq!(links[0].alias.as_deref(), Some("A"));
+
+        assert_eq!(open_link_start("x %%gui", &percent), Some(4));
+        assert_eq!(open_link_start("x %%b%% y", &percent), None);
+    }
+
+    #[test]
+    fn test_link_order() {
+        let text = "[[notes/rust#Traits | Rust traits]] [[todo]]";
+        let alias_first = Delimiters {
+            order: LinkOrder::AliasFirst,
+            ..Delimiters::default()
+        };
+        let links = find_wikilinks(text, &Delimiters::default());
+        assert_eq!(links[0].path, "notes/rust");
+        assert_eq!(links[0].alias.as_deref(), Some("Rust traits"));
+
+        let text = "[[Rust traits | notes/rust#Traits]] [[todo]]";
+        let links = find_wikilinks(text, &alias_first);
+        assert_eq!(links[0].path, "notes/rust");
+        assert_eq!(&text[links[0].path_range.clone()], "notes/rust");
+        assert_eq!(links[0].anchor.as_deref(), Some("Traits"));
+        assert_eq!(links[0].alias.as_deref(), Some("Rust traits"));
+        assert_eq!(&text[links[0].alias_range.clone().unwrap()], "Rust traits");
+        assert_eq!(links[1].path, "todo");
+        assert_eq!(links[1].alias, None);
+
+        assert_eq!(
+            alias_first.link("notes/rust", Some("Rust")),
+            "[[Rust | notes/rust]]"
+        );
+        assert_eq!(
+            Delimiters::default().link("notes/rust", Some("Rust")),
+            "[[notes/rust | Rust]]"
+        );
+        assert_eq!(alias_first.link("todo", None), "[[todo]]");
+    }
+
+    #[test]
+    fn test_embeds() {
+        let text = "[[diagram.png]] ![[diagram.png]] ![[note]] !x[[note]]";
+        let links = find_wikilinks(text, &Delimiters::default());
+        let embeds: Vec<_> = links.iter().map(|link| link.embed).collect();
+        assert_eq!(embeds, vec![false, true, true, false]);
+        assert_eq!(&text[links[1].range.clone()], "[[diagram.png]]");
+        assert!(is_image(&links[1].path));
+        assert!(is_image("photos/Cat.JPG"));
+        assert!(!is_image(&links[2].path));
+    }
+
+    #[test]
+    fn test_wikilink_at_and_open_link_start() {
+        let text = "a [[one]] b [[two]]";
+        assert_eq!(
+            wikilink_at(text, 5, &Delimiters::default()).unwrap().path,
+            "one"
+        );
+        assert_eq!(wikilink_at(text, 10, &Delimiters::default()), None);
+        assert_eq!(
+            wikilink_at(text, 14, &Delimiters::default()).unwrap().path,
+            "two"
+        );
+
+        assert_eq!(
+            open_link_start("x [[guide#", &Delimiters::default()),
+            Some(4)
+        );
+        assert_eq!(
+            open_link_start("x [[guide]] y", &Delimiters::default()),
+            None
+        );
+        assert_eq!(normalize_heading("  Error   HANDLING "), "error handling");
+    }
+
+    #[test]
+    fn test_block_reference() {
+        let links = find_wikilinks(
+            "[[journal/today^idea-1]] [[a#Heading^b2 | alias]]",
+            &Delimiters::default(),
+        );
+        assert_eq!(links[0].path, "journal/today");
+        assert_eq!(links[0].block.as_deref(), Some("idea-1"));
+        assert_eq!(links[0].anchor, None);
+        assert_eq!(links[1].anchor.as_deref(), Some("Heading"));
+        assert_eq!(links[1].block.as_deref(), Some("b2"));
+    }
+
+    #[test]
+    fn test_brackets_inside_alias() {
+        let text = "[[note | See [1]]] and [[other | a \\]] b]] [[x | [a] [b]]]";
+        let links = find_wikilinks(text, &Delimiters::default());
+        assert_eq!(links.len(), 3);
+        assert_eq!(&text[links[0].range.clone()], "[[note | See [1]]]");
+        assert_eq!(links[0].alias.as_deref(), Some("See [1]"));
+        assert_eq!(links[1].alias.as_deref(), Some("a \\]] b"));
+        assert_eq!(links[2].alias.as_deref(), Some("[a] [b]"));
+
+        assert_eq!(
+            open_link_start("[[note | See [1]", &Delimiters::default()),
+            Some(2)
+        );
+        assert_eq!(
+            open_link_start("[[note | See [1]]]", &Delimiters::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_links_in_code_are_ignored() {
+        let text = "[[real]] `[[inline]]`\n```\n[[fenced]]\n```\n";
+        let paths: Vec<_> = find_wikilinks(text, &Delimiters::default())
+            .into_iter()
+            .map(|l| l.path)
+            .collect();
+        assert_eq!(paths, vec!["real"]);
+        assert_eq!(
+            wikilink_at(text, text.find("inline").unwrap(), &Delimiters::default()),
+            None
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Runs the server binary over stdio and formats a document through the protocol.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use serde_json::{json, Value};
+
+/// A running server and the pipes to talk to it.
+struct Server {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Server {
+    fn start() -> Self {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_notemancy-lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("failed to start the server");
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Server {
+            child,
+            stdin,
+            stdout,
+        }
+    }
+
+    /// Writes `message` as a framed JSON-RPC message.
+    fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    /// Reads messages until the response to request `id`, skipping notifications.
+    fn response(&mut self, id: i64) -> Value {
+        loop {
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                self.stdout.read_line(&mut line).unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some(n) = line.strip_prefix("Content-Length: ") {
+                    length = n.parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            self.stdout.read_exact(&mut body).unwrap();
+            let message: Value = serde_json::from_slice(&body).unwrap();
+            if message["id"] == id {
+                return message;
+            }
+        }
+    }
+
+    /// Sends request `id` and waits for its response.
+    fn request(&mut self, id: i64, method: &str, params: Value) -> Value {
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        self.response(id)
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    /// Shuts the server down. Its stdin is closed too, since the runtime only finishes
+    /// once the pending read of stdin does.
+    fn stop(mut self) {
+        self.request(99, "shutdown", Value::Null);
+        self.notify("exit", Value::Null);
+        let Server { mut child, stdin, .. } = self;
+        drop(stdin);
+        child.wait().unwrap();
+    }
+}
+
+#[test]
+fn test_formatting() {
+    let mut server = Server::start();
+    let initialized = server.request(1, "initialize", json!({ "capabilities": {} }));
+    assert_eq!(
+        initialized["result"]["capabilities"]["documentFormattingProvider"],
+        true
+    );
+
+    let uri = "untitled:Format-1";
+    server.notify(
+        "textDocument/didOpen",
+        json!({ "textDocument": {
+            "uri": uri,
+            "languageId": "markdown",
+            "version": 1,
+            "text": "#Heading\nSome text   \n\n\n\n- item\n* other\nMore",
+        } }),
+    );
+    let response = server.request(
+        2,
+        "textDocument/formatting",
+        json!({
+            "textDocument": { "uri": uri },
+            "options": { "tabSize": 4, "insertSpaces": true },
+        }),
+    );
+    let edits = response["result"].as_array().expect("formatting edits");
+    assert_eq!(edits.len(), 1);
+    assert_eq!(
+        edits[0]["newText"],
+        "# Heading\n\nSome text\n\n- item\n- other\nMore\n"
+    );
+    assert_eq!(
+        edits[0]["range"],
+        json!({
+            "start": { "line": 0, "character": 0 },
+            "end": { "line": 7, "character": 4 },
+        })
+    );
+
+    server.stop();
+}